@@ -0,0 +1,86 @@
+//! Derives the constants that can't be computed in a `const` context (they need `powf`, `exp` or `sqrt`)
+//! from their formulas, in double precision, and writes them to `$OUT_DIR` for `src/consts.rs` to include.
+//!
+//! The formulas are repeated here because a build script can't use the crate it builds;
+//! the tests in `src/consts.rs` check the results against the crate's own conversions.
+use std::f64::consts::PI;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::{env, fs};
+
+const D65_XYZ: [f64; 3] = [95.047, 100.0, 108.883];
+
+// the CIECAM02 viewing conditions: average surround, a 64 lux adapting field, and a 20% grey background
+const LA: f64 = (64.0 / PI) / 5.0;
+const YB: f64 = 20.0;
+const F: f64 = 1.0;
+const N: f64 = YB / D65_XYZ[1];
+const K: f64 = 1.0 / ((5.0 * LA) + 1.0);
+
+/// The CAT02 transform from CIEXYZ into LMS.
+fn xyz_to_lms([x, y, z]: [f64; 3]) -> [f64; 3] {
+    [
+        (0.7328 * x) + (0.4296 * y) - (0.1624 * z),
+        (-0.7036 * x) + (1.6975 * y) + (0.0061 * z),
+        (0.0030 * x) + (0.0136 * y) + (0.9834 * z),
+    ]
+}
+
+/// CAT02 LMS into Hunt-Pointer-Estevez cone space.
+fn lms_to_hpe([l, m, s]: [f64; 3]) -> [f64; 3] {
+    [
+        (0.7409792 * l) + (0.218025 * m) + (0.0410058 * s),
+        (0.2853532 * l) + (0.6242014 * m) + (0.0904454 * s),
+        (-0.0096280 * l) - (0.0056980 * m) + (1.015326 * s),
+    ]
+}
+
+fn nonlinear_adaptation(cone_response: f64, fl: f64) -> f64 {
+    let p = ((fl * cone_response.abs()) / 100.0).powf(0.42);
+    ((400.0 * p) / (27.13 + p)).copysign(cone_response) + 0.1
+}
+
+fn linearize(c: f64) -> f64 {
+    if c > 0.04045 {
+        ((c + 0.055) / 1.055).powf(2.4)
+    } else {
+        c / 12.92
+    }
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let d65_lms = xyz_to_lms(D65_XYZ);
+    let z = 1.48 + N.sqrt();
+    let fl = (0.2 * K.powi(4) * (5.0 * LA))
+        + 0.1 * ((1.0 - K.powi(4)).powi(2)) * (5.0 * LA).powf(1.0 / 3.0);
+    let nbb = 0.725 * (1.0 / N).powf(0.2);
+    let d = F * (1.0 - (1.0 / 3.6) * ((-LA - 42.0) / 92.0).exp());
+    let achromatic_response_to_white = {
+        let adapted = d65_lms.map(|cone| cone * (((D65_XYZ[1] * d) / cone) + (1.0 - d)));
+        let [lpa, mpa, spa] = lms_to_hpe(adapted).map(|cone| nonlinear_adaptation(cone, fl));
+        (2.0 * lpa + mpa + 0.05 * spa - 0.305) * nbb
+    };
+
+    let mut derived = String::new();
+    writeln!(derived, "pub const D65_LMS: [f64; 3] = {:?};", d65_lms).unwrap();
+    for (name, value) in [
+        ("z", z),
+        ("fl", fl),
+        ("nbb", nbb),
+        ("d", d),
+        ("achromatic_response_to_white", achromatic_response_to_white),
+    ] {
+        writeln!(derived, "pub const {}: f64 = {:?};", name, value).unwrap();
+    }
+
+    let lookup: Vec<String> = (0..=255u8)
+        .map(|code| format!("{:?}", linearize(code as f64 / 255.0) as f32))
+        .collect();
+    let lookup = format!("[{}]\n", lookup.join(", "));
+
+    let out = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out).join("derived.rs"), derived).unwrap();
+    fs::write(Path::new(&out).join("srgb_lookup.rs"), lookup).unwrap();
+}
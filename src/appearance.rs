@@ -0,0 +1,312 @@
+//! Every CIECAM02 correlate of a color, including the ones [`JCh`] leaves out: the opponent
+//! coordinates and achromatic response the model computes on the way, the hue composition,
+//! and the cartesian forms of lightness, brightness or saturation with a hue.
+//!
+//! ```
+//! use kasi_kule::appearance::CamAppearance;
+//!
+//! let orange = CamAppearance::from([255, 128, 0]);
+//! assert_eq!(orange.hue_composition().to_string(), "52 R 48 Y");
+//! ```
+use core::fmt;
+
+use crate::consts::ViewingConditions;
+use crate::utils::wrap_hue;
+use crate::{sRGB, Float, JCh, LMS};
+
+/// The CIECAM02 correlates of a color, derived from LMS.
+#[derive(Default, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CamAppearance<F: Float = f32> {
+    /// Lightness.
+    pub J: F,
+    /// Chroma.
+    pub C: F,
+    /// Hue angle, in degrees.
+    pub h: F,
+    /// Hue quadrature, from 0 to 400, with the unique hues at multiples of 100.
+    pub H: F,
+    /// Brightness.
+    pub Q: F,
+    /// Colorfulness.
+    pub M: F,
+    /// Saturation.
+    pub s: F,
+    /// The achromatic response.
+    pub A: F,
+    /// The red-green opponent coordinate.
+    pub ca: F,
+    /// The yellow-blue opponent coordinate.
+    pub cb: F,
+}
+
+impl<F: Float> CamAppearance<F> {
+    /// The correlates, from the cone responses once they've been adapted and compressed
+    /// in the viewing conditions `vc`.
+    pub(crate) fn from_adapted_responses(
+        [lpa, mpa, spa]: [F; 3],
+        vc: &ViewingConditions<F>,
+    ) -> CamAppearance<F> {
+        let c = F::from_f64;
+
+        let ca = lpa - ((c(12.0) * mpa) / c(11.0)) + (spa / c(11.0));
+        let cb = c(1.0 / 9.0) * (lpa + mpa - c(2.0) * spa);
+
+        let h = wrap_hue((c(180.0) / F::PI) * cb.atan2(ca));
+
+        let A = (c(2.0) * lpa + mpa + c(0.05) * spa - c(0.305)) * vc.nbb;
+        let J = c(100.0) * (A / vc.achromatic_response_to_white).powf(vc.c * vc.z);
+
+        let et = c(0.25) * (((h * F::PI) / c(180.0) + c(2.0)).cos() + c(3.8));
+        let t = c(50000.0 / 13.0) * vc.nc * vc.ncb * et * (ca.powi(2) + cb.powi(2)).sqrt()
+            / (lpa + mpa + c(21.0 / 20.0) * spa);
+
+        let C =
+            t.powf(c(0.9)) * (J / c(100.0)).sqrt() * (c(1.64) - c(0.29).powf(vc.n)).powf(c(0.73));
+
+        let JCh {
+            J,
+            C,
+            h,
+            H,
+            Q,
+            M,
+            s,
+        } = JCh::with_conditions(J, C, h, vc);
+        CamAppearance {
+            J,
+            C,
+            h,
+            H,
+            Q,
+            M,
+            s,
+            A,
+            ca,
+            cb,
+        }
+    }
+
+    pub fn from_srgb(rgb: impl Into<sRGB>) -> CamAppearance<F> {
+        CamAppearance::from(&LMS::from_srgb(rgb))
+    }
+
+    /// The hue quadrature as the shares of the two unique hues the hue lies between.
+    pub fn hue_composition(&self) -> HueComposition<F> {
+        HueComposition::new(self.H)
+    }
+
+    /// Lightness, and chroma as cartesian coordinates: `[J, a_C, b_C]`, as in [`Jab<CartesianC>`](crate::CartesianC).
+    pub fn jab(&self) -> [F; 3] {
+        let [a, b] = self.cartesian(self.C);
+        [self.J, a, b]
+    }
+
+    /// Lightness, and saturation as cartesian coordinates: `[J, a_s, b_s]`, as in [`Jab<CartesianS>`](crate::CartesianS).
+    pub fn jsh(&self) -> [F; 3] {
+        let [a, b] = self.cartesian(self.s);
+        [self.J, a, b]
+    }
+
+    /// Brightness, and colorfulness as cartesian coordinates: `[Q, a_M, b_M]`.
+    pub fn qmh(&self) -> [F; 3] {
+        let [a, b] = self.cartesian(self.M);
+        [self.Q, a, b]
+    }
+
+    fn cartesian(&self, radius: F) -> [F; 2] {
+        let h = (F::PI / F::from_f64(180.0)) * self.h;
+        [radius * h.cos(), radius * h.sin()]
+    }
+}
+
+/// As with [`JCh`], a negative achromatic response gives NaN for J and the correlates derived from it.
+impl<F: Float> From<&LMS<F>> for CamAppearance<F> {
+    fn from(lms: &LMS<F>) -> CamAppearance<F> {
+        let vc = F::VIEWING_CONDITIONS;
+        CamAppearance::from_adapted_responses(crate::adapted_responses(lms, &vc), &vc)
+    }
+}
+
+impl<T: Into<sRGB>> From<T> for CamAppearance {
+    fn from(rgb: T) -> CamAppearance {
+        CamAppearance::from_srgb(rgb)
+    }
+}
+
+impl<F: Float> From<&CamAppearance<F>> for JCh<F> {
+    fn from(appearance: &CamAppearance<F>) -> JCh<F> {
+        JCh {
+            J: appearance.J,
+            C: appearance.C,
+            H: appearance.H,
+            h: appearance.h,
+            Q: appearance.Q,
+            M: appearance.M,
+            s: appearance.s,
+        }
+    }
+}
+
+/// One of the four unique hues, which hue quadrature is measured between.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UniqueHue {
+    Red,
+    Yellow,
+    Green,
+    Blue,
+}
+
+impl UniqueHue {
+    /// In order of hue quadrature, with red at both 0 and 400.
+    const ORDER: [UniqueHue; 5] = [
+        UniqueHue::Red,
+        UniqueHue::Yellow,
+        UniqueHue::Green,
+        UniqueHue::Blue,
+        UniqueHue::Red,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            UniqueHue::Red => "red",
+            UniqueHue::Yellow => "yellow",
+            UniqueHue::Green => "green",
+            UniqueHue::Blue => "blue",
+        }
+    }
+
+    /// The initial used in hue compositions, like the `Y` and `G` of "70 Y 30 G".
+    pub fn initial(self) -> char {
+        match self {
+            UniqueHue::Red => 'R',
+            UniqueHue::Yellow => 'Y',
+            UniqueHue::Green => 'G',
+            UniqueHue::Blue => 'B',
+        }
+    }
+}
+
+/// A hue as percentages of the two unique hues it lies between, which add up to 100.
+/// It displays as in CIE 159, like "70 Y 30 G", leaving out a hue whose share rounds to 0.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HueComposition<F: Float = f32> {
+    /// The unique hue the hue follows, and then the one it precedes, with their shares.
+    pub components: [(UniqueHue, F); 2],
+}
+
+impl<F: Float> HueComposition<F> {
+    /// The composition of a hue quadrature H, from 0 to 400.
+    pub fn new(H: F) -> HueComposition<F> {
+        let H = H.to_f64().clamp(0.0, 400.0);
+        let i = ((H / 100.0) as usize).min(3);
+        let share = H - 100.0 * i as f64;
+        HueComposition {
+            components: [
+                (UniqueHue::ORDER[i], F::from_f64(100.0 - share)),
+                (UniqueHue::ORDER[i + 1], F::from_f64(share)),
+            ],
+        }
+    }
+}
+
+impl<F: Float> fmt::Display for HueComposition<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        for (hue, share) in self.components {
+            let share = round_share(share.to_f64());
+            if share == 0.0 {
+                continue;
+            }
+            if !first {
+                f.write_str(" ")?;
+            }
+            write!(f, "{} {}", share, hue.initial())?;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+/// Rounds a share, from 0 to 100, to the nearest whole percent.
+fn round_share(share: f64) -> f64 {
+    (share + 0.5) as u64 as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn correlates_match_jch() {
+        for rgb in [[255, 0, 0], [12, 200, 90], [40, 40, 40], [255, 255, 255]] {
+            let appearance = CamAppearance::from(rgb);
+            let jch = JCh::from(rgb);
+            let from_appearance = JCh::from(&appearance);
+            assert_eq!(
+                [jch.J, jch.C, jch.h, jch.H, jch.Q, jch.M, jch.s],
+                [
+                    from_appearance.J,
+                    from_appearance.C,
+                    from_appearance.h,
+                    from_appearance.H,
+                    from_appearance.Q,
+                    from_appearance.M,
+                    from_appearance.s
+                ]
+            );
+
+            // the opponent coordinates point along the hue angle
+            let h = appearance
+                .cb
+                .atan2(appearance.ca)
+                .to_degrees()
+                .rem_euclid(360.0);
+            assert!((h - appearance.h).abs() < 1e-3 || appearance.C < 1.0);
+        }
+
+        // the achromatic response of the white point is the one lightness is relative to
+        let white = CamAppearance::from([255, 255, 255]);
+        assert!((white.A - crate::consts::VC::achromatic_response_to_white).abs() < 1e-3);
+    }
+
+    #[test]
+    fn cartesian_forms() {
+        let red = CamAppearance::from([255, 0, 0]);
+        let [J, a, b] = red.jab();
+        assert_eq!(J, red.J);
+        assert!(((a * a + b * b).sqrt() - red.C).abs() < 1e-3);
+        assert!(a > 0.0 && b > 0.0);
+
+        let [J, a, b] = red.jsh();
+        assert_eq!(J, red.J);
+        assert!(((a * a + b * b).sqrt() - red.s).abs() < 1e-3);
+
+        let [Q, a, b] = red.qmh();
+        assert_eq!(Q, red.Q);
+        assert!(((a * a + b * b).sqrt() - red.M).abs() < 1e-3);
+        assert!((b / a - red.h.to_radians().tan()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn hue_compositions() {
+        assert_eq!(HueComposition::new(130.0f32).to_string(), "70 Y 30 G");
+        assert_eq!(HueComposition::new(0.0f32).to_string(), "100 R");
+        assert_eq!(HueComposition::new(300.0f32).to_string(), "100 B");
+        assert_eq!(HueComposition::new(350.0f32).to_string(), "50 B 50 R");
+        assert_eq!(HueComposition::new(399.9f32).to_string(), "100 R");
+        assert_eq!(
+            HueComposition::new(225.0f32).components,
+            [(UniqueHue::Green, 75.0), (UniqueHue::Blue, 25.0)]
+        );
+        assert_eq!(UniqueHue::Yellow.name(), "yellow");
+
+        // the sRGB primaries and secondaries
+        for (rgb, composition) in [([0, 255, 0], "G"), ([0, 0, 255], "B"), ([255, 255, 0], "Y")] {
+            let shown = CamAppearance::from(rgb).hue_composition().to_string();
+            assert!(shown.contains(composition), "{:?} is {}", rgb, shown);
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! The palette and image subcommands: palette extraction, contrast and color vision checks, and lightness ramps.
+use std::path::Path;
+
+use clap::ValueEnum;
+use kasi_kule::contrast::{apca, wcag_ratio, Level};
+use kasi_kule::cvd::{distinguishability, Deficiency, Model, Simulation};
+use kasi_kule::gradient::{Curve, Gradient, HueInterpolation, Interpolation};
+use kasi_kule::quantize::{quantize, Method};
+use kasi_kule::{sRGB, JCh, Jab, UCS};
+use serde_json::{json, Value};
+
+use crate::preview::{ansi, svg, Preview, Row, Swatch};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum PaletteMethod {
+    MedianCut,
+    KMeans,
+    Octree,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum SimulationModel {
+    Machado,
+    Brettel,
+    Vienot,
+}
+
+impl From<SimulationModel> for Model {
+    fn from(model: SimulationModel) -> Model {
+        match model {
+            SimulationModel::Machado => Model::Machado2009,
+            SimulationModel::Brettel => Model::Brettel1997,
+            SimulationModel::Vienot => Model::Vienot1999,
+        }
+    }
+}
+
+// iterations of k-means after the median cut it starts from
+const KMEANS_ITERATIONS: usize = 16;
+
+// the lightness J at the ends of a ramp, and the share of the color's chroma they keep
+const RAMP_LIGHTEST: (f32, f32) = (96.0, 0.15);
+const RAMP_DARKEST: (f32, f32) = (16.0, 0.6);
+
+fn jab_json(srgb: sRGB) -> Value {
+    let jab = Jab::<UCS>::from(srgb);
+    json!({ "J": jab.J, "a": jab.a, "b": jab.b })
+}
+
+fn render(rows: &[Row], preview: Preview, json: impl FnOnce() -> Value) -> String {
+    match preview {
+        Preview::Ansi => ansi(rows),
+        Preview::Json => format!("{}\n", json()),
+        Preview::Svg => svg(rows),
+    }
+}
+
+/// The main colors of an image, as clustered in CAM02-UCS, most common first.
+pub fn palette(
+    path: &Path,
+    colors: usize,
+    method: PaletteMethod,
+    preview: Preview,
+) -> Result<String, String> {
+    let image = image::open(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?
+        .to_rgba8();
+    // fully transparent pixels have no color to speak of
+    let pixels: Vec<sRGB> = image
+        .pixels()
+        .filter(|pixel| pixel[3] >= 128)
+        .map(|pixel| sRGB::from([pixel[0], pixel[1], pixel[2]]))
+        .collect();
+    if pixels.is_empty() {
+        return Err(format!("{}: no opaque pixels", path.display()));
+    }
+
+    let method = match method {
+        PaletteMethod::MedianCut => Method::MedianCut,
+        PaletteMethod::KMeans => Method::KMeans {
+            iterations: KMEANS_ITERATIONS,
+        },
+        PaletteMethod::Octree => Method::Octree,
+    };
+    let swatches = quantize::<UCS>(&pixels, colors, method);
+
+    let row = swatches
+        .iter()
+        .map(|swatch| Swatch {
+            srgb: swatch.srgb,
+            label: format!("{} {:.0}%", swatch.srgb, swatch.weight * 100.0),
+            weight: swatch.weight * swatches.len() as f32,
+        })
+        .collect();
+    Ok(render(&[("palette".to_string(), row)], preview, || {
+        swatches
+            .iter()
+            .map(|swatch| {
+                json!({
+                    "hex": swatch.srgb.to_string(),
+                    "ucs": { "J": swatch.color.J, "a": swatch.color.a, "b": swatch.color.b },
+                    "population": swatch.population,
+                    "weight": swatch.weight,
+                })
+            })
+            .collect()
+    }))
+}
+
+/// Settings for [`check`].
+pub struct Check {
+    pub background: sRGB,
+    pub severity: f32,
+    pub model: SimulationModel,
+    /// The smallest ΔE in CAM02-UCS for a pair to count as distinguishable.
+    pub threshold: f32,
+}
+
+const DEFICIENCIES: [(Deficiency, &str); 3] = [
+    (Deficiency::Protan, "protan"),
+    (Deficiency::Deutan, "deutan"),
+    (Deficiency::Tritan, "tritan"),
+];
+
+fn mark(pass: bool) -> &'static str {
+    if pass {
+        "pass"
+    } else {
+        "FAIL"
+    }
+}
+
+/// Checks each color's text contrast against the background, and whether each pair of colors stays
+/// distinguishable with every color vision deficiency.
+pub fn check(colors: &[sRGB], settings: &Check, preview: Preview) -> String {
+    let model = Model::from(settings.model);
+    let pairs: Vec<(usize, usize)> = (0..colors.len())
+        .flat_map(|i| (i + 1..colors.len()).map(move |j| (i, j)))
+        .collect();
+    let pair_differences: Vec<_> = pairs
+        .iter()
+        .map(|&(i, j)| distinguishability(colors[i], colors[j], settings.severity, model))
+        .collect();
+
+    let mut rows: Vec<Row> = vec![(
+        "normal".to_string(),
+        colors.iter().map(|srgb| Swatch::new(*srgb)).collect(),
+    )];
+    for (deficiency, name) in DEFICIENCIES {
+        let simulation = Simulation::new(deficiency, settings.severity, model);
+        rows.push((
+            name.to_string(),
+            colors
+                .iter()
+                .map(|srgb| Swatch::new(simulation.srgb(*srgb)))
+                .collect(),
+        ));
+    }
+
+    let json = || {
+        json!({
+            "background": settings.background.to_string(),
+            "colors": colors.iter().map(|srgb| {
+                let ratio: f32 = wcag_ratio(*srgb, settings.background);
+                json!({
+                    "hex": srgb.to_string(),
+                    "ucs": jab_json(*srgb),
+                    "wcag": ratio,
+                    "aa": Level::AA.passes(ratio, false),
+                    "aaa": Level::AAA.passes(ratio, false),
+                    "apca": apca::<f32>(*srgb, settings.background),
+                })
+            }).collect::<Vec<Value>>(),
+            "pairs": pairs.iter().zip(&pair_differences).map(|(&(i, j), difference)| {
+                json!({
+                    "a": colors[i].to_string(),
+                    "b": colors[j].to_string(),
+                    "normal": difference.normal,
+                    "protan": difference.protan,
+                    "deutan": difference.deutan,
+                    "tritan": difference.tritan,
+                    "distinguishable": difference.is_distinguishable(settings.threshold),
+                })
+            }).collect::<Vec<Value>>(),
+        })
+    };
+    if preview != Preview::Ansi {
+        return render(&rows, preview, json);
+    }
+
+    let mut out = ansi(&rows);
+    let background = settings.background;
+    out += &format!("\ncontrast on {}\n", background);
+    for srgb in colors {
+        let ratio: f32 = wcag_ratio(*srgb, background);
+        out += &format!(
+            "\x1b[48;2;{};{};{}m\x1b[38;2;{};{};{}m {} \x1b[0m  WCAG {:>5.2}:1  AA {}  AAA {}  APCA Lc {:>7.2}\n",
+            background.r,
+            background.g,
+            background.b,
+            srgb.r,
+            srgb.g,
+            srgb.b,
+            srgb,
+            ratio,
+            mark(Level::AA.passes(ratio, false)),
+            mark(Level::AAA.passes(ratio, false)),
+            apca::<f32>(*srgb, background),
+        );
+    }
+
+    out += &format!(
+        "\nΔE in CAM02-UCS, distinguishable from {}\n",
+        settings.threshold
+    );
+    for (&(i, j), difference) in pairs.iter().zip(&pair_differences) {
+        out += &format!(
+            "{} {}  normal {:>6.2}  protan {:>6.2}  deutan {:>6.2}  tritan {:>6.2}  {}\n",
+            colors[i],
+            colors[j],
+            difference.normal,
+            difference.protan,
+            difference.deutan,
+            difference.tritan,
+            mark(difference.is_distinguishable(settings.threshold)),
+        );
+    }
+    out
+}
+
+/// Steps from a light tint to a dark shade of a color, through the color itself, evenly spaced in CAM02-UCS.
+pub fn ramp(color: sRGB, steps: usize, preview: Preview) -> String {
+    let base = JCh::from(color);
+    let end = |(J, chroma): (f32, f32)| Jab::<UCS>::from(&JCh::new(J, base.C * chroma, base.h));
+    let mut stops = vec![end(RAMP_LIGHTEST)];
+    // colors lighter or darker than the ends would turn the ramp back on itself
+    if base.J < RAMP_LIGHTEST.0 && base.J > RAMP_DARKEST.0 {
+        stops.push(Jab::from(&base));
+    }
+    stops.push(end(RAMP_DARKEST));
+
+    let gradient = Gradient::new(
+        &stops,
+        Interpolation::JCh(HueInterpolation::Shorter),
+        Curve::MonotoneCubic,
+    );
+    let colors = gradient.steps(steps);
+
+    let row = colors.iter().map(|srgb| Swatch::new(*srgb)).collect();
+    render(&[("ramp".to_string(), row)], preview, || {
+        colors
+            .iter()
+            .map(|srgb| json!({ "hex": srgb.to_string(), "ucs": jab_json(*srgb) }))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn palettes() {
+        // three quarters red and a quarter blue, with a transparent stripe of green
+        let image = image::RgbaImage::from_fn(8, 8, |x, y| match (x, y) {
+            (_, 0) => image::Rgba([0, 255, 0, 0]),
+            (0..=5, _) => image::Rgba([220, 20, 30, 255]),
+            _ => image::Rgba([20, 40, 200, 255]),
+        });
+        let path =
+            std::env::temp_dir().join(format!("kasi-kule-palette-{}.png", std::process::id()));
+        image.save(&path).unwrap();
+
+        let json: Value = serde_json::from_str(
+            &palette(&path, 3, PaletteMethod::MedianCut, Preview::Json).unwrap(),
+        )
+        .unwrap();
+        let svg = palette(&path, 2, PaletteMethod::Octree, Preview::Svg).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let swatches = json.as_array().unwrap();
+        assert_eq!(swatches.len(), 2);
+        assert_eq!(swatches[0]["hex"], "#dc141e");
+        assert_eq!(swatches[0]["population"], 42);
+        assert_eq!(swatches[1]["hex"], "#1428c8");
+        assert!(svg.contains("fill=\"#dc141e\""));
+
+        assert!(palette(
+            Path::new("missing.png"),
+            3,
+            PaletteMethod::KMeans,
+            Preview::Ansi
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn ramps() {
+        let blue = sRGB::from([40, 90, 200]);
+        let json: Value = serde_json::from_str(&ramp(blue, 9, Preview::Json)).unwrap();
+        let steps = json.as_array().unwrap();
+        assert_eq!(steps.len(), 9);
+
+        // from light to dark
+        let lightness: Vec<f64> = steps
+            .iter()
+            .map(|step| step["ucs"]["J"].as_f64().unwrap())
+            .collect();
+        assert!(lightness.windows(2).all(|pair| pair[0] > pair[1]));
+        assert!(lightness[0] > 90.0 && lightness[8] < 25.0);
+
+        assert_eq!(ramp(blue, 5, Preview::Svg).matches("<rect").count(), 5);
+        // even from white, which only bounds the ramp
+        assert_eq!(
+            ramp(sRGB::from([255, 255, 255]), 9, Preview::Ansi)
+                .lines()
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn checks() {
+        let colors = [
+            sRGB::from([200, 40, 40]),
+            sRGB::from([90, 120, 40]),
+            sRGB::from([20, 20, 120]),
+        ];
+        let settings = Check {
+            background: sRGB::from([255, 255, 255]),
+            severity: 1.0,
+            model: SimulationModel::Machado,
+            threshold: 10.0,
+        };
+        let json: Value = serde_json::from_str(&check(&colors, &settings, Preview::Json)).unwrap();
+        assert_eq!(json["colors"].as_array().unwrap().len(), 3);
+        assert_eq!(json["colors"][2]["aaa"], true);
+
+        // red and green blur together with a red-green deficiency
+        let pairs = json["pairs"].as_array().unwrap();
+        assert_eq!(pairs.len(), 3);
+        assert_eq!(pairs[0]["distinguishable"], false);
+        assert_eq!(pairs[1]["distinguishable"], true);
+
+        let text = check(&colors, &settings, Preview::Ansi);
+        assert!(text.contains("deutan"));
+        // AAA for the red and the green, and the pair of them
+        assert_eq!(text.matches("FAIL").count(), 3, "{}", text);
+        let svg = check(&colors, &settings, Preview::Svg);
+        assert_eq!(svg.matches("<rect").count(), 12);
+    }
+}
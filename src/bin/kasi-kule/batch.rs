@@ -0,0 +1,204 @@
+//! Batches of colors on stdin and stdout, as lines of text, CSV or JSON.
+use clap::ValueEnum;
+use serde_json::Value;
+
+use crate::color::{Color, Format};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Input {
+    /// One record per line, with the colors of a pair separated by a tab
+    Lines,
+    /// One record per row: a field for each color, or 3 component fields for each color.
+    /// A header row is skipped
+    Csv,
+    /// An array of colors, or of arrays of colors for pairs
+    Json,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    Text,
+    Csv,
+    Json,
+}
+
+/// Splits a CSV row into fields, honouring double quotes.
+pub fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+        .iter()
+        .map(|field| field.trim().to_string())
+        .collect()
+}
+
+/// Joins fields into a CSV row, quoting where needed.
+pub fn csv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            let field = field.as_ref();
+            if field.contains([',', '"', '\n']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn csv_record(fields: &[String], from: Format, arity: usize) -> Result<Vec<Color>, String> {
+    if fields.len() == arity {
+        return fields.iter().map(|field| from.parse(field)).collect();
+    }
+    if fields.len() == arity * 3 && from != Format::Hex {
+        return fields
+            .chunks(3)
+            .map(|components| from.parse(&components.join(" ")))
+            .collect();
+    }
+    Err(format!(
+        "expected {} or {} fields, found {}",
+        arity,
+        arity * 3,
+        fields.len()
+    ))
+}
+
+/// Reads records of `arity` colors each, written in the format `from`.
+pub fn read(
+    input: Input,
+    text: &str,
+    from: Format,
+    arity: usize,
+) -> Result<Vec<Vec<Color>>, String> {
+    let lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    match input {
+        Input::Lines => lines
+            .map(|(n, line)| {
+                let colors: Vec<&str> = if arity == 1 {
+                    vec![line]
+                } else {
+                    line.split('\t').collect()
+                };
+                if colors.len() != arity {
+                    return Err(format!(
+                        "line {}: expected {} tab separated colors",
+                        n + 1,
+                        arity
+                    ));
+                }
+                colors
+                    .iter()
+                    .map(|color| from.parse(color))
+                    .collect::<Result<Vec<Color>, String>>()
+                    .map_err(|e| format!("line {}: {}", n + 1, e))
+            })
+            .collect(),
+        Input::Csv => {
+            let mut records = Vec::new();
+            for (i, (n, line)) in lines.enumerate() {
+                let fields = csv_fields(line);
+                match csv_record(&fields, from, arity) {
+                    Ok(record) => records.push(record),
+                    // a header names its columns, without any numbers
+                    Err(_) if i == 0 && !line.contains(|c: char| c.is_ascii_digit()) => {}
+                    Err(e) => return Err(format!("line {}: {}", n + 1, e)),
+                }
+            }
+            Ok(records)
+        }
+        Input::Json => {
+            let value: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+            let Value::Array(values) = value else {
+                return Err("expected a JSON array".to_string());
+            };
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| {
+                    let record = match (arity, value) {
+                        (1, value) => from.read_json(value).map(|color| vec![color]),
+                        (arity, Value::Array(colors)) if colors.len() == arity => {
+                            colors.iter().map(|color| from.read_json(color)).collect()
+                        }
+                        (arity, _) => Err(format!("expected an array of {} colors", arity)),
+                    };
+                    record.map_err(|e| format!("item {}: {}", i, e))
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            csv_fields(r#"#ff0000, "rgb(0, 128, 0)","say ""hi""""#),
+            vec!["#ff0000", "rgb(0, 128, 0)", r#"say "hi""#]
+        );
+        assert_eq!(
+            csv_row(&["a", "b,c", "d\"e"]),
+            r#"a,"b,c","d""e""#.to_string()
+        );
+    }
+
+    #[test]
+    fn reading() {
+        let hex = |color: &Color| Format::Hex.format(color).unwrap();
+
+        let lines = read(Input::Lines, "red\n\n#00ff00\n", Format::Hex, 1).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(hex(&lines[1][0]), "#00ff00");
+
+        let pairs = read(Input::Lines, "red\tblue\n", Format::Hex, 2).unwrap();
+        assert_eq!(hex(&pairs[0][1]), "#0000ff");
+        assert!(read(Input::Lines, "red blue\n", Format::Hex, 2).is_err());
+
+        // with a header, and with components
+        let csv = read(Input::Csv, "r,g,b\n255,0,0\n0,0,255\n", Format::Srgb, 1).unwrap();
+        assert_eq!(csv.len(), 2);
+        assert_eq!(hex(&csv[1][0]), "#0000ff");
+        let csv = read(Input::Csv, "a,b\nred,\"rgb(0, 0, 255)\"\n", Format::Hex, 2).unwrap();
+        assert_eq!(hex(&csv[0][1]), "#0000ff");
+        assert!(read(Input::Csv, "255,0\n", Format::Srgb, 1).is_err());
+
+        let json = read(
+            Input::Json,
+            r##"[["#ff0000", [0, 0, 255]], [{"r": 0, "g": 255, "b": 0}, "white"]]"##,
+            Format::Srgb,
+            2,
+        );
+        // strings aren't sRGB components
+        assert!(json.is_err());
+        let json = read(
+            Input::Json,
+            r#"[[255, 0, 0], {"r": 0, "g": 255, "b": 0}]"#,
+            Format::Srgb,
+            1,
+        )
+        .unwrap();
+        assert_eq!(hex(&json[1][0]), "#00ff00");
+    }
+}
@@ -0,0 +1,322 @@
+//! Reading and writing colors in each of the representations the tool supports.
+use clap::ValueEnum;
+use kasi_kule::css::CssColor;
+use kasi_kule::{sRGB, JCh, Jab, JabSpace, LinearRGB, LCD, LMS, SCD, UCS, XYZ};
+use serde_json::{Map, Value};
+
+/// Whatever representation a color came in as, it's carried as CIEXYZ (0-100, D65).
+pub type Color = XYZ<f64>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// `#rrggbb`; any CSS color on input
+    Hex,
+    /// 8-bit sRGB, 0-255
+    Srgb,
+    /// CIEXYZ, 0-100, D65
+    Xyz,
+    /// CAM02 lightness, chroma and hue angle
+    Jch,
+    /// CAM02-UCS
+    Ucs,
+    /// CAM02-LCD
+    Lcd,
+    /// CAM02-SCD
+    Scd,
+}
+
+/// The Jab space color differences are measured in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Space {
+    Ucs,
+    Lcd,
+    Scd,
+}
+
+fn to_jab<S: JabSpace>(color: &Color) -> [f64; 3] {
+    let jab = Jab::<S, f64>::from(&JCh::from(&LMS::from(color)));
+    [jab.J, jab.a, jab.b]
+}
+
+fn from_jab<S: JabSpace>([J, a, b]: [f64; 3]) -> Color {
+    XYZ::from(&LMS::from(&JCh::from(&Jab::<S, f64>::new_const(J, a, b))))
+}
+
+fn to_srgb(color: &Color) -> Result<sRGB, String> {
+    LinearRGB::from(color)
+        .to_srgb()
+        .ok_or_else(|| "color is outside the sRGB gamut".to_string())
+}
+
+/// Rounds to 4 decimal places, without trailing zeros.
+pub fn number(value: f64) -> String {
+    let rounded = format!("{:.4}", value);
+    let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" => "0".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+impl Format {
+    /// The names of the components, as used for CSV headers and JSON keys.
+    pub fn components(self) -> [&'static str; 3] {
+        match self {
+            Format::Hex | Format::Srgb => ["r", "g", "b"],
+            Format::Xyz => ["X", "Y", "Z"],
+            Format::Jch => ["J", "C", "h"],
+            Format::Ucs | Format::Lcd | Format::Scd => ["J", "a", "b"],
+        }
+    }
+
+    /// Reads a color from text: any CSS color for hex, and three numbers separated by commas or spaces otherwise.
+    pub fn parse(self, text: &str) -> Result<Color, String> {
+        if self == Format::Hex {
+            let css: CssColor = text.parse().map_err(|e| format!("{:?}: {}", text, e))?;
+            return Ok(css.to_xyz());
+        }
+
+        let numbers = text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                part.parse::<f64>()
+                    .map_err(|_| format!("{:?} isn't a number", part))
+            })
+            .collect::<Result<Vec<f64>, String>>()?;
+        let &[x, y, z] = numbers.as_slice() else {
+            return Err(format!(
+                "{:?}: expected 3 components, found {}",
+                text,
+                numbers.len()
+            ));
+        };
+        self.read_components([x, y, z])
+    }
+
+    /// Reads a color from its three components, rejecting any that aren't finite, and any combination
+    /// the model has no color for, like a negative lightness.
+    pub fn read_components(self, components: [f64; 3]) -> Result<Color, String> {
+        if let Some(component) = components.iter().find(|c| !c.is_finite()) {
+            return Err(format!("{} isn't a finite number", component));
+        }
+
+        let xyz = match self {
+            Format::Hex | Format::Srgb => {
+                let code = |c: f64| {
+                    if (0.0..=255.0).contains(&c) {
+                        Ok(c.round() as u8)
+                    } else {
+                        Err(format!("{} is outside of 0-255", c))
+                    }
+                };
+                let [r, g, b] = components;
+                XYZ::from_srgb([code(r)?, code(g)?, code(b)?])
+            }
+            Format::Xyz => {
+                let [x, y, z] = components;
+                XYZ { x, y, z }
+            }
+            Format::Jch => {
+                let [J, C, h] = components;
+                XYZ::from(&LMS::from(&JCh::new(J, C, h)))
+            }
+            Format::Ucs => from_jab::<UCS>(components),
+            Format::Lcd => from_jab::<LCD>(components),
+            Format::Scd => from_jab::<SCD>(components),
+        };
+        if [xyz.x, xyz.y, xyz.z].iter().all(|c| c.is_finite()) {
+            Ok(xyz)
+        } else {
+            let [x, y, z] = components;
+            Err(format!("{} {} {} isn't a color", x, y, z))
+        }
+    }
+
+    /// Reads a color from JSON: a string as for [`Format::parse`], an array of three components,
+    /// or an object keyed by the component names.
+    pub fn read_json(self, value: &Value) -> Result<Color, String> {
+        let component = |value: Option<&Value>| {
+            value
+                .and_then(Value::as_f64)
+                .ok_or_else(|| format!("{} isn't a color", value.unwrap_or(&Value::Null)))
+        };
+        match value {
+            Value::String(text) => self.parse(text),
+            Value::Array(values) if values.len() == 3 => self.read_components([
+                component(values.first())?,
+                component(values.get(1))?,
+                component(values.get(2))?,
+            ]),
+            Value::Object(map) => {
+                let [x, y, z] = self.components();
+                self.read_components([
+                    component(map.get(x))?,
+                    component(map.get(y))?,
+                    component(map.get(z))?,
+                ])
+            }
+            value => Err(format!("{} isn't a color", value)),
+        }
+    }
+
+    pub fn to_components(self, color: &Color) -> Result<[f64; 3], String> {
+        Ok(match self {
+            Format::Hex | Format::Srgb => {
+                let sRGB { r, g, b } = to_srgb(color)?;
+                [r, g, b].map(f64::from)
+            }
+            Format::Xyz => [color.x, color.y, color.z],
+            Format::Jch => {
+                let jch = JCh::from(&LMS::from(color));
+                [jch.J, jch.C, jch.h]
+            }
+            Format::Ucs => to_jab::<UCS>(color),
+            Format::Lcd => to_jab::<LCD>(color),
+            Format::Scd => to_jab::<SCD>(color),
+        })
+    }
+
+    /// Formats a color as text: `#rrggbb` for hex, and its components separated by spaces otherwise.
+    pub fn format(self, color: &Color) -> Result<String, String> {
+        if self == Format::Hex {
+            return Ok(to_srgb(color)?.to_string());
+        }
+        Ok(self.to_components(color)?.map(number).join(" "))
+    }
+
+    /// The color's fields for a CSV row: the hex string alone, or its components.
+    pub fn to_csv(self, color: &Color) -> Result<Vec<String>, String> {
+        if self == Format::Hex {
+            return Ok(vec![to_srgb(color)?.to_string()]);
+        }
+        Ok(self.to_components(color)?.map(number).to_vec())
+    }
+
+    pub fn csv_header(self) -> Vec<&'static str> {
+        match self {
+            Format::Hex => vec!["hex"],
+            format => format.components().to_vec(),
+        }
+    }
+
+    /// Formats a color as JSON: a string for hex, and an object keyed by component name otherwise.
+    pub fn to_json(self, color: &Color) -> Result<Value, String> {
+        if self == Format::Hex {
+            return Ok(Value::String(to_srgb(color)?.to_string()));
+        }
+        let components = self.to_components(color)?;
+        let mut map = Map::new();
+        for (name, value) in self.components().iter().zip(components) {
+            let value = if self == Format::Srgb {
+                Value::from(value as u8)
+            } else {
+                Value::from(value)
+            };
+            map.insert(name.to_string(), value);
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+impl Space {
+    /// The color difference ΔE between two colors.
+    pub fn delta_e(self, a: &Color, b: &Color) -> f64 {
+        fn difference<S: JabSpace>(a: &Color, b: &Color) -> f64 {
+            let [a, b] = [a, b].map(|color| Jab::<S, f64>::from(to_jab::<S>(color)));
+            a.squared_difference(&b).sqrt()
+        }
+
+        match self {
+            Space::Ucs => difference::<UCS>(a, b),
+            Space::Lcd => difference::<LCD>(a, b),
+            Space::Scd => difference::<SCD>(a, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let orange = Format::Hex.parse("#ff8000").unwrap();
+        for format in [
+            Format::Srgb,
+            Format::Xyz,
+            Format::Jch,
+            Format::Ucs,
+            Format::Lcd,
+            Format::Scd,
+        ] {
+            let text = format.format(&orange).unwrap();
+            let back = format.parse(&text).unwrap();
+            assert_eq!(
+                Format::Hex.format(&back).unwrap(),
+                "#ff8000",
+                "{:?}",
+                format
+            );
+
+            let json = format.to_json(&orange).unwrap();
+            let back = format.read_json(&json).unwrap();
+            assert_eq!(
+                Format::Hex.format(&back).unwrap(),
+                "#ff8000",
+                "{:?}",
+                format
+            );
+        }
+
+        assert_eq!(Format::Srgb.format(&orange).unwrap(), "255 128 0");
+        assert_eq!(
+            Format::Hex.format(&Format::Srgb.parse("255, 128, 0").unwrap()),
+            Ok("#ff8000".to_string())
+        );
+        assert_eq!(
+            Format::Hex.format(&Format::Hex.parse("rgb(0 128 128)").unwrap()),
+            Ok("#008080".to_string())
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Format::Srgb.parse("1 2").is_err());
+        assert!(Format::Srgb.parse("1 2 300").is_err());
+        assert!(Format::Jch.parse("50 twenty 30").is_err());
+        assert!(Format::Hex.parse("#ff80f").is_err());
+
+        // nothing that isn't finite gets through as NaN
+        assert!(Format::Xyz.parse("nan 1 1").is_err());
+        assert!(Format::Jch.parse("50 inf 30").is_err());
+        assert!(Format::Ucs
+            .read_json(&serde_json::json!([1e308, 1e308, 0]))
+            .is_err());
+        assert!(Format::Jch.parse("-20 10 30").is_err());
+
+        // far too much chroma for sRGB
+        let vivid = Format::Jch.parse("50 120 140").unwrap();
+        assert!(Format::Hex.format(&vivid).is_err());
+        assert!(Format::Ucs.format(&vivid).is_ok());
+    }
+
+    #[test]
+    fn differences() {
+        let (black, white) = (
+            Format::Hex.parse("black").unwrap(),
+            Format::Hex.parse("white").unwrap(),
+        );
+        assert_eq!(Space::Ucs.delta_e(&black, &black), 0.0);
+        let ucs = Space::Ucs.delta_e(&black, &white);
+        assert!((ucs - 100.0).abs() < 0.1, "{}", ucs);
+        assert!(Space::Lcd.delta_e(&black, &white) > ucs);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(number(1.0), "1");
+        assert_eq!(number(-0.00001), "0");
+        assert_eq!(number(12.34567), "12.3457");
+    }
+}
@@ -0,0 +1,273 @@
+//! `kasi-kule`, a command-line tool for converting colors between representations, measuring their differences,
+//! and checking palettes.
+//!
+//! ```text
+//! $ kasi-kule convert --to ucs '#ff8000' 'oklch(70% 0.1 200)'
+//! $ kasi-kule convert --from srgb --to jch --input csv --output json < colors.csv
+//! $ kasi-kule diff --space scd '#ff0000' '#fe0000'
+//! $ kasi-kule palette --colors 6 photo.png
+//! $ kasi-kule check --background '#fafafa' '#c62828' '#2e7d32' '#1565c0'
+//! $ kasi-kule ramp --format svg '#2563eb' > ramp.svg
+//! ```
+#![allow(non_snake_case)]
+
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand};
+use serde_json::Value;
+
+mod analysis;
+mod batch;
+mod color;
+mod preview;
+
+use analysis::{Check, PaletteMethod, SimulationModel};
+
+use batch::{csv_row, Input, Output};
+use color::{number, Color, Format, Space};
+use kasi_kule::sRGB;
+use preview::Preview;
+
+#[derive(Parser)]
+#[command(
+    name = "kasi-kule",
+    version,
+    about = "CAM02 color conversions and differences"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct Io {
+    /// How the colors are written
+    #[arg(long, value_enum, default_value_t = Format::Hex)]
+    from: Format,
+    /// How colors are read from stdin, when none are given as arguments
+    #[arg(long, value_enum, default_value_t = Input::Lines)]
+    input: Input,
+    #[arg(long, value_enum, default_value_t = Output::Text)]
+    output: Output,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Converts colors from one representation into another
+    Convert {
+        /// The colors to convert, read from stdin if there are none
+        colors: Vec<String>,
+        /// The representation to convert into
+        #[arg(long, value_enum)]
+        to: Format,
+        #[command(flatten)]
+        io: Io,
+    },
+    /// Measures the color difference ΔE between pairs of colors
+    Diff {
+        /// A pair of colors, or none to read pairs from stdin
+        colors: Vec<String>,
+        /// The space to measure the difference in
+        #[arg(long, value_enum, default_value_t = Space::Ucs)]
+        space: Space,
+        #[command(flatten)]
+        io: Io,
+    },
+    /// Extracts the main colors of a PNG image
+    Palette {
+        image: PathBuf,
+        /// How many colors to extract
+        #[arg(long, default_value_t = 8)]
+        colors: usize,
+        #[arg(long, value_enum, default_value_t = PaletteMethod::KMeans)]
+        method: PaletteMethod,
+        #[arg(long, value_enum, default_value_t = Preview::Ansi)]
+        format: Preview,
+    },
+    /// Checks colors for text contrast against a background, and for telling them apart with color vision deficiencies
+    Check {
+        /// The colors to check, as CSS colors
+        #[arg(required = true)]
+        colors: Vec<String>,
+        #[arg(long, default_value = "white")]
+        background: String,
+        /// The severity of the deficiencies, from 0 to 1
+        #[arg(long, default_value_t = 1.0, value_parser = unit_interval)]
+        severity: f32,
+        #[arg(long, value_enum, default_value_t = SimulationModel::Machado)]
+        model: SimulationModel,
+        /// The smallest ΔE in CAM02-UCS for a pair of colors to count as distinguishable
+        #[arg(long, default_value_t = 10.0)]
+        threshold: f32,
+        #[arg(long, value_enum, default_value_t = Preview::Ansi)]
+        format: Preview,
+    },
+    /// Generates a ramp from a light tint to a dark shade of a color
+    Ramp {
+        color: String,
+        #[arg(long, default_value_t = 9)]
+        steps: usize,
+        #[arg(long, value_enum, default_value_t = Preview::Ansi)]
+        format: Preview,
+    },
+}
+
+/// Parses a number from 0 to 1.
+fn unit_interval(text: &str) -> Result<f32, String> {
+    let value: f32 = text
+        .parse()
+        .map_err(|_| format!("{:?} isn't a number", text))?;
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("{} is outside of 0-1", value))
+    }
+}
+
+fn srgb(color: &str) -> Result<sRGB, String> {
+    color.parse().map_err(|e| format!("{:?}: {}", color, e))
+}
+
+/// Reads records of `arity` colors from the arguments if there are any, and from stdin otherwise.
+fn records(colors: &[String], io: &Io, arity: usize) -> Result<Vec<Vec<Color>>, String> {
+    if !colors.is_empty() {
+        let colors = colors
+            .iter()
+            .map(|color| io.from.parse(color))
+            .collect::<Result<Vec<Color>, String>>()?;
+        if colors.len() % arity != 0 {
+            return Err(format!("expected colors in groups of {}", arity));
+        }
+        return Ok(colors.chunks(arity).map(<[Color]>::to_vec).collect());
+    }
+
+    let mut text = String::new();
+    std::io::stdin()
+        .read_to_string(&mut text)
+        .map_err(|e| e.to_string())?;
+    batch::read(io.input, &text, io.from, arity)
+}
+
+fn convert(colors: &[String], to: Format, io: &Io) -> Result<String, String> {
+    let colors: Vec<Color> = records(colors, io, 1)?.into_iter().flatten().collect();
+    let mut out = String::new();
+    match io.output {
+        Output::Text => {
+            for color in &colors {
+                out += &to.format(color)?;
+                out.push('\n');
+            }
+        }
+        Output::Csv => {
+            out += &csv_row(&to.csv_header());
+            out.push('\n');
+            for color in &colors {
+                out += &csv_row(&to.to_csv(color)?);
+                out.push('\n');
+            }
+        }
+        Output::Json => {
+            let values = colors
+                .iter()
+                .map(|color| to.to_json(color))
+                .collect::<Result<Vec<Value>, String>>()?;
+            out += &Value::Array(values).to_string();
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+fn diff(colors: &[String], space: Space, io: &Io) -> Result<String, String> {
+    let differences: Vec<f64> = records(colors, io, 2)?
+        .iter()
+        .map(|pair| space.delta_e(&pair[0], &pair[1]))
+        .collect();
+    let mut out = match io.output {
+        Output::Text => String::new(),
+        Output::Csv => "delta_e\n".to_string(),
+        Output::Json => {
+            let values = differences.iter().map(|d| Value::from(*d)).collect();
+            return Ok(format!("{}\n", Value::Array(values)));
+        }
+    };
+    for difference in differences {
+        out += &number(difference);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn check(
+    colors: &[String],
+    background: &str,
+    severity: f32,
+    model: SimulationModel,
+    threshold: f32,
+    format: Preview,
+) -> Result<String, String> {
+    let colors = colors
+        .iter()
+        .map(|color| srgb(color))
+        .collect::<Result<Vec<sRGB>, String>>()?;
+    let settings = Check {
+        background: srgb(background)?,
+        severity,
+        model,
+        threshold,
+    };
+    Ok(analysis::check(&colors, &settings, format))
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Convert { colors, to, io } => convert(&colors, to, &io),
+        Command::Diff { colors, space, io } => diff(&colors, space, &io),
+        Command::Palette {
+            image,
+            colors,
+            method,
+            format,
+        } => analysis::palette(&image, colors, method, format),
+        Command::Check {
+            colors,
+            background,
+            severity,
+            model,
+            threshold,
+            format,
+        } => check(&colors, &background, severity, model, threshold, format),
+        Command::Ramp {
+            color,
+            steps,
+            format,
+        } => srgb(&color).map(|color| analysis::ramp(color, steps, format)),
+    };
+
+    match result {
+        Ok(out) => {
+            print!("{}", out);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("kasi-kule: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn severity_is_checked() {
+        let check = |severity: &str| {
+            Cli::try_parse_from(["kasi-kule", "check", "--severity", severity, "#c62828"]).is_ok()
+        };
+        assert!(check("0") && check("0.6") && check("1"));
+        assert!(!check("2") && !check("-0.1") && !check("NaN") && !check("high"));
+    }
+}
@@ -0,0 +1,158 @@
+//! Previews of rows of color swatches, as 24-bit ANSI terminal output or as SVG.
+use clap::ValueEnum;
+use kasi_kule::contrast::wcag_ratio;
+use kasi_kule::sRGB;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Preview {
+    /// Swatches in the terminal, in 24-bit color
+    Ansi,
+    Json,
+    Svg,
+}
+
+/// A color to show, with a label drawn on it, and its share of the width of its row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Swatch {
+    pub srgb: sRGB,
+    pub label: String,
+    pub weight: f32,
+}
+
+impl Swatch {
+    /// A swatch labelled with its hex code, as wide as every other.
+    pub fn new(srgb: sRGB) -> Swatch {
+        Swatch {
+            srgb,
+            label: srgb.to_string(),
+            weight: 1.0,
+        }
+    }
+}
+
+/// A titled row of swatches.
+pub type Row = (String, Vec<Swatch>);
+
+/// Black or white, whichever contrasts more with the background.
+fn ink(background: sRGB) -> sRGB {
+    let (black, white) = (sRGB::from([0, 0, 0]), sRGB::from([255, 255, 255]));
+    if wcag_ratio::<f32>(black, background) >= wcag_ratio::<f32>(white, background) {
+        black
+    } else {
+        white
+    }
+}
+
+// the width of a swatch of weight 1, in terminal columns and in SVG pixels
+const ANSI_WIDTH: f32 = 10.0;
+const SVG_WIDTH: f32 = 96.0;
+const SVG_HEIGHT: f32 = 48.0;
+const SVG_TITLE_WIDTH: f32 = 96.0;
+
+/// Each row as a line of swatches with their labels printed on them, after its title.
+pub fn ansi(rows: &[Row]) -> String {
+    let title_width = rows.iter().map(|(title, _)| title.len()).max().unwrap_or(0);
+    let mut out = String::new();
+    for (title, swatches) in rows {
+        out += &format!("{:width$} ", title, width = title_width);
+        for swatch in swatches {
+            let width = ((swatch.weight * ANSI_WIDTH).round() as usize).max(swatch.label.len() + 2);
+            let (background, text) = (swatch.srgb, ink(swatch.srgb));
+            out += &format!(
+                "\x1b[48;2;{};{};{}m\x1b[38;2;{};{};{}m{:^width$}\x1b[0m",
+                background.r,
+                background.g,
+                background.b,
+                text.r,
+                text.g,
+                text.b,
+                swatch.label,
+                width = width
+            );
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// An SVG image with the rows stacked top to bottom, their titles on the left.
+pub fn svg(rows: &[Row]) -> String {
+    let width = rows
+        .iter()
+        .map(|(_, swatches)| swatches.iter().map(|swatch| swatch.weight).sum::<f32>())
+        .fold(0.0, f32::max)
+        * SVG_WIDTH
+        + SVG_TITLE_WIDTH;
+    let height = rows.len() as f32 * SVG_HEIGHT;
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"12\">\n",
+        width, height
+    );
+    for (i, (title, swatches)) in rows.iter().enumerate() {
+        let y = i as f32 * SVG_HEIGHT;
+        let middle = y + SVG_HEIGHT / 2.0;
+        out += &format!(
+            "  <text x=\"8\" y=\"{}\" dominant-baseline=\"middle\">{}</text>\n",
+            middle,
+            escape(title)
+        );
+
+        let mut x = SVG_TITLE_WIDTH;
+        for swatch in swatches {
+            let swatch_width = swatch.weight * SVG_WIDTH;
+            out += &format!(
+                "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                x, y, swatch_width, SVG_HEIGHT, swatch.srgb
+            );
+            out += &format!(
+                "  <text x=\"{}\" y=\"{}\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"middle\">{}</text>\n",
+                x + swatch_width / 2.0,
+                middle,
+                ink(swatch.srgb),
+                escape(&swatch.label)
+            );
+            x += swatch_width;
+        }
+    }
+    out += "</svg>\n";
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn previews() {
+        let rows = vec![(
+            "a<b".to_string(),
+            vec![
+                Swatch::new(sRGB::from([255, 255, 0])),
+                Swatch {
+                    srgb: sRGB::from([0, 0, 128]),
+                    label: "navy".to_string(),
+                    weight: 2.0,
+                },
+            ],
+        )];
+
+        let ansi = ansi(&rows);
+        assert!(ansi.starts_with("a<b "));
+        // black on yellow, white on navy
+        assert!(ansi.contains("\x1b[48;2;255;255;0m\x1b[38;2;0;0;0m #ffff00 "));
+        assert!(ansi.contains("\x1b[48;2;0;0;128m\x1b[38;2;255;255;255m"));
+
+        let svg = svg(&rows);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert!(svg.contains("width=\"384\""));
+        assert!(svg.contains("a&lt;b"));
+        assert!(svg.contains("fill=\"#000080\""));
+    }
+}
@@ -0,0 +1,321 @@
+//! Gamut boundary descriptor for sRGB in CIECAM02.
+//!
+//! [`GamutBoundary`] answers "what is the most colorful sRGB color at this lightness and hue?",
+//! and holds the cusp (the most colorful color overall) of every hue:
+//! ```
+//! use kasi_kule::boundary::GamutBoundary;
+//!
+//! let boundary = GamutBoundary::<f32>::default();
+//! let red = boundary.cusp(32.15);
+//! assert!((red.C - 111.3).abs() < 0.5);
+//!
+//! // queries are interpolated from a grid which is filled in as it's used
+//! assert!(boundary.max_chroma(70.0, 32.15) < red.C);
+//! ```
+//!
+//! The cusps are found along the edges of the sRGB cube between the primaries and secondaries,
+//! where the most colorful color of every hue lies. Maximum chroma comes from bisecting along chroma,
+//! through the inverse CIECAM02 transform.
+use alloc::vec::Vec;
+
+use crate::gamut::{bisect, in_gamut};
+use crate::utils::wrap_hue;
+use crate::{sRGB, Float, JCh};
+
+// with std, the grid can be filled in from several threads at once
+#[cfg(feature = "std")]
+type GridCell<F> = std::sync::OnceLock<F>;
+#[cfg(not(feature = "std"))]
+type GridCell<F> = core::cell::OnceCell<F>;
+
+// above the chroma of any sRGB color, which peaks at ~111 for red
+const CHROMA_LIMIT: f64 = 160.0;
+
+/// The most colorful color of a hue.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cusp<F: Float = f32> {
+    pub J: F,
+    pub C: F,
+    pub h: F,
+}
+
+/// The maximum chroma of an sRGB color with lightness `J` and hue angle `h` (in degrees), computed exactly.
+/// Returns 0 if even the grey at that lightness is outside sRGB.
+pub fn max_chroma<F: Float>(J: F, h: F) -> F {
+    let c = F::from_f64;
+    if J <= F::default() || !in_gamut(&JCh::new(J, F::default(), h)) {
+        return F::default();
+    }
+
+    bisect(|t| JCh::new(J, t * c(CHROMA_LIMIT), h)) * c(CHROMA_LIMIT)
+}
+
+/// Wraps a hue difference into [-180, 180).
+fn hue_offset<F: Float>(difference: F) -> F {
+    let c = F::from_f64;
+    let mut difference = c(difference.to_f64() % 360.0);
+    if difference >= c(180.0) {
+        difference -= c(360.0);
+    } else if difference < c(-180.0) {
+        difference += c(360.0);
+    }
+    difference
+}
+
+/// The edges of the sRGB cube running red, yellow, green, cyan, blue, magenta and back to red.
+fn cube_edges() -> impl Iterator<Item = sRGB> {
+    let ramp = |i: u8| [i, 255 - i];
+    (0..6).flat_map(move |edge| {
+        (0..255u8).map(move |i| {
+            let [up, down] = ramp(i);
+            sRGB::from(match edge {
+                0 => [255, up, 0],
+                1 => [down, 255, 0],
+                2 => [0, 255, up],
+                3 => [0, down, 255],
+                4 => [up, 0, 255],
+                _ => [255, 0, down],
+            })
+        })
+    })
+}
+
+/// Finds the cusp at hue `h` on the segment between two edge samples that straddles it.
+fn edge_cusp<F: Float>(edges: &[Cusp<F>], h: F) -> Cusp<F> {
+    let mut cusp = Cusp {
+        J: F::default(),
+        C: F::default(),
+        h,
+    };
+
+    for (i, from) in edges.iter().enumerate() {
+        let to = &edges[(i + 1) % edges.len()];
+        let span = hue_offset(to.h - from.h);
+        if span == F::default() {
+            continue;
+        }
+
+        let t = hue_offset(h - from.h) / span;
+        if t >= F::default() && t <= F::from_f64(1.0) {
+            let C = from.C + t * (to.C - from.C);
+            if C > cusp.C {
+                cusp.J = from.J + t * (to.J - from.J);
+                cusp.C = C;
+            }
+        }
+    }
+
+    cusp
+}
+
+/// Maximum chroma of sRGB over a grid of lightness and hue, plus the cusp of every hue on the grid.
+///
+/// The grid is filled in lazily: every query computes (and caches) only the grid points around it.
+/// Between grid points the maximum chroma is interpolated bilinearly,
+/// which rounds off the boundary near the cusps a little.
+///
+/// With the `std` feature, a boundary is `Sync`, and can be shared between threads.
+#[derive(Debug, Clone)]
+pub struct GamutBoundary<F: Float = f32> {
+    hue_steps: usize,
+    lightness_steps: usize,
+    // every code value along the cube edges the cusps lie on
+    edges: Vec<Cusp<F>>,
+    cusps: Vec<Cusp<F>>,
+    // maximum chroma at each (lightness, hue) grid point, lightness-major
+    grid: Vec<GridCell<F>>,
+}
+
+impl<F: Float> Default for GamutBoundary<F> {
+    /// A grid of 1 degree of hue by 1 unit of lightness.
+    fn default() -> GamutBoundary<F> {
+        GamutBoundary::new(360, 100)
+    }
+}
+
+impl<F: Float> GamutBoundary<F> {
+    /// A boundary split into `hue_steps` hues and `lightness_steps` lightness steps.
+    /// The cusps are computed straight away, and maximum chroma as it's queried.
+    pub fn new(hue_steps: usize, lightness_steps: usize) -> GamutBoundary<F> {
+        let (hue_steps, lightness_steps) = (hue_steps.max(1), lightness_steps.max(1));
+        let c = F::from_f64;
+
+        let edges: Vec<Cusp<F>> = cube_edges()
+            .map(|rgb| {
+                let JCh { J, C, h, .. } = JCh::from_srgb(rgb);
+                Cusp { J, C, h }
+            })
+            .collect();
+        let cusps = (0..hue_steps)
+            .map(|step| edge_cusp(&edges, c(step as f64 * 360.0 / hue_steps as f64)))
+            .collect();
+
+        GamutBoundary {
+            hue_steps,
+            lightness_steps,
+            edges,
+            cusps,
+            grid: (0..(lightness_steps + 1) * hue_steps)
+                .map(|_| GridCell::new())
+                .collect(),
+        }
+    }
+
+    /// The cusp of every hue step, starting at h = 0.
+    pub fn cusps(&self) -> &[Cusp<F>] {
+        &self.cusps
+    }
+
+    /// The cusp at any hue angle `h` (in degrees).
+    pub fn cusp(&self, h: F) -> Cusp<F> {
+        edge_cusp(&self.edges, wrap_hue(h))
+    }
+
+    /// The maximum chroma of an sRGB color with lightness `J` and hue angle `h` (in degrees).
+    pub fn max_chroma(&self, J: F, h: F) -> F {
+        let c = F::from_f64;
+        let J = if J > c(100.0) {
+            c(100.0)
+        } else if J > F::default() {
+            J
+        } else {
+            F::default()
+        };
+
+        let (hue, th) = self.hue_step(wrap_hue(h));
+        let position = J / c(100.0) * c(self.lightness_steps as f64);
+        let lightness = (position.to_f64() as usize).min(self.lightness_steps - 1);
+        let tj = position - c(lightness as f64);
+
+        let next_hue = (hue + 1) % self.hue_steps;
+        let lerp = |a: F, b: F, t: F| a + t * (b - a);
+        lerp(
+            lerp(
+                self.grid_chroma(lightness, hue),
+                self.grid_chroma(lightness, next_hue),
+                th,
+            ),
+            lerp(
+                self.grid_chroma(lightness + 1, hue),
+                self.grid_chroma(lightness + 1, next_hue),
+                th,
+            ),
+            tj,
+        )
+    }
+
+    /// The most colorful sRGB color with lightness `J` and hue angle `h` (in degrees).
+    pub fn most_colorful(&self, J: F, h: F) -> JCh<F> {
+        JCh::new(J, self.max_chroma(J, h), h)
+    }
+
+    /// Whether a color is inside the boundary. Only J, C and h are used.
+    pub fn contains(&self, color: &JCh<F>) -> bool {
+        color.C <= self.max_chroma(color.J, color.h)
+    }
+
+    /// The hue step at or below `h`, and how far `h` is towards the next one.
+    fn hue_step(&self, h: F) -> (usize, F) {
+        let c = F::from_f64;
+        let position = h / c(360.0) * c(self.hue_steps as f64);
+        let step = (position.to_f64() as usize).min(self.hue_steps - 1);
+        (step, position - c(step as f64))
+    }
+
+    fn grid_chroma(&self, lightness: usize, hue: usize) -> F {
+        *self.grid[lightness * self.hue_steps + hue].get_or_init(|| {
+            let c = F::from_f64;
+            max_chroma(
+                c(lightness as f64 * 100.0 / self.lightness_steps as f64),
+                c(hue as f64 * 360.0 / self.hue_steps as f64),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cusps_match_primaries() {
+        let boundary = GamutBoundary::<f32>::default();
+        assert_eq!(boundary.cusps().len(), 360);
+
+        for rgb in [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 0]] {
+            let primary = JCh::from(rgb);
+            let cusp = boundary.cusp(primary.h);
+            assert!((cusp.J - primary.J).abs() < 0.5, "{:?} {:?}", rgb, cusp);
+            assert!((cusp.C - primary.C).abs() < 0.5, "{:?} {:?}", rgb, cusp);
+        }
+
+        for cusp in boundary.cusps() {
+            assert!(cusp.C > 0.0 && cusp.C < CHROMA_LIMIT as f32);
+            // nothing at the cusp's lightness is noticeably more colorful
+            assert!(max_chroma(cusp.J, cusp.h) < cusp.C + 0.5, "{:?}", cusp);
+        }
+    }
+
+    #[test]
+    fn exact_max_chroma() {
+        let red = JCh::from([255, 0, 0]);
+        assert!((max_chroma(red.J, red.h) - red.C).abs() < 0.1);
+
+        let teal = JCh::from([0, 128, 128]);
+        let limit = max_chroma(teal.J, teal.h);
+        assert!(limit >= teal.C - 0.1);
+        assert!(in_gamut(&JCh::new(teal.J, limit, teal.h)));
+        assert!(!in_gamut(&JCh::new(teal.J, limit + 0.5, teal.h)));
+
+        assert_eq!(max_chroma(0.0, 120.0), 0.0);
+    }
+
+    #[test]
+    fn cached_queries() {
+        let boundary = GamutBoundary::<f32>::new(90, 50);
+        assert!(boundary.grid.iter().all(|cell| cell.get().is_none()));
+
+        let first = boundary.max_chroma(55.0, 200.0);
+        let filled = boundary.grid.iter().filter(|cell| cell.get().is_some());
+        assert_eq!(filled.count(), 4);
+        assert_eq!(boundary.max_chroma(55.0, 200.0), first);
+
+        // the interpolated boundary stays close to the exact one away from the cusps
+        let boundary = GamutBoundary::<f32>::default();
+        for h in (0..360).step_by(7) {
+            for J in (10..95).step_by(9) {
+                let (J, h) = (J as f32 + 0.3, h as f32 + 0.6);
+                let exact = max_chroma(J, h);
+                let interpolated = boundary.max_chroma(J, h);
+                let cusp = boundary.cusp(h);
+                if (J - cusp.J).abs() > 5.0 {
+                    assert!(
+                        (exact - interpolated).abs() < 0.25,
+                        "J {} h {}: {} vs {}",
+                        J,
+                        h,
+                        exact,
+                        interpolated
+                    );
+                }
+            }
+        }
+
+        let inside = JCh::from([40, 90, 200]);
+        assert!(boundary.contains(&inside));
+        assert!(!boundary.contains(&JCh::new(inside.J, inside.C + 30.0, inside.h)));
+        assert!(boundary.most_colorful(inside.J, inside.h).C > inside.C);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn shared_between_threads() {
+        let boundary = GamutBoundary::<f32>::new(36, 10);
+        let expected = max_chroma(50.0, 120.0);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| assert_eq!(boundary.max_chroma(50.0, 120.0), expected));
+            }
+        });
+    }
+}
@@ -0,0 +1,585 @@
+//! Perceptually uniform colormaps in CAM02-UCS.
+//!
+//! A [`Design`] describes a sequential, diverging or cyclic map as a path through [`JCh`],
+//! which is kept inside sRGB with the [`GamutBoundary`] and sampled at constant ΔE steps in [`Jab<UCS>`]:
+//! ```
+//! use kasi_kule::colormap::{Design, Kind};
+//!
+//! // dark blue through to light yellow, like viridis
+//! let map = Design::Sequential { lightness: (20.0, 95.0), hue: (270.0, 100.0), chroma: 50.0 }.generate(16);
+//!
+//! let report = map.validate();
+//! assert!(report.monotone_lightness);
+//! // what's left over comes from rounding to code values
+//! assert!(report.spread() < 0.15);
+//!
+//! let csv = map.to_csv();
+//! assert!(csv.starts_with("position,r,g,b,hex\n"));
+//! ```
+//!
+//! Existing maps can be checked the same way, with [`Colormap::new`] and [`Colormap::validate`].
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::boundary::GamutBoundary;
+use crate::gamut::GamutMapping;
+use crate::{sRGB, Float, JCh, Jab, UCS};
+
+/// What a colormap encodes, which decides how its lightness should behave.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Kind {
+    /// Ordered data: lightness runs monotonically from one end to the other.
+    Sequential,
+    /// Data around a midpoint: lightness is monotone on either side of the center.
+    Diverging,
+    /// Periodic data, like angles: the map wraps around from its last color to its first.
+    Cyclic,
+}
+
+/// A colormap to generate, as a path through lightness, chroma and hue.
+///
+/// Chroma is capped to what sRGB can show at every point of the path, so it's a target rather than a guarantee.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Design {
+    /// Lightness and hue run linearly from their first value to their second.
+    /// Hues can go past 360 to turn further around the hue circle.
+    Sequential {
+        lightness: (f32, f32),
+        hue: (f32, f32),
+        chroma: f32,
+    },
+    /// Two arms of constant hue, with chroma fading out towards a neutral center.
+    /// `lightness` is the lightness at the ends, and at the center.
+    Diverging {
+        hue: (f32, f32),
+        lightness: (f32, f32),
+        chroma: f32,
+    },
+    /// A full turn of the hue circle at constant lightness and chroma, starting at `hue`.
+    /// Chroma is lowered to the largest value sRGB can show at every hue.
+    Cyclic {
+        lightness: f32,
+        chroma: f32,
+        hue: f32,
+    },
+}
+
+// points along a design's path, which the colors are then spaced out over
+const PATH_SAMPLES: usize = 512;
+
+// how much of the maximum chroma a path uses, leaving room for the interpolation of the gamut boundary
+const CHROMA_MARGIN: f32 = 0.97;
+
+// bisection steps when spacing out colors, both for the step length and for each step along the path
+const BISECTION_STEPS: usize = 24;
+
+impl Design {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Design::Sequential { .. } => Kind::Sequential,
+            Design::Diverging { .. } => Kind::Diverging,
+            Design::Cyclic { .. } => Kind::Cyclic,
+        }
+    }
+
+    /// Generates a colormap of `n` colors, with equal ΔE between neighbours.
+    pub fn generate(&self, n: usize) -> Colormap {
+        let boundary = GamutBoundary::<f32>::default();
+        let capped = |J: f32, C: f32, h: f32| {
+            let h = crate::utils::wrap_hue(h);
+            let C = C.min(boundary.max_chroma(J, h) * CHROMA_MARGIN).max(0.0);
+            Jab::<UCS>::from(&JCh::new(J, C, h))
+        };
+        let lerp = |(from, to): (f32, f32), t: f32| from + t * (to - from);
+
+        // a cyclic map is a circle, so it only needs the chroma every hue can reach
+        let cyclic_chroma = match *self {
+            Design::Cyclic {
+                lightness, chroma, ..
+            } => (0..360).fold(chroma, |chroma, h| {
+                chroma.min(boundary.max_chroma(lightness, h as f32) * CHROMA_MARGIN)
+            }),
+            _ => 0.0,
+        };
+
+        let path: Vec<[f32; 3]> = (0..=PATH_SAMPLES)
+            .map(|i| {
+                let t = i as f32 / PATH_SAMPLES as f32;
+                let jab = match *self {
+                    Design::Sequential {
+                        lightness,
+                        hue,
+                        chroma,
+                    } => capped(lerp(lightness, t), chroma, lerp(hue, t)),
+                    Design::Diverging {
+                        hue,
+                        lightness: (ends, center),
+                        chroma,
+                    } => {
+                        let (arm, u) = if t < 0.5 {
+                            (hue.0, 1.0 - 2.0 * t)
+                        } else {
+                            (hue.1, 2.0 * t - 1.0)
+                        };
+                        capped(lerp((center, ends), u), chroma * u, arm)
+                    }
+                    Design::Cyclic { lightness, hue, .. } => {
+                        capped(lightness, cyclic_chroma, hue + 360.0 * t)
+                    }
+                };
+                [jab.J, jab.a, jab.b]
+            })
+            .collect();
+
+        // a cyclic map also steps from its last color back to the first, which is the end of the path
+        let steps = match self.kind() {
+            Kind::Cyclic => n,
+            _ => n.saturating_sub(1),
+        };
+        let mut points = even_steps(&path, steps);
+        points.truncate(n);
+
+        let colors = points
+            .iter()
+            .map(|&[J, a, b]| {
+                let jch = JCh::from(&Jab::<UCS>::new_const(J, a, b));
+                GamutMapping::Clip.map(&jch).srgb
+            })
+            .collect();
+
+        Colormap::new(colors, self.kind())
+    }
+}
+
+fn distance(from: &[f32; 3], to: &[f32; 3]) -> f32 {
+    let [J, a, b] = [to[0] - from[0], to[1] - from[1], to[2] - from[2]];
+    Float::sqrt(J * J + a * a + b * b)
+}
+
+fn lerp_point(from: &[f32; 3], to: &[f32; 3], t: f32) -> [f32; 3] {
+    [0, 1, 2].map(|axis| from[axis] + t * (to[axis] - from[axis]))
+}
+
+/// Walks along a polyline in straight steps of ΔE `length`, starting at its first point.
+/// Returns the points reached, and whether the walk ran into the end of the line before taking `steps` steps.
+fn walk(path: &[[f32; 3]], length: f32, steps: usize) -> (Vec<[f32; 3]>, bool) {
+    let mut points = Vec::with_capacity(steps + 1);
+    let mut current = path[0];
+    let (mut segment, mut start) = (0, 0.0);
+    points.push(current);
+
+    while points.len() <= steps {
+        // the first segment that ends far enough away
+        let Some(next) =
+            (segment..path.len() - 1).find(|&i| distance(&current, &path[i + 1]) >= length)
+        else {
+            return (points, true);
+        };
+        if next != segment {
+            start = 0.0;
+        }
+
+        // bisect within that segment for the point at exactly `length`
+        let (mut near, mut far) = (start, 1.0);
+        for _ in 0..BISECTION_STEPS {
+            let t = (near + far) / 2.0;
+            if distance(&current, &lerp_point(&path[next], &path[next + 1], t)) < length {
+                near = t;
+            } else {
+                far = t;
+            }
+        }
+
+        current = lerp_point(&path[next], &path[next + 1], far);
+        (segment, start) = (next, far);
+        points.push(current);
+    }
+
+    (points, false)
+}
+
+/// Picks `steps + 1` points along a polyline, from its first point to its last,
+/// with the same ΔE between each pair of neighbours.
+///
+/// Spacing points evenly along the line itself wouldn't do,
+/// since a step across a bend of the line is shorter than the stretch of line it cuts off.
+fn even_steps(path: &[[f32; 3]], steps: usize) -> Vec<[f32; 3]> {
+    let last = path[path.len() - 1];
+    if steps == 0 {
+        return alloc::vec![path[0]];
+    }
+
+    // a step can't be longer than an even share of the length of the line
+    let length: f32 = path
+        .windows(2)
+        .map(|pair| distance(&pair[0], &pair[1]))
+        .sum();
+    let (mut short, mut long) = (0.0, length / steps as f32);
+    for _ in 0..BISECTION_STEPS {
+        let step = (short + long) / 2.0;
+        if walk(path, step, steps).1 {
+            long = step;
+        } else {
+            short = step;
+        }
+    }
+
+    // the walk ends just short of the end of the line, which the last step is stretched to
+    let (mut points, _) = walk(path, short, steps);
+    if let Some(end) = points.last_mut() {
+        *end = last;
+    }
+    points
+}
+
+/// A list of colors, spread evenly over the data range.
+#[derive(Debug, Clone)]
+pub struct Colormap {
+    pub colors: Vec<sRGB>,
+    pub kind: Kind,
+}
+
+/// How well a colormap follows the rules for its kind.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Report {
+    /// Whether lightness never turns back, as [`Kind`] describes. Always true for cyclic maps.
+    pub monotone_lightness: bool,
+    /// The number of steps where lightness goes the wrong way.
+    pub lightness_reversals: usize,
+    /// The smallest ΔE in CAM02-UCS between neighbouring colors. For cyclic maps this includes the step from the
+    /// last color back to the first.
+    pub min_delta_e: f32,
+    pub mean_delta_e: f32,
+    pub max_delta_e: f32,
+}
+
+impl Report {
+    /// The range of ΔE between neighbours relative to the mean, which is 0 for a perfectly uniform map.
+    pub fn spread(&self) -> f32 {
+        if self.mean_delta_e > 0.0 {
+            (self.max_delta_e - self.min_delta_e) / self.mean_delta_e
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Writes a string as a JSON string literal.
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Python's keywords, which can't be assigned to.
+const PYTHON_KEYWORDS: [&str; 35] = [
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// Turns a name into a Python identifier: anything but ASCII letters, digits and underscores becomes an
+/// underscore, and a name that's empty, starts with a digit or is a keyword gets a leading underscore.
+fn python_identifier(name: &str) -> String {
+    let mut identifier = String::new();
+    if name.is_empty()
+        || name.starts_with(|c: char| c.is_ascii_digit())
+        || PYTHON_KEYWORDS.contains(&name)
+    {
+        identifier.push('_');
+    }
+    identifier.extend(
+        name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }),
+    );
+    identifier
+}
+
+impl Colormap {
+    pub fn new(colors: Vec<sRGB>, kind: Kind) -> Colormap {
+        Colormap { colors, kind }
+    }
+
+    /// Checks the lightness and ΔE uniformity of the map.
+    pub fn validate(&self) -> Report {
+        let jabs: Vec<Jab<UCS>> = self.colors.iter().map(|rgb| Jab::from_srgb(*rgb)).collect();
+
+        let mut steps: Vec<f32> = jabs
+            .windows(2)
+            .map(|pair| Float::sqrt(pair[0].squared_difference(&pair[1])))
+            .collect();
+        if self.kind == Kind::Cyclic && jabs.len() > 2 {
+            steps.push(Float::sqrt(
+                jabs[jabs.len() - 1].squared_difference(&jabs[0]),
+            ));
+        }
+
+        let lightness: Vec<f32> = jabs.iter().map(|jab| jab.J).collect();
+        let reversals = |lightness: &[f32]| {
+            let rising = lightness
+                .windows(2)
+                .filter(|pair| pair[1] > pair[0])
+                .count();
+            let falling = lightness
+                .windows(2)
+                .filter(|pair| pair[1] < pair[0])
+                .count();
+            rising.min(falling)
+        };
+        let lightness_reversals = match self.kind {
+            Kind::Sequential => reversals(&lightness),
+            Kind::Diverging if lightness.len() > 2 => {
+                // split at whichever extreme of lightness is furthest from the ends
+                let ends = (lightness[0] + lightness[lightness.len() - 1]) / 2.0;
+                let center = (0..lightness.len())
+                    .max_by(|&a, &b| {
+                        (lightness[a] - ends)
+                            .abs()
+                            .total_cmp(&(lightness[b] - ends).abs())
+                    })
+                    .unwrap_or(0);
+                reversals(&lightness[..=center]) + reversals(&lightness[center..])
+            }
+            _ => 0,
+        };
+
+        let (min, max, sum) = steps
+            .iter()
+            .fold((f32::INFINITY, 0.0f32, 0.0), |(min, max, sum), step| {
+                (min.min(*step), max.max(*step), sum + step)
+            });
+
+        Report {
+            monotone_lightness: lightness_reversals == 0,
+            lightness_reversals,
+            min_delta_e: if steps.is_empty() { 0.0 } else { min },
+            mean_delta_e: if steps.is_empty() {
+                0.0
+            } else {
+                sum / steps.len() as f32
+            },
+            max_delta_e: max,
+        }
+    }
+
+    /// Where each color sits in the data range, from 0 to 1.
+    fn positions(&self) -> impl Iterator<Item = f32> + '_ {
+        let last = self.colors.len().saturating_sub(1).max(1) as f32;
+        (0..self.colors.len()).map(move |i| i as f32 / last)
+    }
+
+    /// One `position,r,g,b,hex` row per color, with positions from 0 to 1 and channels from 0 to 255.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("position,r,g,b,hex\n");
+        for (position, color) in self.positions().zip(&self.colors) {
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{}",
+                position, color.r, color.g, color.b, color
+            );
+        }
+        out
+    }
+
+    /// A JSON object with the map's name and its colors as hex strings.
+    pub fn to_json(&self, name: &str) -> String {
+        let mut out = String::from("{\"name\":");
+        write_json_string(&mut out, name);
+        out.push_str(",\"colors\":[");
+        for (i, color) in self.colors.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "\"{}\"", color);
+        }
+        out.push_str("]}");
+        out
+    }
+
+    /// Python source defining the map as a matplotlib `ListedColormap` called `name`.
+    /// The variable it's assigned to is `name` made into a Python identifier, with anything but
+    /// ASCII letters, digits and underscores replaced by underscores.
+    pub fn to_matplotlib(&self, name: &str) -> String {
+        let mut out = String::from("from matplotlib.colors import ListedColormap\n\ncm_data = [\n");
+        for color in &self.colors {
+            let _ = writeln!(
+                out,
+                "    [{:.6}, {:.6}, {:.6}],",
+                color.r as f32 / 255.0,
+                color.g as f32 / 255.0,
+                color.b as f32 / 255.0
+            );
+        }
+        let _ = write!(
+            out,
+            "]\n\n{} = ListedColormap(cm_data, name=",
+            python_identifier(name)
+        );
+        write_json_string(&mut out, name);
+        out.push_str(")\n");
+        out
+    }
+
+    /// A ParaView color map preset, as JSON.
+    pub fn to_paraview(&self, name: &str) -> String {
+        let mut out = String::from("[{\"ColorSpace\":\"Lab\",\"Name\":");
+        write_json_string(&mut out, name);
+        out.push_str(",\"RGBPoints\":[");
+        for (i, (position, color)) in self.positions().zip(&self.colors).enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{},{:.6},{:.6},{:.6}",
+                position,
+                color.r as f32 / 255.0,
+                color.g as f32 / 255.0,
+                color.b as f32 / 255.0
+            );
+        }
+        out.push_str("]}]");
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SEQUENTIAL: Design = Design::Sequential {
+        lightness: (20.0, 95.0),
+        hue: (270.0, 100.0),
+        chroma: 50.0,
+    };
+
+    #[test]
+    fn sequential() {
+        let map = SEQUENTIAL.generate(12);
+        assert_eq!(map.colors.len(), 12);
+
+        let report = map.validate();
+        assert!(report.monotone_lightness, "{:?}", report);
+        assert!(report.spread() < 0.1, "{:?}", report);
+
+        let (first, last) = (JCh::from(map.colors[0]), JCh::from(map.colors[11]));
+        assert!((first.J - 20.0).abs() < 1.0 && (last.J - 95.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn diverging() {
+        let map = Design::Diverging {
+            hue: (250.0, 30.0),
+            lightness: (35.0, 92.0),
+            chroma: 45.0,
+        }
+        .generate(13);
+
+        let report = map.validate();
+        assert!(report.monotone_lightness, "{:?}", report);
+        assert!(report.spread() < 0.1, "{:?}", report);
+
+        let center = JCh::from(map.colors[6]);
+        assert!(
+            center.C < 3.0 && (center.J - 92.0).abs() < 1.0,
+            "{:?}",
+            center
+        );
+        assert!((JCh::from(map.colors[0]).h - 250.0).abs() < 5.0);
+        assert!((JCh::from(map.colors[12]).h - 30.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn cyclic() {
+        let map = Design::Cyclic {
+            lightness: 65.0,
+            chroma: 40.0,
+            hue: 0.0,
+        }
+        .generate(24);
+        assert_eq!(map.colors.len(), 24);
+
+        // the wrap-around step is as long as all the others
+        let report = map.validate();
+        assert!(report.spread() < 0.2, "{:?}", report);
+        for color in &map.colors {
+            assert!((JCh::from(*color).J - 65.0).abs() < 1.0);
+        }
+    }
+
+    #[test]
+    fn validate_finds_problems() {
+        // a jet-like rainbow, whose lightness rises and falls
+        let jet: Vec<sRGB> = [
+            [0, 0, 143],
+            [0, 0, 255],
+            [0, 255, 255],
+            [255, 255, 0],
+            [255, 0, 0],
+            [128, 0, 0],
+        ]
+        .iter()
+        .map(|rgb| sRGB::from(*rgb))
+        .collect();
+        let report = Colormap::new(jet, Kind::Sequential).validate();
+        assert!(!report.monotone_lightness);
+        assert_eq!(report.lightness_reversals, 2);
+        assert!(report.spread() > 0.5);
+
+        let report = Colormap::new(Vec::new(), Kind::Sequential).validate();
+        assert_eq!((report.min_delta_e, report.mean_delta_e), (0.0, 0.0));
+    }
+
+    #[test]
+    fn exports() {
+        let map = Colormap::new(
+            [[0, 0, 0], [255, 128, 0], [255, 255, 255]]
+                .iter()
+                .map(|rgb| sRGB::from(*rgb))
+                .collect(),
+            Kind::Sequential,
+        );
+
+        assert_eq!(
+            map.to_csv(),
+            "position,r,g,b,hex\n0,0,0,0,#000000\n0.5,255,128,0,#ff8000\n1,255,255,255,#ffffff\n"
+        );
+        assert_eq!(
+            map.to_json("my \"map\""),
+            r##"{"name":"my \"map\"","colors":["#000000","#ff8000","#ffffff"]}"##
+        );
+        assert_eq!(
+            map.to_paraview("ramp"),
+            r#"[{"ColorSpace":"Lab","Name":"ramp","RGBPoints":[0,0.000000,0.000000,0.000000,0.5,1.000000,0.501961,0.000000,1,1.000000,1.000000,1.000000]}]"#
+        );
+
+        let python = map.to_matplotlib("ramp");
+        assert!(python.contains("    [1.000000, 0.501961, 0.000000],\n"));
+        assert!(python.ends_with("ramp = ListedColormap(cm_data, name=\"ramp\")\n"));
+        let python = map.to_matplotlib("my map\nimport os");
+        assert!(python.ends_with(
+            "my_map_import_os = ListedColormap(cm_data, name=\"my map\\u000aimport os\")\n"
+        ));
+        assert_eq!(python_identifier("2-tone"), "_2_tone");
+        assert_eq!(python_identifier("lambda"), "_lambda");
+        assert_eq!(python_identifier(""), "_");
+        assert_eq!(python_identifier("viridis_r"), "viridis_r");
+
+        // the exports parse as JSON
+        let json: serde_json::Value = serde_json::from_str(&map.to_json("ramp")).unwrap();
+        assert_eq!(json["colors"][1], "#ff8000");
+        let preset: serde_json::Value = serde_json::from_str(&map.to_paraview("ramp")).unwrap();
+        assert_eq!(preset[0]["RGBPoints"].as_array().unwrap().len(), 12);
+    }
+}
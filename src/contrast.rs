@@ -0,0 +1,273 @@
+//! Text contrast: the WCAG 2.1 contrast ratio, and APCA lightness contrast from the WCAG 3 draft.
+//!
+//! ```
+//! use kasi_kule::contrast::{apca, wcag_ratio, Level};
+//!
+//! let ratio: f32 = wcag_ratio([119, 119, 119], [255, 255, 255]);
+//! assert!((ratio - 4.48).abs() < 0.01);
+//! assert!(!Level::AA.passes(ratio, false));
+//! assert!(Level::AA.passes(ratio, true));
+//!
+//! // APCA is signed: positive for dark text on a light background
+//! let lc: f32 = apca([136, 136, 136], [255, 255, 255]);
+//! assert!((lc - 63.06).abs() < 0.1);
+//! ```
+//!
+//! [`adjust_lightness`] finds the foreground lightness that reaches a target contrast, holding hue and chroma.
+use crate::gamut::GamutMapping;
+use crate::{sRGB, Float, JCh, LinearRGB, XYZ};
+
+/// A WCAG 2.1 conformance level for text contrast.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Level {
+    AA,
+    AAA,
+}
+
+impl Level {
+    /// The smallest contrast ratio that passes, for normal or large text
+    /// (at least 18 point, or 14 point bold).
+    pub fn min_ratio<F: Float>(self, large_text: bool) -> F {
+        F::from_f64(match (self, large_text) {
+            (Level::AA, false) => 4.5,
+            (Level::AA, true) => 3.0,
+            (Level::AAA, false) => 7.0,
+            (Level::AAA, true) => 4.5,
+        })
+    }
+
+    pub fn passes<F: Float>(self, ratio: F, large_text: bool) -> bool {
+        ratio >= self.min_ratio(large_text)
+    }
+}
+
+/// The relative luminance of a color, from 0 for black to 1 for white.
+pub fn relative_luminance<F: Float>(rgb: impl Into<sRGB>) -> F {
+    XYZ::from(&LinearRGB::<F>::from_srgb(rgb)).y / F::from_f64(100.0)
+}
+
+/// The WCAG 2.1 contrast ratio between two colors, from 1 to 21. The order of the colors doesn't matter.
+pub fn wcag_ratio<F: Float>(a: impl Into<sRGB>, b: impl Into<sRGB>) -> F {
+    let offset = F::from_f64(0.05);
+    let (a, b) = (relative_luminance::<F>(a), relative_luminance::<F>(b));
+    let (lighter, darker) = if a > b { (a, b) } else { (b, a) };
+    (lighter + offset) / (darker + offset)
+}
+
+// the constants of APCA-W3 0.0.98G-4g
+const APCA_TRC: f64 = 2.4;
+const APCA_COEFFICIENTS: [f64; 3] = [0.2126729, 0.7151522, 0.0721750];
+const APCA_NORMAL_BACKGROUND: f64 = 0.56;
+const APCA_NORMAL_TEXT: f64 = 0.57;
+const APCA_REVERSE_BACKGROUND: f64 = 0.65;
+const APCA_REVERSE_TEXT: f64 = 0.62;
+const APCA_BLACK_THRESHOLD: f64 = 0.022;
+const APCA_BLACK_CLAMP: f64 = 1.414;
+const APCA_SCALE: f64 = 1.14;
+const APCA_OFFSET: f64 = 0.027;
+const APCA_MIN_DELTA_Y: f64 = 0.0005;
+const APCA_LOW_CLIP: f64 = 0.1;
+
+/// APCA's estimate of screen luminance, which uses a plain 2.4 gamma rather than the sRGB transfer function,
+/// with a soft clamp near black.
+fn apca_luminance<F: Float>(rgb: sRGB) -> F {
+    let c = F::from_f64;
+    let channel =
+        |code: u8, coefficient: f64| c(code as f64 / 255.0).powf(c(APCA_TRC)) * c(coefficient);
+    let y = channel(rgb.r, APCA_COEFFICIENTS[0])
+        + channel(rgb.g, APCA_COEFFICIENTS[1])
+        + channel(rgb.b, APCA_COEFFICIENTS[2]);
+
+    if y < c(APCA_BLACK_THRESHOLD) {
+        y + (c(APCA_BLACK_THRESHOLD) - y).powf(c(APCA_BLACK_CLAMP))
+    } else {
+        y
+    }
+}
+
+/// The APCA lightness contrast Lc of text on a background, roughly from -108 to 106.
+/// Positive for dark text on a light background, negative for light text on a dark one,
+/// and 0 when the contrast is too low to matter.
+pub fn apca<F: Float>(text: impl Into<sRGB>, background: impl Into<sRGB>) -> F {
+    let c = F::from_f64;
+    let (text, background) = (
+        apca_luminance::<F>(text.into()),
+        apca_luminance::<F>(background.into()),
+    );
+    if (background - text).abs() < c(APCA_MIN_DELTA_Y) {
+        return F::default();
+    }
+
+    let contrast = if background > text {
+        let s = (background.powf(c(APCA_NORMAL_BACKGROUND)) - text.powf(c(APCA_NORMAL_TEXT)))
+            * c(APCA_SCALE);
+        if s < c(APCA_LOW_CLIP) {
+            F::default()
+        } else {
+            s - c(APCA_OFFSET)
+        }
+    } else {
+        let s = (background.powf(c(APCA_REVERSE_BACKGROUND)) - text.powf(c(APCA_REVERSE_TEXT)))
+            * c(APCA_SCALE);
+        if s > c(-APCA_LOW_CLIP) {
+            F::default()
+        } else {
+            s + c(APCA_OFFSET)
+        }
+    };
+
+    contrast * c(100.0)
+}
+
+/// A contrast to reach against a background.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Target<F: Float = f32> {
+    /// A WCAG 2.1 contrast ratio, like 4.5 for AA.
+    Wcag(F),
+    /// An APCA Lc of at least this magnitude, of either polarity.
+    Apca(F),
+}
+
+impl<F: Float> Target<F> {
+    fn reached(&self, foreground: sRGB, background: sRGB) -> bool {
+        match *self {
+            Target::Wcag(ratio) => wcag_ratio::<F>(foreground, background) >= ratio,
+            Target::Apca(lc) => apca::<F>(foreground, background).abs() >= lc,
+        }
+    }
+}
+
+// bisection steps for the lightness search, which gets within 1e-4 of J
+const BISECTION_STEPS: usize = 20;
+
+/// Changes the lightness J of a foreground color, holding its hue and chroma, until it reaches `target` against
+/// `background`. Chooses whichever of a lighter or darker color needs the smaller change,
+/// and returns None if neither reaches the target.
+///
+/// Colors that already reach the target are returned as they are. Where sRGB can't show the chroma at the new
+/// lightness, chroma is reduced as by [`GamutMapping::ChromaReduction`].
+pub fn adjust_lightness<F: Float>(
+    foreground: &JCh<F>,
+    background: impl Into<sRGB>,
+    target: Target<F>,
+) -> Option<sRGB> {
+    let c = F::from_f64;
+    let background = background.into();
+    let at = |J: F| {
+        GamutMapping::ChromaReduction
+            .map(&JCh::new(J, foreground.C, foreground.h))
+            .srgb
+    };
+
+    let start = at(foreground.J);
+    if target.reached(start, background) {
+        return Some(start);
+    }
+
+    // bisects between the start and an end, which reaches the target, for the smallest change that still does
+    let search = |end: F| {
+        if !target.reached(at(end), background) {
+            return None;
+        }
+        let (mut near, mut far) = (foreground.J, end);
+        for _ in 0..BISECTION_STEPS {
+            let J = (near + far) / c(2.0);
+            if target.reached(at(J), background) {
+                far = J;
+            } else {
+                near = J;
+            }
+        }
+        Some(far)
+    };
+
+    let change = |J: F| (J - foreground.J).abs();
+    match (search(F::default()), search(c(100.0))) {
+        (Some(darker), Some(lighter)) if change(lighter) < change(darker) => Some(at(lighter)),
+        (Some(J), _) | (None, Some(J)) => Some(at(J)),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wcag() {
+        let ratio = wcag_ratio::<f32>([0, 0, 0], [255, 255, 255]);
+        assert!((ratio - 21.0).abs() < 1e-3);
+        assert_eq!(wcag_ratio::<f32>([10, 20, 30], [10, 20, 30]), 1.0);
+        assert_eq!(
+            wcag_ratio::<f32>([255, 0, 0], [0, 0, 255]),
+            wcag_ratio::<f32>([0, 0, 255], [255, 0, 0])
+        );
+
+        // the colors at the AA and AAA limits on white
+        let aa = wcag_ratio::<f32>([118, 118, 118], [255, 255, 255]);
+        assert!(Level::AA.passes(aa, false) && !Level::AAA.passes(aa, false));
+        let aaa = wcag_ratio::<f32>([89, 89, 89], [255, 255, 255]);
+        assert!(Level::AAA.passes(aaa, false));
+
+        assert!((relative_luminance::<f32>([255, 255, 255]) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn apca_reference_values() {
+        // from the APCA-W3 reference implementation
+        for (text, background, expected) in [
+            ([0, 0, 0], [255, 255, 255], 106.04),
+            ([255, 255, 255], [0, 0, 0], -107.88),
+            ([136, 136, 136], [255, 255, 255], 63.06),
+            ([255, 255, 255], [136, 136, 136], -68.54),
+            ([17, 34, 51], [221, 238, 255], 91.67),
+            ([221, 238, 255], [17, 34, 51], -93.07),
+        ] {
+            let lc: f32 = apca(text, background);
+            assert!(
+                (lc - expected).abs() < 0.05,
+                "{:?} on {:?}: {}",
+                text,
+                background,
+                lc
+            );
+        }
+
+        assert_eq!(apca::<f32>([120, 120, 120], [121, 121, 121]), 0.0);
+    }
+
+    #[test]
+    fn adjusting_lightness() {
+        let white = [255, 255, 255];
+        let orange = JCh::from([255, 150, 50]);
+        assert!(wcag_ratio::<f32>([255, 150, 50], white) < 4.5);
+
+        let adjusted = adjust_lightness(&orange, white, Target::Wcag(4.5)).unwrap();
+        let ratio = wcag_ratio::<f32>(adjusted, white);
+        assert!((4.5..4.7).contains(&ratio), "{}", ratio);
+
+        // darker, with the same hue
+        let result = JCh::from(adjusted);
+        assert!(result.J < orange.J);
+        assert!((result.h - orange.h).abs() < 3.0);
+
+        // on black, it gets lighter instead
+        let on_black =
+            adjust_lightness(&JCh::from([60, 30, 120]), [0, 0, 0], Target::Apca(60.0)).unwrap();
+        let lc: f32 = apca(on_black, [0, 0, 0]);
+        assert!((-63.0..=-60.0).contains(&lc), "{}", lc);
+
+        // already enough
+        let black = JCh::from([0, 0, 0]);
+        assert_eq!(
+            adjust_lightness(&black, white, Target::Wcag(7.0)),
+            Some(sRGB::from([0, 0, 0]))
+        );
+
+        // mid grey can't reach 21:1 either way
+        assert_eq!(
+            adjust_lightness(&orange, [128, 128, 128], Target::Wcag(21.0)),
+            None
+        );
+    }
+}
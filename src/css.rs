@@ -0,0 +1,1142 @@
+//! Parsing and formatting of [CSS color](https://www.w3.org/TR/css-color-4/) strings.
+//!
+//! [`CssColor`] parses hex colors, named colors and the `rgb()`, `hsl()`, `lab()`, `oklch()` and `color()` functions,
+//! keeping the color in the space it was written in.
+//! It converts into [`XYZ`] (and so into the CAM02 spaces) and back:
+//! ```
+//! use kasi_kule::css::CssColor;
+//! use kasi_kule::{sRGB, Jab, UCS};
+//!
+//! let red: CssColor = "oklch(62.8% 0.2577 29.23)".parse().unwrap();
+//! let jab = red.to_jab::<UCS, f32>();
+//! assert!(jab.squared_difference(&Jab::<UCS>::from([255, 0, 0])) < 0.1);
+//!
+//! // sRGB parses straight from any CSS color inside its gamut, and formats as hex
+//! let teal: sRGB = "rgb(0 128 128)".parse().unwrap();
+//! assert_eq!(teal.to_string(), "#008080");
+//! ```
+//!
+//! Like the CSS Color 4 spec, `lab()` is relative to a D50 white, and converted to D65 with a Bradford transform.
+use core::fmt;
+use core::str::FromStr;
+
+use crate::utils::{delinearize, linearize};
+use crate::{sRGB, Float, JCh, Jab, JabSpace, LinearRGB, LMS, XYZ};
+
+/// The color space a [`CssColor`] was written in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorSpace {
+    /// Gamma encoded sRGB, from hex colors, named colors, `rgb()`, `hsl()` and `color(srgb ...)`.
+    Srgb,
+    /// `color(srgb-linear ...)`.
+    SrgbLinear,
+    /// `color(display-p3 ...)`.
+    DisplayP3,
+    /// `color(xyz-d50 ...)`, with Y = 1 for the reference white.
+    XyzD50,
+    /// `color(xyz-d65 ...)` or `color(xyz ...)`, with Y = 1 for the reference white.
+    XyzD65,
+    /// CIELAB, relative to D50.
+    Lab,
+    /// The polar form of OKLab.
+    Oklch,
+}
+
+/// A color parsed from CSS.
+///
+/// The components are in the ranges CSS uses for the space: 0-1 for the RGB spaces and XYZ,
+/// L (0-100), a, b for `lab()`, and L (0-1), C, h (degrees) for `oklch()`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CssColor {
+    pub space: ColorSpace,
+    pub components: [f32; 3],
+    /// Opacity, from 0 (transparent) to 1 (opaque).
+    pub alpha: f32,
+}
+
+/// Why a string couldn't be parsed as a CSS color.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The string was empty, or only whitespace.
+    Empty,
+    /// A hex color didn't have 3, 4, 6 or 8 digits. Holds the number of characters after the `#`.
+    InvalidHexLength(usize),
+    /// A hex color had a character that isn't a hex digit.
+    InvalidHexDigit,
+    /// The string isn't one of the CSS named colors.
+    UnknownName,
+    /// The function isn't one of `rgb()`, `rgba()`, `hsl()`, `hsla()`, `lab()`, `oklch()` or `color()`.
+    UnknownFunction,
+    /// `color()` was given a color space other than `srgb`, `srgb-linear`, `display-p3`, `xyz`, `xyz-d50` or `xyz-d65`.
+    UnknownColorSpace,
+    /// A function was missing its closing parenthesis, or had text after it.
+    UnbalancedParenthesis,
+    /// A function had the wrong number of color channels, not counting alpha.
+    ComponentCount { expected: usize, found: usize },
+    /// A component couldn't be parsed, or has a type its position doesn't accept.
+    /// Channels are numbered from 0, and alpha is 3.
+    InvalidComponent(usize),
+    /// Commas were mixed with the space separated syntax, or used in a function that only has the space separated syntax.
+    InvalidSeparator,
+    /// The legacy comma separated `rgb()` syntax mixed numbers and percentages.
+    MixedUnits,
+    /// The color is valid, but can't be represented in the requested space.
+    OutOfGamut,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => f.write_str("empty color string"),
+            ParseError::InvalidHexLength(len) => {
+                write!(f, "hex colors need 3, 4, 6 or 8 digits, found {}", len)
+            }
+            ParseError::InvalidHexDigit => f.write_str("invalid hex digit"),
+            ParseError::UnknownName => f.write_str("unknown color name"),
+            ParseError::UnknownFunction => f.write_str("unknown color function"),
+            ParseError::UnknownColorSpace => f.write_str("unknown color() space"),
+            ParseError::UnbalancedParenthesis => {
+                f.write_str("color function has unbalanced parentheses")
+            }
+            ParseError::ComponentCount { expected, found } => {
+                write!(f, "expected {} color channels, found {}", expected, found)
+            }
+            ParseError::InvalidComponent(3) => f.write_str("invalid alpha"),
+            ParseError::InvalidComponent(index) => write!(f, "invalid color channel {}", index),
+            ParseError::InvalidSeparator => f.write_str("invalid mix of separators"),
+            ParseError::MixedUnits => f.write_str("legacy rgb() can't mix numbers and percentages"),
+            ParseError::OutOfGamut => f.write_str("color is out of gamut"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+type Matrix = [[f64; 3]; 3];
+
+// the matrices below are from the CSS Color 4 spec's sample code
+const P3_TO_XYZ: Matrix = [
+    [0.4865709486482162, 0.26566769316909306, 0.1982172852343625],
+    [0.2289745640697488, 0.6917385218365064, 0.079286914093745],
+    [0.0, 0.04511338185890264, 1.043944368900976],
+];
+
+const XYZ_TO_P3: Matrix = [
+    [2.493496911941425, -0.9313836179191239, -0.40271078445071684],
+    [
+        -0.8294889695615747,
+        1.7626640603183463,
+        0.023624685841943577,
+    ],
+    [
+        0.03584583024378447,
+        -0.07617238926804182,
+        0.9568845240076872,
+    ],
+];
+
+// Bradford chromatic adaptation between the D50 and D65 whites
+const D50_TO_D65: Matrix = [
+    [
+        0.9554734527042182,
+        -0.023098536874261423,
+        0.0632593086610217,
+    ],
+    [
+        -0.028369706963208136,
+        1.0099954580058226,
+        0.021041398966943008,
+    ],
+    [
+        0.012314001688319899,
+        -0.020507696433477912,
+        1.3303659366080753,
+    ],
+];
+
+const D65_TO_D50: Matrix = [
+    [
+        1.0479298208405488,
+        0.022946793341019088,
+        -0.05019222954313557,
+    ],
+    [
+        0.029627815688159344,
+        0.990434484573249,
+        -0.01707382502938514,
+    ],
+    [
+        -0.009243058152591178,
+        0.015055144896577895,
+        0.7518742899580008,
+    ],
+];
+
+const XYZ_TO_OKLMS: Matrix = [
+    [0.819022437996703, 0.3619062600528904, -0.1288737815209879],
+    [0.0329836539323885, 0.9292868615863434, 0.0361446663506424],
+    [0.0481771893596242, 0.2642395317527308, 0.6335478284694309],
+];
+
+const OKLMS_TO_XYZ: Matrix = [
+    [1.2268798758459243, -0.5578149944602171, 0.2813910456659647],
+    [-0.0405757452148008, 1.112286803280317, -0.0717110580655164],
+    [-0.0763729366746601, -0.4214933324022432, 1.5869240198367816],
+];
+
+const OKLMS_TO_OKLAB: Matrix = [
+    [0.210454268309314, 0.7936177747023054, -0.0040720430116193],
+    [1.9779985324311684, -2.42859224204858, 0.450593709617411],
+    [0.0259040424655478, 0.7827717124575296, -0.8086757549230774],
+];
+
+const OKLAB_TO_OKLMS: Matrix = [
+    [1.0, 0.3963377773761749, 0.2158037573099136],
+    [1.0, -0.1055613458156586, -0.0638541728258133],
+    [1.0, -0.0894841775298119, -1.2914855480194092],
+];
+
+// the D50 white as xy (0.3457, 0.3585), and the CIELAB constants as exact fractions
+const D50_WHITE: [f64; 3] = [0.3457 / 0.3585, 1.0, (1.0 - 0.3457 - 0.3585) / 0.3585];
+const LAB_KAPPA: f64 = 24389.0 / 27.0;
+const LAB_EPSILON: f64 = 216.0 / 24389.0;
+
+fn multiply<F: Float>(m: &Matrix, v: [F; 3]) -> [F; 3] {
+    let c = F::from_f64;
+    let row = |r: &[f64; 3]| c(r[0]) * v[0] + c(r[1]) * v[1] + c(r[2]) * v[2];
+    [row(&m[0]), row(&m[1]), row(&m[2])]
+}
+
+fn cbrt<F: Float>(v: F) -> F {
+    let root = v.abs().powf(F::from_f64(1.0 / 3.0));
+    if v < F::default() {
+        -root
+    } else {
+        root
+    }
+}
+
+fn lab_to_xyz_d50<F: Float>([l, a, b]: [F; 3]) -> [F; 3] {
+    let c = F::from_f64;
+    let f1 = (l + c(16.0)) / c(116.0);
+    let f0 = a / c(500.0) + f1;
+    let f2 = f1 - b / c(200.0);
+
+    let component = |f: F| {
+        if f.powi(3) > c(LAB_EPSILON) {
+            f.powi(3)
+        } else {
+            (c(116.0) * f - c(16.0)) / c(LAB_KAPPA)
+        }
+    };
+    let y = if l > c(LAB_KAPPA * LAB_EPSILON) {
+        f1.powi(3)
+    } else {
+        l / c(LAB_KAPPA)
+    };
+
+    [
+        component(f0) * c(D50_WHITE[0]),
+        y * c(D50_WHITE[1]),
+        component(f2) * c(D50_WHITE[2]),
+    ]
+}
+
+fn xyz_d50_to_lab<F: Float>(xyz: [F; 3]) -> [F; 3] {
+    let c = F::from_f64;
+    let f = |v: F, white: f64| {
+        let v = v / c(white);
+        if v > c(LAB_EPSILON) {
+            cbrt(v)
+        } else {
+            (c(LAB_KAPPA) * v + c(16.0)) / c(116.0)
+        }
+    };
+    let (f0, f1, f2) = (
+        f(xyz[0], D50_WHITE[0]),
+        f(xyz[1], D50_WHITE[1]),
+        f(xyz[2], D50_WHITE[2]),
+    );
+
+    [
+        c(116.0) * f1 - c(16.0),
+        c(500.0) * (f0 - f1),
+        c(200.0) * (f1 - f2),
+    ]
+}
+
+fn oklch_to_xyz<F: Float>([l, chroma, hue]: [F; 3]) -> [F; 3] {
+    let hue = hue * F::PI / F::from_f64(180.0);
+    let lms = multiply(&OKLAB_TO_OKLMS, [l, chroma * hue.cos(), chroma * hue.sin()]);
+    multiply(&OKLMS_TO_XYZ, lms.map(|v| v.powi(3)))
+}
+
+fn xyz_to_oklch<F: Float>(xyz: [F; 3]) -> [F; 3] {
+    let c = F::from_f64;
+    let [l, a, b] = multiply(&OKLMS_TO_OKLAB, multiply(&XYZ_TO_OKLMS, xyz).map(cbrt));
+
+    let mut hue = b.atan2(a) * c(180.0) / F::PI;
+    if hue < F::default() {
+        hue += c(360.0);
+    }
+
+    [l, (a * a + b * b).sqrt(), hue]
+}
+
+impl CssColor {
+    /// An opaque color with the given components.
+    pub const fn new(space: ColorSpace, components: [f32; 3]) -> CssColor {
+        CssColor {
+            space,
+            components,
+            alpha: 1.0,
+        }
+    }
+
+    /// Converts into CIEXYZ (0-100, D65), the entry point into the CAM02 conversions.
+    /// Alpha is dropped.
+    pub fn to_xyz<F: Float>(&self) -> XYZ<F> {
+        let c = F::from_f64;
+        let [x, y, z] = self.components.map(|v| c(v as f64));
+
+        let xyz = match self.space {
+            ColorSpace::Srgb => {
+                return XYZ::from(&LinearRGB {
+                    r: linearize(x),
+                    g: linearize(y),
+                    b: linearize(z),
+                })
+            }
+            ColorSpace::SrgbLinear => return XYZ::from(&LinearRGB { r: x, g: y, b: z }),
+            ColorSpace::DisplayP3 => multiply(&P3_TO_XYZ, [x, y, z].map(linearize)),
+            ColorSpace::XyzD50 => multiply(&D50_TO_D65, [x, y, z]),
+            ColorSpace::XyzD65 => [x, y, z],
+            ColorSpace::Lab => multiply(&D50_TO_D65, lab_to_xyz_d50([x, y, z])),
+            ColorSpace::Oklch => oklch_to_xyz([x, y, z]),
+        };
+
+        XYZ {
+            x: xyz[0] * c(100.0),
+            y: xyz[1] * c(100.0),
+            z: xyz[2] * c(100.0),
+        }
+    }
+
+    /// Converts a CIEXYZ (0-100, D65) color into the given space, with full opacity.
+    pub fn from_xyz<F: Float>(xyz: &XYZ<F>, space: ColorSpace) -> CssColor {
+        let c = F::from_f64;
+        let v = [xyz.x / c(100.0), xyz.y / c(100.0), xyz.z / c(100.0)];
+
+        let components = match space {
+            ColorSpace::Srgb => {
+                let rgb = LinearRGB::from(xyz);
+                [rgb.r, rgb.g, rgb.b].map(delinearize)
+            }
+            ColorSpace::SrgbLinear => {
+                let rgb = LinearRGB::from(xyz);
+                [rgb.r, rgb.g, rgb.b]
+            }
+            ColorSpace::DisplayP3 => multiply(&XYZ_TO_P3, v).map(delinearize),
+            ColorSpace::XyzD50 => multiply(&D65_TO_D50, v),
+            ColorSpace::XyzD65 => v,
+            ColorSpace::Lab => xyz_d50_to_lab(multiply(&D65_TO_D50, v)),
+            ColorSpace::Oklch => xyz_to_oklch(v),
+        };
+
+        CssColor::new(space, components.map(|v| v.to_f64() as f32))
+    }
+
+    /// Converts into sRGB, dropping alpha.
+    /// Returns `None` if the color is outside the sRGB gamut by more than half a code value.
+    pub fn to_srgb(&self) -> Option<sRGB> {
+        match self.space {
+            ColorSpace::Srgb => {
+                let encode = |c: f32| {
+                    let code = c * 255.0;
+                    if !(-0.5..=255.5).contains(&code) {
+                        return None;
+                    }
+                    Some((code + 0.5).clamp(0.0, 255.0) as u8)
+                };
+                let [r, g, b] = self.components;
+
+                Some(sRGB {
+                    r: encode(r)?,
+                    g: encode(g)?,
+                    b: encode(b)?,
+                })
+            }
+            ColorSpace::SrgbLinear => {
+                let [r, g, b] = self.components;
+                LinearRGB { r, g, b }.to_srgb()
+            }
+            _ => LinearRGB::from(&self.to_xyz::<f32>()).to_srgb(),
+        }
+    }
+
+    /// Converts into a CAM02 Jab space.
+    pub fn to_jab<S: JabSpace, F: Float>(&self) -> Jab<S, F> {
+        Jab::from(&JCh::from(&LMS::from(&self.to_xyz::<F>())))
+    }
+}
+
+impl From<&sRGB> for CssColor {
+    fn from(rgb: &sRGB) -> CssColor {
+        CssColor::new(
+            ColorSpace::Srgb,
+            [rgb.r, rgb.g, rgb.b].map(|c| c as f32 / 255.0),
+        )
+    }
+}
+
+impl FromStr for CssColor {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<CssColor, ParseError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseError::Empty);
+        }
+
+        if let Some(hex) = s.strip_prefix('#') {
+            return parse_hex(hex);
+        }
+
+        match s.find('(') {
+            Some(open) => {
+                let body = s[open + 1..]
+                    .strip_suffix(')')
+                    .filter(|body| !body.contains(['(', ')']))
+                    .ok_or(ParseError::UnbalancedParenthesis)?;
+                parse_function(&s[..open], body)
+            }
+            None if s.eq_ignore_ascii_case("transparent") => Ok(CssColor {
+                space: ColorSpace::Srgb,
+                components: [0.0; 3],
+                alpha: 0.0,
+            }),
+            None => NAMED_COLORS
+                .binary_search_by(|(name, _)| {
+                    name.bytes().cmp(s.bytes().map(|b| b.to_ascii_lowercase()))
+                })
+                .map(|i| CssColor::from(&sRGB::from(NAMED_COLORS[i].1)))
+                .map_err(|_| ParseError::UnknownName),
+        }
+    }
+}
+
+impl FromStr for sRGB {
+    type Err = ParseError;
+
+    /// Parses any CSS color inside the sRGB gamut, dropping alpha.
+    fn from_str(s: &str) -> Result<sRGB, ParseError> {
+        s.parse::<CssColor>()?
+            .to_srgb()
+            .ok_or(ParseError::OutOfGamut)
+    }
+}
+
+/// Formats as a `#rrggbb` hex color.
+impl fmt::Display for sRGB {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/// Formats as the function for its space, which parses back into the same color.
+/// Components are rounded to 4 decimal places.
+impl fmt::Display for CssColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [x, y, z] = self.components.map(Num);
+        match self.space {
+            ColorSpace::Srgb => {
+                let [r, g, b] = self.components.map(|c| Num(c * 255.0));
+                write!(f, "rgb({} {} {}", r, g, b)?
+            }
+            ColorSpace::SrgbLinear => write!(f, "color(srgb-linear {} {} {}", x, y, z)?,
+            ColorSpace::DisplayP3 => write!(f, "color(display-p3 {} {} {}", x, y, z)?,
+            ColorSpace::XyzD50 => write!(f, "color(xyz-d50 {} {} {}", x, y, z)?,
+            ColorSpace::XyzD65 => write!(f, "color(xyz-d65 {} {} {}", x, y, z)?,
+            ColorSpace::Lab => write!(f, "lab({} {} {}", x, y, z)?,
+            ColorSpace::Oklch => write!(f, "oklch({} {} {}", x, y, z)?,
+        }
+
+        if self.alpha < 1.0 {
+            write!(f, " / {}", Num(self.alpha))?;
+        }
+        f.write_str(")")
+    }
+}
+
+/// Formats a number with at most 4 decimal places and no trailing zeros.
+struct Num(f32);
+
+impl fmt::Display for Num {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let scaled = self.0 as f64 * 10_000.0;
+        let scaled = if scaled < 0.0 {
+            scaled - 0.5
+        } else {
+            scaled + 0.5
+        } as i64;
+        let sign = if scaled < 0 { "-" } else { "" };
+        let scaled = scaled.unsigned_abs();
+
+        write!(f, "{}{}", sign, scaled / 10_000)?;
+        let (mut fraction, mut digits) = (scaled % 10_000, 4);
+        if fraction != 0 {
+            while fraction % 10 == 0 {
+                fraction /= 10;
+                digits -= 1;
+            }
+            write!(f, ".{:0width$}", fraction, width = digits)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<CssColor, ParseError> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ParseError::InvalidHexDigit);
+    }
+
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap_or(0);
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0);
+    let (rgb, alpha) = match hex.len() {
+        3 => ([digit(0) * 17, digit(1) * 17, digit(2) * 17], 255),
+        4 => ([digit(0) * 17, digit(1) * 17, digit(2) * 17], digit(3) * 17),
+        6 => ([byte(0), byte(2), byte(4)], 255),
+        8 => ([byte(0), byte(2), byte(4)], byte(6)),
+        len => return Err(ParseError::InvalidHexLength(len)),
+    };
+
+    Ok(CssColor {
+        alpha: alpha as f32 / 255.0,
+        ..CssColor::from(&sRGB::from(rgb))
+    })
+}
+
+fn parse_function(name: &str, body: &str) -> Result<CssColor, ParseError> {
+    let is = |function: &str| name.eq_ignore_ascii_case(function);
+
+    if is("rgb") || is("rgba") {
+        parse_rgb(Arguments::split(body)?)
+    } else if is("hsl") || is("hsla") {
+        parse_hsl(Arguments::split(body)?)
+    } else if is("lab") {
+        parse_lab(Arguments::split_modern(body)?)
+    } else if is("oklch") {
+        parse_oklch(Arguments::split_modern(body)?)
+    } else if is("color") {
+        let body = body.trim_start();
+        let (space, rest) = body
+            .split_once(|c: char| c.is_ascii_whitespace())
+            .unwrap_or((body, ""));
+        parse_color(space, Arguments::split_modern(rest)?)
+    } else {
+        Err(ParseError::UnknownFunction)
+    }
+}
+
+/// A component, before it's resolved against what its position accepts.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Value {
+    Number(f32),
+    Percentage(f32),
+    /// An angle with a unit, in degrees.
+    Angle(f32),
+    /// The `none` keyword, which resolves to 0.
+    None,
+}
+
+impl Value {
+    fn parse(token: &str, index: usize) -> Result<Value, ParseError> {
+        let number = |s: &str| {
+            let valid = !s.is_empty()
+                && s.bytes()
+                    .all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b));
+            valid
+                .then(|| s.parse::<f32>().ok())
+                .flatten()
+                .filter(|n| n.is_finite())
+                .ok_or(ParseError::InvalidComponent(index))
+        };
+
+        if token.eq_ignore_ascii_case("none") {
+            return Ok(Value::None);
+        }
+        if let Some(percentage) = token.strip_suffix('%') {
+            return number(percentage).map(Value::Percentage);
+        }
+
+        // grad has to be checked before rad
+        for (unit, degrees) in [
+            ("deg", 1.0),
+            ("grad", 0.9),
+            ("rad", 180.0 / core::f32::consts::PI),
+            ("turn", 360.0),
+        ] {
+            let split = token.len().saturating_sub(unit.len());
+            match (token.get(..split), token.get(split..)) {
+                (Some(angle), Some(suffix)) if suffix.eq_ignore_ascii_case(unit) => {
+                    return number(angle).map(|angle| Value::Angle(angle * degrees))
+                }
+                _ => {}
+            }
+        }
+
+        number(token).map(Value::Number)
+    }
+
+    /// Resolves a channel that takes a number or a percentage, with 100% being `percent`.
+    fn number_or_percentage(self, index: usize, percent: f32) -> Result<f32, ParseError> {
+        match self {
+            Value::Number(n) => Ok(n),
+            Value::Percentage(p) => Ok(p / 100.0 * percent),
+            Value::None => Ok(0.0),
+            Value::Angle(_) => Err(ParseError::InvalidComponent(index)),
+        }
+    }
+
+    /// Resolves a hue, in degrees, into [0, 360).
+    fn hue(self, index: usize) -> Result<f32, ParseError> {
+        let hue = match self {
+            Value::Number(degrees) | Value::Angle(degrees) => degrees % 360.0,
+            Value::None => 0.0,
+            Value::Percentage(_) => return Err(ParseError::InvalidComponent(index)),
+        };
+
+        Ok(if hue < 0.0 { hue + 360.0 } else { hue })
+    }
+}
+
+/// The arguments of a color function: three channels, and an optional alpha.
+struct Arguments<'a> {
+    channels: [&'a str; 3],
+    alpha: Option<&'a str>,
+    /// Whether the arguments were comma separated, as in `rgb(255, 0, 0)`.
+    legacy: bool,
+}
+
+impl<'a> Arguments<'a> {
+    /// Splits either the legacy comma separated syntax, or the modern space separated one.
+    fn split(body: &'a str) -> Result<Arguments<'a>, ParseError> {
+        if !body.contains(',') {
+            return Arguments::split_modern(body);
+        }
+        if body.contains('/') {
+            return Err(ParseError::InvalidSeparator);
+        }
+
+        let mut parts = [""; 4];
+        let mut found = 0;
+        for part in body.split(',') {
+            let part = part.trim();
+            if part.is_empty() || part.contains(|c: char| c.is_ascii_whitespace()) {
+                return Err(ParseError::InvalidSeparator);
+            }
+            if let Some(slot) = parts.get_mut(found) {
+                *slot = part;
+            }
+            found += 1;
+        }
+
+        if !(3..=4).contains(&found) {
+            return Err(ParseError::ComponentCount { expected: 3, found });
+        }
+
+        Ok(Arguments {
+            channels: [parts[0], parts[1], parts[2]],
+            alpha: (found == 4).then(|| parts[3]),
+            legacy: true,
+        })
+    }
+
+    /// Splits the space separated syntax, with an optional `/ alpha`.
+    fn split_modern(body: &'a str) -> Result<Arguments<'a>, ParseError> {
+        if body.contains(',') {
+            return Err(ParseError::InvalidSeparator);
+        }
+
+        let (channels, alpha) = match body.split_once('/') {
+            Some((channels, alpha)) => {
+                let mut tokens = alpha.split_ascii_whitespace();
+                match (tokens.next(), tokens.next()) {
+                    (Some(alpha), None) if !alpha.contains('/') => (channels, Some(alpha)),
+                    _ => return Err(ParseError::InvalidComponent(3)),
+                }
+            }
+            None => (body, None),
+        };
+
+        let mut parts = [""; 3];
+        let mut found = 0;
+        for part in channels.split_ascii_whitespace() {
+            if let Some(slot) = parts.get_mut(found) {
+                *slot = part;
+            }
+            found += 1;
+        }
+
+        if found != 3 {
+            return Err(ParseError::ComponentCount { expected: 3, found });
+        }
+
+        Ok(Arguments {
+            channels: parts,
+            alpha,
+            legacy: false,
+        })
+    }
+
+    fn values(&self) -> Result<[Value; 3], ParseError> {
+        let mut values = [Value::None; 3];
+        for (index, (value, token)) in values.iter_mut().zip(self.channels).enumerate() {
+            *value = Value::parse(token, index)?;
+            // none only exists in the modern syntax
+            if self.legacy && *value == Value::None {
+                return Err(ParseError::InvalidComponent(index));
+            }
+        }
+        Ok(values)
+    }
+
+    fn alpha(&self) -> Result<f32, ParseError> {
+        let alpha = match self.alpha {
+            Some(token) => Value::parse(token, 3)?,
+            None => return Ok(1.0),
+        };
+        if self.legacy && alpha == Value::None {
+            return Err(ParseError::InvalidComponent(3));
+        }
+
+        Ok(alpha.number_or_percentage(3, 1.0)?.clamp(0.0, 1.0))
+    }
+}
+
+fn parse_rgb(args: Arguments) -> Result<CssColor, ParseError> {
+    let values = args.values()?;
+    if args.legacy {
+        let percentages = values
+            .iter()
+            .filter(|v| matches!(v, Value::Percentage(_)))
+            .count();
+        if percentages != 0 && percentages != 3 {
+            return Err(ParseError::MixedUnits);
+        }
+    }
+
+    let mut components = [0.0; 3];
+    for (index, (component, value)) in components.iter_mut().zip(values).enumerate() {
+        *component = (value.number_or_percentage(index, 255.0)? / 255.0).clamp(0.0, 1.0);
+    }
+
+    Ok(CssColor {
+        space: ColorSpace::Srgb,
+        components,
+        alpha: args.alpha()?,
+    })
+}
+
+fn parse_hsl(args: Arguments) -> Result<CssColor, ParseError> {
+    let [h, s, l] = args.values()?;
+    let h = h.hue(0)?;
+    // saturation and lightness are percentages, which the modern syntax also accepts as bare numbers
+    let percentage = |value: Value, index: usize| match value {
+        Value::Number(_) if args.legacy => Err(ParseError::InvalidComponent(index)),
+        value => Ok(value.number_or_percentage(index, 100.0)?.clamp(0.0, 100.0) / 100.0),
+    };
+    let (s, l) = (percentage(s, 1)?, percentage(l, 2)?);
+
+    // from the CSS Color 4 spec's sample code
+    let channel = |n: f32| {
+        let k = (n + h / 30.0) % 12.0;
+        let a = s * l.min(1.0 - l);
+        l - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+
+    Ok(CssColor {
+        space: ColorSpace::Srgb,
+        components: [channel(0.0), channel(8.0), channel(4.0)],
+        alpha: args.alpha()?,
+    })
+}
+
+fn parse_lab(args: Arguments) -> Result<CssColor, ParseError> {
+    let [l, a, b] = args.values()?;
+
+    Ok(CssColor {
+        space: ColorSpace::Lab,
+        components: [
+            l.number_or_percentage(0, 100.0)?.clamp(0.0, 100.0),
+            a.number_or_percentage(1, 125.0)?,
+            b.number_or_percentage(2, 125.0)?,
+        ],
+        alpha: args.alpha()?,
+    })
+}
+
+fn parse_oklch(args: Arguments) -> Result<CssColor, ParseError> {
+    let [l, c, h] = args.values()?;
+
+    Ok(CssColor {
+        space: ColorSpace::Oklch,
+        components: [
+            l.number_or_percentage(0, 1.0)?.clamp(0.0, 1.0),
+            c.number_or_percentage(1, 0.4)?.max(0.0),
+            h.hue(2)?,
+        ],
+        alpha: args.alpha()?,
+    })
+}
+
+fn parse_color(space: &str, args: Arguments) -> Result<CssColor, ParseError> {
+    let space = [
+        ("srgb", ColorSpace::Srgb),
+        ("srgb-linear", ColorSpace::SrgbLinear),
+        ("display-p3", ColorSpace::DisplayP3),
+        ("xyz", ColorSpace::XyzD65),
+        ("xyz-d50", ColorSpace::XyzD50),
+        ("xyz-d65", ColorSpace::XyzD65),
+    ]
+    .iter()
+    .find(|(name, _)| space.eq_ignore_ascii_case(name))
+    .ok_or(ParseError::UnknownColorSpace)?
+    .1;
+
+    let mut components = [0.0; 3];
+    for (index, (component, value)) in components.iter_mut().zip(args.values()?).enumerate() {
+        *component = value.number_or_percentage(index, 1.0)?;
+    }
+
+    Ok(CssColor {
+        space,
+        components,
+        alpha: args.alpha()?,
+    })
+}
+
+/// The CSS named colors, sorted for binary search.
+#[rustfmt::skip]
+const NAMED_COLORS: [(&str, [u8; 3]); 148] = [
+    ("aliceblue", [240, 248, 255]),
+    ("antiquewhite", [250, 235, 215]),
+    ("aqua", [0, 255, 255]),
+    ("aquamarine", [127, 255, 212]),
+    ("azure", [240, 255, 255]),
+    ("beige", [245, 245, 220]),
+    ("bisque", [255, 228, 196]),
+    ("black", [0, 0, 0]),
+    ("blanchedalmond", [255, 235, 205]),
+    ("blue", [0, 0, 255]),
+    ("blueviolet", [138, 43, 226]),
+    ("brown", [165, 42, 42]),
+    ("burlywood", [222, 184, 135]),
+    ("cadetblue", [95, 158, 160]),
+    ("chartreuse", [127, 255, 0]),
+    ("chocolate", [210, 105, 30]),
+    ("coral", [255, 127, 80]),
+    ("cornflowerblue", [100, 149, 237]),
+    ("cornsilk", [255, 248, 220]),
+    ("crimson", [220, 20, 60]),
+    ("cyan", [0, 255, 255]),
+    ("darkblue", [0, 0, 139]),
+    ("darkcyan", [0, 139, 139]),
+    ("darkgoldenrod", [184, 134, 11]),
+    ("darkgray", [169, 169, 169]),
+    ("darkgreen", [0, 100, 0]),
+    ("darkgrey", [169, 169, 169]),
+    ("darkkhaki", [189, 183, 107]),
+    ("darkmagenta", [139, 0, 139]),
+    ("darkolivegreen", [85, 107, 47]),
+    ("darkorange", [255, 140, 0]),
+    ("darkorchid", [153, 50, 204]),
+    ("darkred", [139, 0, 0]),
+    ("darksalmon", [233, 150, 122]),
+    ("darkseagreen", [143, 188, 143]),
+    ("darkslateblue", [72, 61, 139]),
+    ("darkslategray", [47, 79, 79]),
+    ("darkslategrey", [47, 79, 79]),
+    ("darkturquoise", [0, 206, 209]),
+    ("darkviolet", [148, 0, 211]),
+    ("deeppink", [255, 20, 147]),
+    ("deepskyblue", [0, 191, 255]),
+    ("dimgray", [105, 105, 105]),
+    ("dimgrey", [105, 105, 105]),
+    ("dodgerblue", [30, 144, 255]),
+    ("firebrick", [178, 34, 34]),
+    ("floralwhite", [255, 250, 240]),
+    ("forestgreen", [34, 139, 34]),
+    ("fuchsia", [255, 0, 255]),
+    ("gainsboro", [220, 220, 220]),
+    ("ghostwhite", [248, 248, 255]),
+    ("gold", [255, 215, 0]),
+    ("goldenrod", [218, 165, 32]),
+    ("gray", [128, 128, 128]),
+    ("green", [0, 128, 0]),
+    ("greenyellow", [173, 255, 47]),
+    ("grey", [128, 128, 128]),
+    ("honeydew", [240, 255, 240]),
+    ("hotpink", [255, 105, 180]),
+    ("indianred", [205, 92, 92]),
+    ("indigo", [75, 0, 130]),
+    ("ivory", [255, 255, 240]),
+    ("khaki", [240, 230, 140]),
+    ("lavender", [230, 230, 250]),
+    ("lavenderblush", [255, 240, 245]),
+    ("lawngreen", [124, 252, 0]),
+    ("lemonchiffon", [255, 250, 205]),
+    ("lightblue", [173, 216, 230]),
+    ("lightcoral", [240, 128, 128]),
+    ("lightcyan", [224, 255, 255]),
+    ("lightgoldenrodyellow", [250, 250, 210]),
+    ("lightgray", [211, 211, 211]),
+    ("lightgreen", [144, 238, 144]),
+    ("lightgrey", [211, 211, 211]),
+    ("lightpink", [255, 182, 193]),
+    ("lightsalmon", [255, 160, 122]),
+    ("lightseagreen", [32, 178, 170]),
+    ("lightskyblue", [135, 206, 250]),
+    ("lightslategray", [119, 136, 153]),
+    ("lightslategrey", [119, 136, 153]),
+    ("lightsteelblue", [176, 196, 222]),
+    ("lightyellow", [255, 255, 224]),
+    ("lime", [0, 255, 0]),
+    ("limegreen", [50, 205, 50]),
+    ("linen", [250, 240, 230]),
+    ("magenta", [255, 0, 255]),
+    ("maroon", [128, 0, 0]),
+    ("mediumaquamarine", [102, 205, 170]),
+    ("mediumblue", [0, 0, 205]),
+    ("mediumorchid", [186, 85, 211]),
+    ("mediumpurple", [147, 112, 219]),
+    ("mediumseagreen", [60, 179, 113]),
+    ("mediumslateblue", [123, 104, 238]),
+    ("mediumspringgreen", [0, 250, 154]),
+    ("mediumturquoise", [72, 209, 204]),
+    ("mediumvioletred", [199, 21, 133]),
+    ("midnightblue", [25, 25, 112]),
+    ("mintcream", [245, 255, 250]),
+    ("mistyrose", [255, 228, 225]),
+    ("moccasin", [255, 228, 181]),
+    ("navajowhite", [255, 222, 173]),
+    ("navy", [0, 0, 128]),
+    ("oldlace", [253, 245, 230]),
+    ("olive", [128, 128, 0]),
+    ("olivedrab", [107, 142, 35]),
+    ("orange", [255, 165, 0]),
+    ("orangered", [255, 69, 0]),
+    ("orchid", [218, 112, 214]),
+    ("palegoldenrod", [238, 232, 170]),
+    ("palegreen", [152, 251, 152]),
+    ("paleturquoise", [175, 238, 238]),
+    ("palevioletred", [219, 112, 147]),
+    ("papayawhip", [255, 239, 213]),
+    ("peachpuff", [255, 218, 185]),
+    ("peru", [205, 133, 63]),
+    ("pink", [255, 192, 203]),
+    ("plum", [221, 160, 221]),
+    ("powderblue", [176, 224, 230]),
+    ("purple", [128, 0, 128]),
+    ("rebeccapurple", [102, 51, 153]),
+    ("red", [255, 0, 0]),
+    ("rosybrown", [188, 143, 143]),
+    ("royalblue", [65, 105, 225]),
+    ("saddlebrown", [139, 69, 19]),
+    ("salmon", [250, 128, 114]),
+    ("sandybrown", [244, 164, 96]),
+    ("seagreen", [46, 139, 87]),
+    ("seashell", [255, 245, 238]),
+    ("sienna", [160, 82, 45]),
+    ("silver", [192, 192, 192]),
+    ("skyblue", [135, 206, 235]),
+    ("slateblue", [106, 90, 205]),
+    ("slategray", [112, 128, 144]),
+    ("slategrey", [112, 128, 144]),
+    ("snow", [255, 250, 250]),
+    ("springgreen", [0, 255, 127]),
+    ("steelblue", [70, 130, 180]),
+    ("tan", [210, 180, 140]),
+    ("teal", [0, 128, 128]),
+    ("thistle", [216, 191, 216]),
+    ("tomato", [255, 99, 71]),
+    ("turquoise", [64, 224, 208]),
+    ("violet", [238, 130, 238]),
+    ("wheat", [245, 222, 179]),
+    ("white", [255, 255, 255]),
+    ("whitesmoke", [245, 245, 245]),
+    ("yellow", [255, 255, 0]),
+    ("yellowgreen", [154, 205, 50]),
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::UCS;
+
+    fn srgb(s: &str) -> [u8; 3] {
+        let rgb: sRGB = s.parse().unwrap();
+        [rgb.r, rgb.g, rgb.b]
+    }
+
+    fn error(s: &str) -> ParseError {
+        s.parse::<CssColor>().unwrap_err()
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(srgb("#ff8000"), [255, 128, 0]);
+        assert_eq!(srgb("#F80"), [255, 136, 0]);
+        assert_eq!(srgb("  #00ff0080 "), [0, 255, 0]);
+        assert_eq!("#0f08".parse::<CssColor>().unwrap().alpha, 136.0 / 255.0);
+
+        assert_eq!(error("#12345"), ParseError::InvalidHexLength(5));
+        assert_eq!(error("#ggg"), ParseError::InvalidHexDigit);
+        assert_eq!(error("#"), ParseError::InvalidHexLength(0));
+    }
+
+    #[test]
+    fn named() {
+        assert_eq!(srgb("rebeccapurple"), [102, 51, 153]);
+        assert_eq!(srgb("AliceBlue"), [240, 248, 255]);
+        assert_eq!(srgb("yellowgreen"), [154, 205, 50]);
+        assert_eq!("transparent".parse::<CssColor>().unwrap().alpha, 0.0);
+
+        assert!(NAMED_COLORS.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(error("blurple"), ParseError::UnknownName);
+        assert_eq!(error(" "), ParseError::Empty);
+    }
+
+    #[test]
+    fn rgb() {
+        assert_eq!(srgb("rgb(255, 128, 0)"), [255, 128, 0]);
+        assert_eq!(srgb("rgba(100%, 50%, 0%, 0.5)"), [255, 128, 0]);
+        assert_eq!(srgb("RGB(255 128 0 / 50%)"), [255, 128, 0]);
+        assert_eq!(srgb("rgb(300 -20 none)"), [255, 0, 0]);
+        assert_eq!(srgb("rgb(100% 128 0)"), [255, 128, 0]);
+
+        let color: CssColor = "rgb(0 0 0 / 25%)".parse().unwrap();
+        assert_eq!(color.alpha, 0.25);
+
+        assert_eq!(error("rgb(100%, 128, 0)"), ParseError::MixedUnits);
+        assert_eq!(error("rgb(255, 128 0)"), ParseError::InvalidSeparator);
+        assert_eq!(error("rgb(255, 128, 0 / 1)"), ParseError::InvalidSeparator);
+        assert_eq!(error("rgb(none, 128, 0)"), ParseError::InvalidComponent(0));
+        assert_eq!(
+            error("rgb(255 128)"),
+            ParseError::ComponentCount {
+                expected: 3,
+                found: 2
+            }
+        );
+        assert_eq!(error("rgb(255 12x8 0)"), ParseError::InvalidComponent(1));
+        assert_eq!(error("rgb(255 128 0deg)"), ParseError::InvalidComponent(2));
+        assert_eq!(error("rgb(255 128 0 /)"), ParseError::InvalidComponent(3));
+        assert_eq!(error("rgb(255 128 inf)"), ParseError::InvalidComponent(2));
+        assert_eq!(error("rgb(255 128 0"), ParseError::UnbalancedParenthesis);
+        assert_eq!(error("rgb(255 128 0) x"), ParseError::UnbalancedParenthesis);
+        assert_eq!(error("rgbx(255 128 0)"), ParseError::UnknownFunction);
+    }
+
+    #[test]
+    fn hsl() {
+        assert_eq!(srgb("hsl(120, 100%, 25%)"), [0, 128, 0]);
+        assert_eq!(srgb("hsl(0.5turn 100% 50%)"), [0, 255, 255]);
+        assert_eq!(srgb("hsl(-120deg 100 50)"), [0, 0, 255]);
+        assert_eq!(srgb("hsla(200grad, 100%, 50%, 1)"), [0, 255, 255]);
+        assert_eq!(srgb("hsl(3.14159rad 100% 50%)"), [0, 255, 255]);
+        assert_eq!(srgb("hsl(30 0% 50%)"), [128, 128, 128]);
+
+        assert_eq!(error("hsl(120, 100, 25%)"), ParseError::InvalidComponent(1));
+        assert_eq!(error("hsl(10% 100% 25%)"), ParseError::InvalidComponent(0));
+    }
+
+    #[test]
+    fn lab_and_oklch() {
+        // the values CSS Color 4 gives for sRGB red
+        assert_eq!(srgb("lab(54.29% 80.82 69.91)"), [255, 0, 0]);
+        assert_eq!(srgb("oklch(62.8% 0.2577 29.23)"), [255, 0, 0]);
+        assert_eq!(srgb("oklch(1 0 none)"), [255, 255, 255]);
+        assert_eq!(srgb("lab(0 0 0)"), [0, 0, 0]);
+
+        let red = CssColor::from_xyz(&XYZ::<f32>::from([255, 0, 0]), ColorSpace::Lab);
+        let [l, a, b] = red.components;
+        assert!((l - 54.29).abs() < 0.05 && (a - 80.82).abs() < 0.1 && (b - 69.91).abs() < 0.1);
+
+        assert_eq!(error("lab(50, 20, 20)"), ParseError::InvalidSeparator);
+        assert_eq!(error("oklch(0.5 0.1 10%)"), ParseError::InvalidComponent(2));
+    }
+
+    #[test]
+    fn color_function() {
+        assert_eq!(srgb("color(srgb 1 0.5 0)"), [255, 128, 0]);
+        assert_eq!(srgb("color(srgb-linear 1 0.2158605 0)"), [255, 128, 0]);
+        assert_eq!(srgb("color(display-p3 1 1 1)"), [255, 255, 255]);
+        assert_eq!(srgb("color(xyz-d65 0.95047 1 1.08883)"), [255, 255, 255]);
+        assert_eq!(srgb("color(xyz-d50 0.9643 1 0.8251)"), [255, 255, 255]);
+        assert_eq!(srgb("color(xyz 0 0 0 / 0.1)"), [0, 0, 0]);
+
+        // display-p3 red is outside sRGB
+        assert_eq!(
+            "color(display-p3 1 0 0)".parse::<sRGB>().unwrap_err(),
+            ParseError::OutOfGamut
+        );
+        assert_eq!(
+            "color(srgb 1.2 0 0)".parse::<sRGB>().unwrap_err(),
+            ParseError::OutOfGamut
+        );
+        assert_eq!(error("color(rec2020 1 0 0)"), ParseError::UnknownColorSpace);
+        assert_eq!(
+            error("color(srgb 1 0)"),
+            ParseError::ComponentCount {
+                expected: 3,
+                found: 2
+            }
+        );
+    }
+
+    #[test]
+    fn formatting_round_trips() {
+        assert_eq!(sRGB::from([255, 128, 0]).to_string(), "#ff8000");
+        assert_eq!(
+            "#ff800080".parse::<CssColor>().unwrap().to_string(),
+            "rgb(255 128 0 / 0.502)"
+        );
+        assert_eq!(
+            "lab(54.29% 80.82 -69.9)"
+                .parse::<CssColor>()
+                .unwrap()
+                .to_string(),
+            "lab(54.29 80.82 -69.9)"
+        );
+
+        let xyz = XYZ::<f32>::from([12, 200, 99]);
+        for space in [
+            ColorSpace::Srgb,
+            ColorSpace::SrgbLinear,
+            ColorSpace::DisplayP3,
+            ColorSpace::XyzD50,
+            ColorSpace::XyzD65,
+            ColorSpace::Lab,
+            ColorSpace::Oklch,
+        ] {
+            let color = CssColor::from_xyz(&xyz, space);
+            let parsed: CssColor = color.to_string().parse().unwrap();
+            assert_eq!(parsed.space, space);
+            assert_eq!(
+                parsed.to_srgb().map(|c| [c.r, c.g, c.b]),
+                Some([12, 200, 99])
+            );
+        }
+    }
+
+    #[test]
+    fn into_jab() {
+        let jab = "teal".parse::<CssColor>().unwrap().to_jab::<UCS, f32>();
+        let expected = Jab::<UCS>::from([0, 128, 128]);
+        assert!(jab.squared_difference(&expected) < 1e-6);
+    }
+}
@@ -0,0 +1,543 @@
+//! Color vision deficiency simulation.
+//!
+//! A [`Simulation`] shows how a color looks to someone with protanopia, deuteranopia or tritanopia,
+//! or with the milder anomalous trichromacy at a severity below 1:
+//! ```
+//! use kasi_kule::cvd::{distinguishability, Deficiency, Model, Simulation};
+//!
+//! let deutan = Simulation::new(Deficiency::Deutan, 1.0, Model::Machado2009);
+//! let red = deutan.srgb([200, 40, 40]);
+//! let green = deutan.srgb([90, 120, 40]);
+//! assert!((red.r as i32 - green.r as i32).abs() < 30);
+//!
+//! // red and green are far apart for normal vision, but close together for every red-green deficiency
+//! let pair = distinguishability([200, 40, 40], [90, 120, 40], 1.0, Model::Machado2009);
+//! assert!(pair.normal > 30.0 && pair.deutan < 10.0);
+//! assert!(!pair.is_distinguishable(10.0));
+//! ```
+//!
+//! The simulations work on [`LinearRGB`], through the Hunt-Pointer-Estevez cone space ([`HPE`]) for the models that
+//! need cone responses.
+use crate::{sRGB, Float, Jab, LinearRGB, HPE, LMS, UCS, XYZ};
+
+/// The cone type that's missing, or shifted for anomalous trichromacy.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Deficiency {
+    /// Long wavelength (red) cones.
+    Protan,
+    /// Medium wavelength (green) cones.
+    Deutan,
+    /// Short wavelength (blue) cones.
+    Tritan,
+}
+
+/// How to simulate a deficiency.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Model {
+    /// Machado, Oliveira and Fernandes (2009), which models anomalous trichromacy as a shift of the cone
+    /// sensitivities. Their matrices are tabulated at steps of 0.1 severity, and interpolated in between.
+    Machado2009,
+    /// Brettel, Viénot and Mollon (1997): dichromats see colors on two half planes through white and the
+    /// monochromatic colors that look the same to them (475 nm and 575 nm, or 485 nm and 660 nm for tritans).
+    Brettel1997,
+    /// Viénot, Brettel and Mollon (1999): a single plane through white and the sRGB blue primary (the red primary for
+    /// tritans), which is faster than Brettel's model and as accurate for protans and deutans, but not tritans.
+    Vienot1999,
+}
+
+/// Machado's matrices on linear RGB, at severities 0.1 to 1 (0 being the identity).
+const MACHADO_PROTAN: [[[f64; 3]; 3]; 10] = [
+    [
+        [0.856167, 0.182038, -0.038205],
+        [0.029342, 0.955115, 0.015544],
+        [-0.002880, -0.001563, 1.004443],
+    ],
+    [
+        [0.734766, 0.334872, -0.069637],
+        [0.051840, 0.919198, 0.028963],
+        [-0.004928, -0.004209, 1.009137],
+    ],
+    [
+        [0.630323, 0.465641, -0.095964],
+        [0.069181, 0.890046, 0.040773],
+        [-0.006308, -0.007724, 1.014032],
+    ],
+    [
+        [0.539009, 0.579343, -0.118352],
+        [0.082546, 0.866121, 0.051332],
+        [-0.007136, -0.011959, 1.019095],
+    ],
+    [
+        [0.458064, 0.679578, -0.137642],
+        [0.092785, 0.846313, 0.060902],
+        [-0.007494, -0.016807, 1.024301],
+    ],
+    [
+        [0.385450, 0.769005, -0.154455],
+        [0.100526, 0.829802, 0.069673],
+        [-0.007442, -0.022190, 1.029632],
+    ],
+    [
+        [0.319627, 0.849633, -0.169261],
+        [0.106241, 0.815969, 0.077790],
+        [-0.007025, -0.028051, 1.035076],
+    ],
+    [
+        [0.259411, 0.923008, -0.182420],
+        [0.110296, 0.804340, 0.085364],
+        [-0.006276, -0.034346, 1.040622],
+    ],
+    [
+        [0.203876, 0.990338, -0.194214],
+        [0.112975, 0.794542, 0.092483],
+        [-0.005222, -0.041043, 1.046265],
+    ],
+    [
+        [0.152286, 1.052583, -0.204868],
+        [0.114503, 0.786281, 0.099216],
+        [-0.003882, -0.048116, 1.051998],
+    ],
+];
+
+const MACHADO_DEUTAN: [[[f64; 3]; 3]; 10] = [
+    [
+        [0.866435, 0.177704, -0.044139],
+        [0.049567, 0.939063, 0.011370],
+        [-0.003453, 0.007233, 0.996220],
+    ],
+    [
+        [0.760729, 0.319078, -0.079807],
+        [0.090568, 0.889315, 0.020117],
+        [-0.006027, 0.013325, 0.992702],
+    ],
+    [
+        [0.675425, 0.433850, -0.109275],
+        [0.125303, 0.847755, 0.026942],
+        [-0.007950, 0.018572, 0.989378],
+    ],
+    [
+        [0.605511, 0.528560, -0.134071],
+        [0.155318, 0.812366, 0.032316],
+        [-0.009376, 0.023176, 0.986200],
+    ],
+    [
+        [0.547494, 0.607765, -0.155259],
+        [0.181692, 0.781742, 0.036566],
+        [-0.010410, 0.027275, 0.983136],
+    ],
+    [
+        [0.498864, 0.674741, -0.173604],
+        [0.205199, 0.754872, 0.039929],
+        [-0.011131, 0.030969, 0.980162],
+    ],
+    [
+        [0.457771, 0.731899, -0.189670],
+        [0.226409, 0.731012, 0.042579],
+        [-0.011595, 0.034333, 0.977261],
+    ],
+    [
+        [0.422823, 0.781057, -0.203881],
+        [0.245752, 0.709602, 0.044646],
+        [-0.011843, 0.037423, 0.974421],
+    ],
+    [
+        [0.392952, 0.823610, -0.216562],
+        [0.263559, 0.690210, 0.046232],
+        [-0.011910, 0.040281, 0.971630],
+    ],
+    [
+        [0.367322, 0.860646, -0.227968],
+        [0.280085, 0.672501, 0.047413],
+        [-0.011820, 0.042940, 0.968881],
+    ],
+];
+
+const MACHADO_TRITAN: [[[f64; 3]; 3]; 10] = [
+    [
+        [0.926670, 0.092514, -0.019184],
+        [0.021191, 0.964503, 0.014306],
+        [0.008437, 0.054813, 0.936750],
+    ],
+    [
+        [0.895720, 0.133330, -0.029050],
+        [0.029997, 0.945400, 0.024603],
+        [0.013027, 0.104707, 0.882266],
+    ],
+    [
+        [0.905871, 0.127791, -0.033662],
+        [0.026856, 0.941251, 0.031893],
+        [0.013410, 0.148296, 0.838294],
+    ],
+    [
+        [0.948035, 0.089490, -0.037526],
+        [0.014364, 0.946792, 0.038844],
+        [0.010853, 0.193991, 0.795156],
+    ],
+    [
+        [1.017277, 0.027029, -0.044306],
+        [-0.006113, 0.958479, 0.047634],
+        [0.006379, 0.248708, 0.744913],
+    ],
+    [
+        [1.104996, -0.046633, -0.058363],
+        [-0.032137, 0.971635, 0.060503],
+        [0.001336, 0.317922, 0.680742],
+    ],
+    [
+        [1.193214, -0.109812, -0.083402],
+        [-0.058496, 0.979410, 0.079086],
+        [-0.002346, 0.403492, 0.598854],
+    ],
+    [
+        [1.257728, -0.139648, -0.118081],
+        [-0.078003, 0.975409, 0.102594],
+        [-0.003316, 0.501214, 0.502102],
+    ],
+    [
+        [1.278864, -0.125333, -0.153531],
+        [-0.084748, 0.957674, 0.127074],
+        [-0.000989, 0.601151, 0.399838],
+    ],
+    [
+        [1.255528, -0.076749, -0.178779],
+        [-0.078411, 0.930809, 0.147602],
+        [0.004733, 0.691367, 0.303900],
+    ],
+];
+
+// the CIE 1931 color matching functions at the wavelengths Brettel's half planes run through
+const XYZ_475NM: [f64; 3] = [0.1421, 0.1126, 1.0419];
+const XYZ_485NM: [f64; 3] = [0.05795, 0.1693, 0.6162];
+const XYZ_575NM: [f64; 3] = [0.8425, 0.9154, 0.0018];
+const XYZ_660NM: [f64; 3] = [0.1649, 0.0610, 0.0];
+
+/// A deficiency, how severe it is, and how to simulate it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Simulation<F: Float = f32> {
+    pub deficiency: Deficiency,
+    /// From 0 for normal vision to 1 for dichromacy. Values in between are anomalous trichromacy.
+    pub severity: F,
+    pub model: Model,
+}
+
+fn cones<F: Float>(rgb: &LinearRGB<F>) -> [F; 3] {
+    let HPE { lh, mh, sh } = HPE::from(&LMS::from(&XYZ::from(rgb)));
+    [lh, mh, sh]
+}
+
+fn xyz_cones<F: Float>(xyz: [f64; 3]) -> [F; 3] {
+    let c = F::from_f64;
+    let HPE { lh, mh, sh } = HPE::from(&LMS::from(&XYZ {
+        x: c(xyz[0]),
+        y: c(xyz[1]),
+        z: c(xyz[2]),
+    }));
+    [lh, mh, sh]
+}
+
+fn cross<F: Float>(a: &[F; 3], b: &[F; 3]) -> [F; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn clip<F: Float>(c: F) -> F {
+    let (zero, one) = (F::default(), F::from_f64(1.0));
+    if c > one {
+        one
+    } else if c > zero {
+        c
+    } else {
+        zero
+    }
+}
+
+impl<F: Float> Simulation<F> {
+    pub fn new(deficiency: Deficiency, severity: F, model: Model) -> Simulation<F> {
+        Simulation {
+            deficiency,
+            severity,
+            model,
+        }
+    }
+
+    /// Simulates the deficiency on a linear RGB color.
+    /// The result can fall slightly outside of sRGB.
+    pub fn linear(&self, rgb: &LinearRGB<F>) -> LinearRGB<F> {
+        let (zero, one) = (F::default(), F::from_f64(1.0));
+        let severity = clip(self.severity);
+        if severity == zero {
+            return *rgb;
+        }
+
+        let simulated = match self.model {
+            Model::Machado2009 => return self.machado(rgb, severity),
+            Model::Brettel1997 | Model::Vienot1999 => self.dichromat(rgb),
+        };
+
+        // anomalous trichromacy as a mix of normal vision and dichromacy
+        let mix = |normal: F, dichromat: F| (one - severity) * normal + severity * dichromat;
+        LinearRGB {
+            r: mix(rgb.r, simulated.r),
+            g: mix(rgb.g, simulated.g),
+            b: mix(rgb.b, simulated.b),
+        }
+    }
+
+    /// Simulates the deficiency on an sRGB color, clipping the result into sRGB.
+    pub fn srgb(&self, rgb: impl Into<sRGB>) -> sRGB {
+        let simulated = self.linear(&LinearRGB::from_srgb(rgb));
+        LinearRGB {
+            r: clip(simulated.r),
+            g: clip(simulated.g),
+            b: clip(simulated.b),
+        }
+        .to_srgb()
+        .unwrap_or_default()
+    }
+
+    /// ΔE in CAM02-UCS between two colors as they look with the deficiency.
+    pub fn delta_e(&self, a: impl Into<sRGB>, b: impl Into<sRGB>) -> F {
+        let a = Jab::<UCS, F>::from_srgb(self.srgb(a));
+        let b = Jab::<UCS, F>::from_srgb(self.srgb(b));
+        a.squared_difference(&b).sqrt()
+    }
+
+    fn machado(&self, rgb: &LinearRGB<F>, severity: F) -> LinearRGB<F> {
+        let c = F::from_f64;
+        let table = match self.deficiency {
+            Deficiency::Protan => &MACHADO_PROTAN,
+            Deficiency::Deutan => &MACHADO_DEUTAN,
+            Deficiency::Tritan => &MACHADO_TRITAN,
+        };
+
+        // matrices either side of the severity, with the identity at severity 0
+        let position = severity * c(10.0);
+        let step = (position.to_f64() as usize).min(9);
+        let t = position - c(step as f64);
+        let matrix = |step: usize, row: usize, column: usize| match step {
+            0 => c(if row == column { 1.0 } else { 0.0 }),
+            step => c(table[step - 1][row][column]),
+        };
+        let entry = |row, column| {
+            let low = matrix(step, row, column);
+            low + t * (matrix((step + 1).min(10), row, column) - low)
+        };
+
+        let input = [rgb.r, rgb.g, rgb.b];
+        let row = |row: usize| (0..3).fold(F::default(), |sum, i| sum + entry(row, i) * input[i]);
+        LinearRGB {
+            r: row(0),
+            g: row(1),
+            b: row(2),
+        }
+    }
+
+    /// Projects a color onto the dichromat's planes in cone space, along the axis of the missing cones.
+    fn dichromat(&self, rgb: &LinearRGB<F>) -> LinearRGB<F> {
+        let one = F::from_f64(1.0);
+        let white = cones(&LinearRGB {
+            r: one,
+            g: one,
+            b: one,
+        });
+        let color = cones(rgb);
+
+        // the missing cone, and the two that are left
+        let (missing, first, second) = match self.deficiency {
+            Deficiency::Protan => (0, 1, 2),
+            Deficiency::Deutan => (1, 0, 2),
+            Deficiency::Tritan => (2, 0, 1),
+        };
+
+        let normal = match self.model {
+            Model::Vienot1999 => {
+                let primary = match self.deficiency {
+                    Deficiency::Tritan => LinearRGB {
+                        r: one,
+                        ..LinearRGB::default()
+                    },
+                    _ => LinearRGB {
+                        b: one,
+                        ..LinearRGB::default()
+                    },
+                };
+                cross(&white, &cones(&primary))
+            }
+            _ => {
+                let (near, far) = match self.deficiency {
+                    Deficiency::Tritan => (XYZ_485NM, XYZ_660NM),
+                    _ => (XYZ_475NM, XYZ_575NM),
+                };
+                // the half plane on the same side of the white axis as the color
+                let ratio =
+                    |cones: &[F; 3]| cones[second] * white[first] - cones[first] * white[second];
+                let anchor = if ratio(&color) >= F::default() {
+                    near
+                } else {
+                    far
+                };
+                cross(&white, &xyz_cones(anchor))
+            }
+        };
+
+        let mut projected = color;
+        projected[missing] = F::default()
+            - (normal[first] * color[first] + normal[second] * color[second]) / normal[missing];
+
+        let [lh, mh, sh] = projected;
+        LinearRGB::from(&XYZ::from(&LMS::from(&HPE { lh, mh, sh })))
+    }
+}
+
+/// ΔE in CAM02-UCS between two colors, for normal vision and with each deficiency.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Distinguishability<F: Float = f32> {
+    pub normal: F,
+    pub protan: F,
+    pub deutan: F,
+    pub tritan: F,
+}
+
+impl<F: Float> Distinguishability<F> {
+    /// The smallest difference with any of the deficiencies.
+    pub fn worst(&self) -> F {
+        let mut worst = self.protan;
+        for delta_e in [self.deutan, self.tritan] {
+            if delta_e < worst {
+                worst = delta_e;
+            }
+        }
+        worst
+    }
+
+    /// Whether the colors stay at least `threshold` apart with every deficiency.
+    pub fn is_distinguishable(&self, threshold: F) -> bool {
+        self.worst() >= threshold
+    }
+}
+
+/// How far apart two colors look with each deficiency, at the given severity.
+pub fn distinguishability<F: Float>(
+    a: impl Into<sRGB>,
+    b: impl Into<sRGB>,
+    severity: F,
+    model: Model,
+) -> Distinguishability<F> {
+    let (a, b) = (a.into(), b.into());
+    let delta_e = |deficiency| Simulation::new(deficiency, severity, model).delta_e(a, b);
+    Distinguishability {
+        normal: Jab::<UCS, F>::from_srgb(a)
+            .squared_difference(&Jab::from_srgb(b))
+            .sqrt(),
+        protan: delta_e(Deficiency::Protan),
+        deutan: delta_e(Deficiency::Deutan),
+        tritan: delta_e(Deficiency::Tritan),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DEFICIENCIES: [Deficiency; 3] =
+        [Deficiency::Protan, Deficiency::Deutan, Deficiency::Tritan];
+    const MODELS: [Model; 3] = [Model::Machado2009, Model::Brettel1997, Model::Vienot1999];
+
+    #[test]
+    fn machado_rows_keep_white() {
+        for table in [&MACHADO_PROTAN, &MACHADO_DEUTAN, &MACHADO_TRITAN] {
+            for matrix in table.iter() {
+                for row in matrix {
+                    assert!((row.iter().sum::<f64>() - 1.0).abs() < 1e-5, "{:?}", row);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn neutrals_are_kept() {
+        for deficiency in DEFICIENCIES {
+            for model in MODELS {
+                let simulation = Simulation::new(deficiency, 1.0, model);
+                for grey in [0, 60, 128, 255] {
+                    let result = simulation.srgb([grey, grey, grey]);
+                    for channel in [result.r, result.g, result.b] {
+                        assert!(
+                            (channel as i32 - grey as i32).abs() <= 2,
+                            "{:?} {:?} {} -> {:?}",
+                            deficiency,
+                            model,
+                            grey,
+                            result
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn confusion_colors() {
+        // red and green collapse for protans and deutans with every model, but not for tritans
+        for model in MODELS {
+            let pair = distinguishability([220, 50, 40], [110, 130, 40], 1.0, model);
+            let collapsed = pair.normal / 3.0;
+            assert!(
+                pair.protan < collapsed && pair.deutan < collapsed,
+                "{:?} {:?}",
+                model,
+                pair
+            );
+            assert!(pair.tritan > pair.normal / 2.0, "{:?} {:?}", model, pair);
+        }
+
+        // and blue and green for tritans
+        let pair = distinguishability([60, 150, 200], [90, 160, 120], 1.0, Model::Brettel1997);
+        assert!(pair.tritan < pair.normal / 2.0, "{:?}", pair);
+        assert!(!pair.is_distinguishable(pair.normal / 2.0));
+    }
+
+    #[test]
+    fn severity() {
+        let color = [200, 60, 90];
+        for model in MODELS {
+            let normal = Simulation::new(Deficiency::Deutan, 0.0, model).srgb(color);
+            assert_eq!(normal, sRGB::from(color));
+
+            let shift = |severity: f32| {
+                Jab::<UCS>::from_srgb(
+                    Simulation::new(Deficiency::Deutan, severity, model).srgb(color),
+                )
+                .squared_difference(&Jab::from_srgb(color))
+                .sqrt()
+            };
+            assert!(shift(0.3) > 0.5);
+            assert!(
+                shift(0.3) < shift(0.7) && shift(0.7) < shift(1.0),
+                "{:?}",
+                model
+            );
+        }
+
+        // severities between the tabulated ones interpolate
+        let at = |severity: f32| Simulation::new(Deficiency::Protan, severity, Model::Machado2009);
+        let linear = LinearRGB::from_srgb([30, 200, 120]);
+        let (low, mid, high) = (
+            at(0.5).linear(&linear),
+            at(0.55).linear(&linear),
+            at(0.6).linear(&linear),
+        );
+        assert!((mid.r - (low.r + high.r) / 2.0).abs() < 1e-5);
+    }
+
+    #[cfg(any(feature = "std", feature = "libm"))]
+    #[test]
+    fn f64_simulation() {
+        let simulation = Simulation::<f64>::new(Deficiency::Protan, 1.0, Model::Brettel1997);
+        let single = Simulation::<f32>::new(Deficiency::Protan, 1.0, Model::Brettel1997);
+        assert_eq!(simulation.srgb([200, 60, 90]), single.srgb([200, 60, 90]));
+    }
+}
@@ -0,0 +1,415 @@
+//! Perceptual differences between two images, for visual regression testing.
+//!
+//! [`difference`] computes ΔE in CAM02-SCD for every pixel, and summarises it:
+//! ```
+//! use kasi_kule::diff::difference;
+//! use kasi_kule::sRGB;
+//!
+//! let before = vec![sRGB::from([200, 100, 50]); 100];
+//! let mut after = before.clone();
+//! after[42] = sRGB::from([200, 100, 60]);
+//!
+//! let map = difference(&before, &after, 10, None);
+//! let statistics = map.statistics();
+//! assert_eq!(statistics.p95, 0.0);
+//! assert!(statistics.max > 1.0);
+//! assert_eq!(map.mask(1.0).iter().filter(|changed| **changed).count(), 1);
+//! ```
+//!
+//! With a [`SpatialFilter`], both images are first blurred the way the eye blurs them at a given viewing distance,
+//! in the spirit of S-CIELAB (Zhang and Wandell, 1996): fine patterns like dithering or antialiasing then only count
+//! for as much as they can be seen.
+use alloc::vec::Vec;
+
+use crate::{sRGB, Float, JCh, Jab, LinearRGB, LMS, SCD, XYZ};
+
+/// Per-pixel ΔE in CAM02-SCD, in row-major order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffMap {
+    pub width: usize,
+    pub height: usize,
+    pub delta_e: Vec<f32>,
+}
+
+/// A summary of a [`DiffMap`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Statistics {
+    pub mean: f32,
+    /// The 95th percentile, by nearest rank.
+    pub p95: f32,
+    pub max: f32,
+}
+
+/// Blurs images like the eye does, at a given number of pixels per degree of visual angle.
+/// The widest of the luminance kernels spreads over 4.3°, so the blur reaches 13° around each pixel.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SpatialFilter {
+    pub samples_per_degree: f32,
+}
+
+impl SpatialFilter {
+    /// The filter for a screen with `dpi` pixels per inch, seen from `distance` inches away.
+    pub fn from_viewing(dpi: f32, distance: f32) -> SpatialFilter {
+        let half_degree = core::f32::consts::PI / 360.0;
+        let tan = Float::sin(half_degree) / Float::cos(half_degree);
+        SpatialFilter {
+            samples_per_degree: dpi * distance * 2.0 * tan,
+        }
+    }
+}
+
+// Poirson and Wandell's opponent channels, from XYZ: luminance, red-green, and blue-yellow
+const OPPONENT: [[f32; 3]; 3] = [
+    [0.279, 0.72, -0.107],
+    [-0.449, 0.29, -0.077],
+    [0.086, -0.59, 0.501],
+];
+
+// the sums of Gaussians blurring each opponent channel, as (weight, spread in degrees)
+const LUMINANCE_KERNEL: &[(f32, f32)] = &[(0.921, 0.0283), (0.105, 0.133), (-0.108, 4.336)];
+const RED_GREEN_KERNEL: &[(f32, f32)] = &[(0.531, 0.0392), (0.330, 0.494)];
+const BLUE_YELLOW_KERNEL: &[(f32, f32)] = &[(0.488, 0.0536), (0.371, 0.386)];
+
+fn inverse(m: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let cofactor = |row: usize, column: usize| {
+        let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+        let (c0, c1) = ((column + 1) % 3, (column + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let determinant: f32 = (0..3)
+        .map(|column| m[0][column] * cofactor(0, column))
+        .sum();
+    [0, 1, 2].map(|row| [0, 1, 2].map(|column| cofactor(column, row) / determinant))
+}
+
+fn multiply(m: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|row| (0..3).map(|column| m[row][column] * v[column]).sum())
+}
+
+/// A 1D Gaussian `exp(-x²/spread²)`, the form S-CIELAB defines its kernels in, with `spread` in pixels,
+/// summing to 1. It's cut off at three spreads, where it's down to e⁻⁹ (about 1e-4) of its peak.
+fn gaussian(spread: f32) -> Vec<f32> {
+    let spread = if spread > 0.1 { spread } else { 0.1 };
+    let radius = (3.0 * spread) as usize + 1;
+    let mut kernel: Vec<f32> = (0..=2 * radius)
+        .map(|i| {
+            let x = i as f32 - radius as f32;
+            Float::exp(-(x * x) / (spread * spread))
+        })
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|weight| *weight /= sum);
+    kernel
+}
+
+/// Convolves a plane with a 1D kernel along rows and then along columns, repeating the edge pixels.
+fn separable_blur(plane: &[f32], width: usize, height: usize, kernel: &[f32]) -> Vec<f32> {
+    let radius = kernel.len() / 2;
+    let pass = |source: &[f32], horizontal: bool| -> Vec<f32> {
+        let mut out = alloc::vec![0.0; source.len()];
+        for y in 0..height {
+            for x in 0..width {
+                out[y * width + x] = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, weight)| {
+                        let offset = k as isize - radius as isize;
+                        let (x, y) = if horizontal {
+                            (
+                                (x as isize + offset).clamp(0, width as isize - 1) as usize,
+                                y,
+                            )
+                        } else {
+                            (
+                                x,
+                                (y as isize + offset).clamp(0, height as isize - 1) as usize,
+                            )
+                        };
+                        weight * source[y * width + x]
+                    })
+                    .sum();
+            }
+        }
+        out
+    };
+
+    pass(&pass(plane, true), false)
+}
+
+impl SpatialFilter {
+    /// Blurs the opponent channels of an image, and returns it as XYZ.
+    ///
+    /// The luminance kernel has a negative lobe, which takes pixels near dark edges below black, where CIECAM02
+    /// has no lightness. Every blurred pixel is clamped to non-negative linear sRGB, where it always has one.
+    fn apply(&self, pixels: &[sRGB], width: usize, height: usize) -> Vec<[f32; 3]> {
+        let opponent: Vec<[f32; 3]> = pixels
+            .iter()
+            .map(|rgb| {
+                let XYZ { x, y, z } = XYZ::from(&LinearRGB::from_srgb(*rgb));
+                multiply(&OPPONENT, [x, y, z])
+            })
+            .collect();
+
+        let mut channels = [LUMINANCE_KERNEL, RED_GREEN_KERNEL, BLUE_YELLOW_KERNEL]
+            .iter()
+            .enumerate()
+            .map(|(channel, kernel)| {
+                let plane: Vec<f32> = opponent.iter().map(|pixel| pixel[channel]).collect();
+                let total: f32 = kernel.iter().map(|(weight, _)| weight).sum();
+                let mut blurred = alloc::vec![0.0; plane.len()];
+                for (weight, spread) in kernel.iter() {
+                    let gaussian = gaussian(spread * self.samples_per_degree);
+                    let pass = separable_blur(&plane, width, height, &gaussian);
+                    for (sum, value) in blurred.iter_mut().zip(pass) {
+                        *sum += weight / total * value;
+                    }
+                }
+                blurred
+            });
+        let (luminance, red_green, blue_yellow) = (
+            channels.next().unwrap_or_default(),
+            channels.next().unwrap_or_default(),
+            channels.next().unwrap_or_default(),
+        );
+
+        let to_xyz = inverse(&OPPONENT);
+        (0..pixels.len())
+            .map(|i| {
+                let [x, y, z] = multiply(&to_xyz, [luminance[i], red_green[i], blue_yellow[i]]);
+                let LinearRGB { r, g, b } = LinearRGB::from(&XYZ { x, y, z });
+                let XYZ { x, y, z } = XYZ::from(&LinearRGB {
+                    r: r.max(0.0),
+                    g: g.max(0.0),
+                    b: b.max(0.0),
+                });
+                [x, y, z]
+            })
+            .collect()
+    }
+}
+
+/// ΔE in CAM02-SCD between two images of the same size, `width` pixels wide, optionally blurred first.
+///
+/// # Panics
+///
+/// Panics if the images differ in size, or don't fill whole rows of `width`.
+pub fn difference(a: &[sRGB], b: &[sRGB], width: usize, filter: Option<SpatialFilter>) -> DiffMap {
+    assert_eq!(a.len(), b.len(), "images differ in size");
+    assert!(
+        width > 0 && a.len() % width == 0,
+        "pixels don't fill whole rows"
+    );
+    let height = a.len() / width;
+
+    let jab = |xyz: &[f32; 3]| {
+        let xyz = XYZ {
+            x: xyz[0],
+            y: xyz[1],
+            z: xyz[2],
+        };
+        Jab::<SCD>::from(&JCh::from(&LMS::from(&xyz)))
+    };
+    let delta_e = match filter {
+        Some(filter) => {
+            let (a, b) = (
+                filter.apply(a, width, height),
+                filter.apply(b, width, height),
+            );
+            a.iter()
+                .zip(&b)
+                .map(|(a, b)| Float::sqrt(jab(a).squared_difference(&jab(b))))
+                .collect()
+        }
+        None => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| {
+                Float::sqrt(Jab::<SCD>::from_srgb(*a).squared_difference(&Jab::from_srgb(*b)))
+            })
+            .collect(),
+    };
+
+    DiffMap {
+        width,
+        height,
+        delta_e,
+    }
+}
+
+impl DiffMap {
+    pub fn statistics(&self) -> Statistics {
+        if self.delta_e.is_empty() {
+            return Statistics {
+                mean: 0.0,
+                p95: 0.0,
+                max: 0.0,
+            };
+        }
+
+        let mut sorted = self.delta_e.clone();
+        sorted.sort_unstable_by(f32::total_cmp);
+        let rank = (sorted.len() * 95).div_ceil(100).max(1);
+        Statistics {
+            mean: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            p95: sorted[rank - 1],
+            max: sorted[sorted.len() - 1],
+        }
+    }
+
+    /// Which pixels differ by more than `threshold`.
+    pub fn mask(&self, threshold: f32) -> Vec<bool> {
+        self.delta_e
+            .iter()
+            .map(|delta_e| *delta_e > threshold)
+            .collect()
+    }
+
+    /// The ΔE at column `x` of row `y`.
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.delta_e[y * self.width + x]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn identical_images() {
+        let image: Vec<sRGB> = (0..64)
+            .map(|i| sRGB::from([i * 4, 255 - i * 2, 128]))
+            .collect();
+        let map = difference(&image, &image, 8, None);
+        assert_eq!((map.width, map.height), (8, 8));
+        assert_eq!(map.statistics().max, 0.0);
+
+        let filtered = difference(
+            &image,
+            &image,
+            8,
+            Some(SpatialFilter {
+                samples_per_degree: 20.0,
+            }),
+        );
+        assert!(filtered.statistics().max < 1e-3);
+    }
+
+    #[test]
+    fn statistics() {
+        let map = DiffMap {
+            width: 10,
+            height: 10,
+            delta_e: (0..100).map(|i| i as f32).collect(),
+        };
+        let statistics = map.statistics();
+        assert_eq!(statistics.mean, 49.5);
+        assert_eq!(statistics.p95, 94.0);
+        assert_eq!(statistics.max, 99.0);
+        assert_eq!(
+            map.mask(89.5).iter().filter(|changed| **changed).count(),
+            10
+        );
+        assert_eq!(map.get(3, 2), 23.0);
+    }
+
+    #[test]
+    fn filtering_hides_fine_patterns() {
+        // a black and white checkerboard against the grey it averages to
+        let width = 32;
+        let checkerboard: Vec<sRGB> = (0..width * width)
+            .map(|i| {
+                let level = if (i % width + i / width) % 2 == 0 {
+                    0
+                } else {
+                    255
+                };
+                sRGB::from([level, level, level])
+            })
+            .collect();
+        let grey = alloc::vec![sRGB::from([188, 188, 188]); width * width];
+
+        let plain = difference(&checkerboard, &grey, width, None).statistics();
+        let filter = SpatialFilter::from_viewing(96.0, 24.0);
+        assert!((filter.samples_per_degree - 40.2).abs() < 0.1);
+        let filtered = difference(&checkerboard, &grey, width, Some(filter)).statistics();
+        assert!(plain.mean > 20.0);
+        assert!(
+            filtered.mean < plain.mean / 4.0,
+            "{:?} {:?}",
+            plain,
+            filtered
+        );
+
+        // but a large patch of color stays as different as it was
+        let red = alloc::vec![sRGB::from([200, 40, 40]); width * width];
+        let plain = difference(&red, &grey, width, None).statistics();
+        let filtered = difference(&red, &grey, width, Some(filter)).statistics();
+        assert!(
+            (plain.mean - filtered.mean).abs() < plain.mean * 0.1,
+            "{:?} {:?}",
+            plain,
+            filtered
+        );
+    }
+
+    #[test]
+    fn edges_stay_defined() {
+        // a black block on white, as large as the negative lobe of the luminance kernel (43 pixels at
+        // 10 pixels per degree), which takes the black side of the edge below black
+        let width = 64;
+        let block: Vec<sRGB> = (0..width * width)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                let level = if (16..48).contains(&x) && (16..48).contains(&y) {
+                    0
+                } else {
+                    255
+                };
+                sRGB::from([level, level, level])
+            })
+            .collect();
+        let filter = SpatialFilter {
+            samples_per_degree: 10.0,
+        };
+
+        let same = difference(&block, &block, width, Some(filter));
+        assert!(same.delta_e.iter().all(|delta_e| *delta_e == 0.0));
+
+        let mut changed = block.clone();
+        changed[20 * width + 40] = sRGB::from([255, 255, 255]);
+        let map = difference(&block, &changed, width, Some(filter));
+        assert!(map.delta_e.iter().all(|delta_e| delta_e.is_finite()));
+        assert!(map.statistics().max > 0.0);
+
+        // any non-negative linear sRGB, even beyond white, has a defined appearance
+        for rgb in [
+            [0.0f32, 0.0, 3.0],
+            [2.0, 0.0, 0.0],
+            [0.0, 0.01, 0.0],
+            [0.0; 3],
+        ] {
+            let [r, g, b] = rgb;
+            let xyz = XYZ::from(&LinearRGB { r, g, b });
+            assert!(JCh::from(&LMS::from(&xyz)).J.is_finite(), "{:?}", rgb);
+        }
+    }
+
+    #[test]
+    fn kernel_shape() {
+        // S-CIELAB's kernels fall to 1/e at one spread from the center
+        let kernel = gaussian(2.0);
+        let center = kernel.len() / 2;
+        assert_eq!(center, 7);
+        assert!((kernel[center + 2] / kernel[center] - (-1.0f32).exp()).abs() < 1e-6);
+        assert!((kernel.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn opponent_inverse() {
+        let xyz = [41.2, 21.3, 1.9];
+        let back = multiply(&inverse(&OPPONENT), multiply(&OPPONENT, xyz));
+        for (a, b) in xyz.iter().zip(back) {
+            assert!((a - b).abs() < 1e-3);
+        }
+    }
+}
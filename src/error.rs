@@ -0,0 +1,96 @@
+//! Errors from the validated conversions: the `TryFrom` impls, and the `try_` constructors like
+//! [`JCh::try_from_xyz`](crate::JCh::try_from_xyz).
+use core::fmt;
+
+use crate::Float;
+
+/// A stage of the CAM02 pipeline, named after the color type it produces or takes in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stage {
+    /// CIEXYZ input.
+    XYZ,
+    /// CAT02 cone responses.
+    LMS,
+    /// The adapted Hunt-Pointer-Estevez cone responses, after the nonlinearity.
+    HPE,
+    /// The CIECAM02 correlates.
+    JCh,
+    /// CAM02-UCS, LCD or SCD coordinates.
+    Jab,
+    /// The viewing conditions the model is computed in.
+    ViewingConditions,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Stage::XYZ => "XYZ",
+            Stage::LMS => "LMS",
+            Stage::HPE => "adapted HPE",
+            Stage::JCh => "JCh",
+            Stage::Jab => "Jab",
+            Stage::ViewingConditions => "viewing conditions",
+        })
+    }
+}
+
+/// Why a validated conversion failed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// A component was NaN or infinite, either in the input or computed from it.
+    NonFinite {
+        stage: Stage,
+        parameter: &'static str,
+    },
+    /// A component was outside the range its stage accepts, like a negative XYZ component,
+    /// a lightness J outside 0-100, or a surround factor F outside 0.8-1.
+    OutOfRange {
+        stage: Stage,
+        parameter: &'static str,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NonFinite { stage, parameter } => {
+                write!(f, "{} {} isn't finite", stage, parameter)
+            }
+            Error::OutOfRange { stage, parameter } => {
+                write!(f, "{} {} is out of range", stage, parameter)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// Checks that every named component is finite.
+pub(crate) fn finite<F: Float>(
+    stage: Stage,
+    components: &[(&'static str, F)],
+) -> Result<(), Error> {
+    match components
+        .iter()
+        .find(|(_, value)| !value.to_f64().is_finite())
+    {
+        Some(&(parameter, _)) => Err(Error::NonFinite { stage, parameter }),
+        None => Ok(()),
+    }
+}
+
+/// Checks that every named component is finite, and within its inclusive range.
+pub(crate) fn within<F: Float>(
+    stage: Stage,
+    components: &[(&'static str, F, (f64, f64))],
+) -> Result<(), Error> {
+    for &(parameter, value, (min, max)) in components {
+        finite(stage, &[(parameter, value)])?;
+        if value < F::from_f64(min) || value > F::from_f64(max) {
+            return Err(Error::OutOfRange { stage, parameter });
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,558 @@
+//! Fast approximations of the transcendental functions used by the CAM02 pipeline.
+//!
+//! These are used in place of the `std` float methods when the `approximate_math` feature is enabled.
+//! They're tuned for the inputs the conversions actually produce (non-negative cone responses, hue angles in radians),
+//! not for general use: subnormal inputs are flushed to zero and trigonometric range reduction is only accurate for |x| < 1e4.
+//!
+//! Maximum errors against `std`, as measured by the tests at the bottom of this module:
+//!
+//! | function         | max error                                         |
+//! |------------------|---------------------------------------------------|
+//! | [`pow_042`]      | 1e-6 relative                                     |
+//! | [`powf`]         | 3e-6 relative, for exponents up to 2.4            |
+//! | [`atan2`]        | 3e-7 radians                                      |
+//! | [`cos`], [`sin`] | 2e-7 absolute                                     |
+//! | [`ln`]           | 3e-7 absolute over [1, 11], 1e-6 over [1e-6, 1e6] |
+//!
+//! Over the whole sRGB cube, [`Jab<UCS>`](crate::Jab) colors computed with these approximations are at most ΔE 0.0004
+//! (mean ΔE 0.00002) away from the ones computed with `std`. With `micromath`, whose square root is refined
+//! from a coarser estimate, the mean goes up to ΔE 0.000023.
+//! That's well under the ΔE 0.008 the `f32` pipeline is already off by compared to an exact `f64` computation.
+
+use core::f32::consts::{FRAC_PI_2, LN_2, LOG2_E, PI, SQRT_2};
+
+// pi/2 split into a high and low part for Cody-Waite range reduction.
+const FRAC_PI_2_HI: f32 = 1.570_796_4;
+const FRAC_PI_2_LO: f32 = -4.371_139e-8;
+
+/// Rounds to the nearest integer, with ties going up. Only valid within the i32 range.
+#[inline(always)]
+fn round(x: f32) -> f32 {
+    let x = x + 0.5;
+    let truncated = x as i32 as f32;
+    if truncated > x {
+        truncated - 1.0
+    } else {
+        truncated
+    }
+}
+
+/// log2 of a positive, finite float.
+#[inline(always)]
+fn log2(x: f32) -> f32 {
+    let bits = x.to_bits();
+    let mut exponent = ((bits >> 23) & 0xff) as i32 - 127;
+    let mut mantissa = f32::from_bits((bits & 0x007f_ffff) | 0x3f80_0000);
+
+    // keep the mantissa in [sqrt(0.5), sqrt(2)) so the series below converges quickly
+    if mantissa > SQRT_2 {
+        mantissa *= 0.5;
+        exponent += 1;
+    }
+
+    // ln(m) = 2 * atanh((m - 1) / (m + 1)), with |t| <= 0.1716
+    let t = (mantissa - 1.0) / (mantissa + 1.0);
+    let t2 = t * t;
+    let series = t * (2.0 + t2 * (2.0 / 3.0 + t2 * (2.0 / 5.0 + t2 * (2.0 / 7.0))));
+
+    exponent as f32 + series * LOG2_E
+}
+
+/// 2^x, saturating to 0 and infinity outside of the representable range.
+#[inline(always)]
+fn exp2(x: f32) -> f32 {
+    if x < -126.0 {
+        return 0.0;
+    }
+    if x >= 128.0 {
+        return f32::INFINITY;
+    }
+
+    let whole = round(x);
+    let fraction = (x - whole) * LN_2; // |fraction| <= ln(2) / 2
+
+    // e^fraction, taylor series up to the 6th power
+    let poly = 1.0
+        + fraction
+            * (1.0
+                + fraction
+                    * (1.0 / 2.0
+                        + fraction
+                            * (1.0 / 6.0
+                                + fraction
+                                    * (1.0 / 24.0
+                                        + fraction * (1.0 / 120.0 + fraction * (1.0 / 720.0))))));
+
+    poly * f32::from_bits(((whole as i32 + 127) as u32) << 23)
+}
+
+/// x^y for non-negative x.
+/// Negative x yields NaN and subnormal x is treated as zero.
+#[inline(always)]
+pub fn powf(x: f32, y: f32) -> f32 {
+    if x.is_nan() || x < 0.0 {
+        return f32::NAN;
+    }
+    if x < f32::MIN_POSITIVE {
+        return 0.0;
+    }
+
+    exp2(y * log2(x))
+}
+
+// 2^(k/50) for k in 0..50: 0.42 is 21/50, so these are the only fractional powers of two x^0.42 takes
+// from the exponent of x.
+const POW2_FIFTIETHS: [f32; 50] = [
+    1.0, 1.0139595, 1.0281138, 1.0424658, 1.057018, 1.0717734, 1.0867349, 1.1019051, 1.1172872,
+    1.1328839, 1.1486983, 1.1647335, 1.1809926, 1.1974787, 1.2141949, 1.2311444, 1.2483306,
+    1.2657566, 1.2834259, 1.3013419, 1.319508, 1.3379276, 1.3566043, 1.3755418, 1.3947437, SQRT_2,
+    1.4339552, 1.4539725, 1.4742693, 1.4948492, 1.5157166, 1.5368751, 1.5583291, 1.5800827,
+    1.6021397, 1.6245048, 1.647182, 1.6701758, 1.6934906, 1.7171309, 1.7411011, 1.765406,
+    1.7900501, 1.8150383, 1.8403753, 1.866066, 1.8921152, 1.9185282, 1.9453099, 1.9724654,
+];
+
+// m^0.42 as a polynomial in t = m - 1, for m in [sqrt(0.5), sqrt(2)): the Chebyshev interpolant of degree 7,
+// within 4e-8 relative of the exact power.
+const POW_042_MANTISSA: [f32; 8] = [
+    1.0,
+    0.4200005,
+    -0.121801674,
+    0.06410619,
+    -0.04126162,
+    0.03045338,
+    -0.025040006,
+    0.014358416,
+];
+
+/// x^0.42, the exponent of the CIECAM02 post-adaptation nonlinearity.
+/// Like [`powf`], negative x yields NaN and subnormal x is treated as zero.
+///
+/// Unlike [`powf`] it takes no logarithm: with x = m * 2^e, the power of the mantissa comes from a polynomial,
+/// and 2^(0.42 e) = 2^(21 e / 50) from a whole power of two and a table of fiftieths.
+#[inline(always)]
+pub fn pow_042(x: f32) -> f32 {
+    if x.is_nan() || x < 0.0 {
+        return f32::NAN;
+    }
+    if x < f32::MIN_POSITIVE {
+        return 0.0;
+    }
+    if x == f32::INFINITY {
+        return x;
+    }
+
+    let bits = x.to_bits();
+    let mut exponent = ((bits >> 23) & 0xff) as i32 - 127;
+    let mut mantissa = f32::from_bits((bits & 0x007f_ffff) | 0x3f80_0000);
+    if mantissa > SQRT_2 {
+        mantissa *= 0.5;
+        exponent += 1;
+    }
+
+    let t = mantissa - 1.0;
+    let power = POW_042_MANTISSA[0]
+        + t * (POW_042_MANTISSA[1]
+            + t * (POW_042_MANTISSA[2]
+                + t * (POW_042_MANTISSA[3]
+                    + t * (POW_042_MANTISSA[4]
+                        + t * (POW_042_MANTISSA[5]
+                            + t * (POW_042_MANTISSA[6] + t * (POW_042_MANTISSA[7])))))));
+
+    // 21 e / 50 is within [-53, 54], so the whole power of two is always a normal float
+    let scaled = 21 * exponent;
+    let whole = f32::from_bits(((scaled.div_euclid(50) + 127) as u32) << 23);
+    power * POW2_FIFTIETHS[scaled.rem_euclid(50) as usize] * whole
+}
+
+/// Natural logarithm of a positive float.
+#[inline(always)]
+pub fn ln(x: f32) -> f32 {
+    if x.is_nan() || x < 0.0 {
+        return f32::NAN;
+    }
+    if x < f32::MIN_POSITIVE {
+        return f32::NEG_INFINITY;
+    }
+
+    log2(x) * LN_2
+}
+
+/// Four-quadrant arctangent of y / x, in radians.
+#[inline(always)]
+pub fn atan2(y: f32, x: f32) -> f32 {
+    let (ax, ay) = (x.abs(), y.abs());
+    let (min, max) = if ax > ay { (ay, ax) } else { (ax, ay) };
+
+    if max == 0.0 {
+        return 0.0;
+    }
+
+    // Abramowitz & Stegun 4.4.49, |error| <= 2e-8 over [-1, 1]
+    let a = min / max;
+    let s = a * a;
+    let mut r = a
+        * (1.0
+            + s * (-0.333_331_45
+                + s * (0.199_935_51
+                    + s * (-0.142_089
+                        + s * (0.106_562_64
+                            + s * (-0.075_289_64
+                                + s * (0.042_909_61
+                                    + s * (-0.016_165_737 + s * 0.002_866_225))))))));
+
+    if ay > ax {
+        r = FRAC_PI_2 - r;
+    }
+    if x < 0.0 {
+        r = PI - r;
+    }
+    if y < 0.0 {
+        r = -r;
+    }
+
+    r
+}
+
+/// Reduces x into [-pi/4, pi/4], returning the reduced value and which quadrant it came from.
+#[inline(always)]
+fn reduce_quadrant(x: f32) -> (f32, i32) {
+    let quadrant = round(x * (2.0 / PI));
+    let r = (x - quadrant * FRAC_PI_2_HI) - quadrant * FRAC_PI_2_LO;
+
+    (r, quadrant as i32 & 3)
+}
+
+// Cephes sinf/cosf polynomials, valid over [-pi/4, pi/4]
+#[inline(always)]
+fn sin_poly(r: f32) -> f32 {
+    let r2 = r * r;
+    r + r * r2 * (-1.666_665_5e-1 + r2 * (8.332_161e-3 + r2 * -1.951_529_6e-4))
+}
+
+#[inline(always)]
+fn cos_poly(r: f32) -> f32 {
+    let r2 = r * r;
+    1.0 - 0.5 * r2 + r2 * r2 * (4.166_664_6e-2 + r2 * (-1.388_731_6e-3 + r2 * 2.443_315_7e-5))
+}
+
+/// Cosine of x, in radians.
+#[inline(always)]
+pub fn cos(x: f32) -> f32 {
+    let (r, quadrant) = reduce_quadrant(x);
+    match quadrant {
+        0 => cos_poly(r),
+        1 => -sin_poly(r),
+        2 => -cos_poly(r),
+        _ => sin_poly(r),
+    }
+}
+
+/// Sine of x, in radians.
+#[inline(always)]
+pub fn sin(x: f32) -> f32 {
+    let (r, quadrant) = reduce_quadrant(x);
+    match quadrant {
+        0 => sin_poly(r),
+        1 => cos_poly(r),
+        2 => -sin_poly(r),
+        _ => -cos_poly(r),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::consts::ViewingConditions;
+    use crate::{Jab, LMS, UCS, XYZ};
+
+    fn max_relative_error(
+        f: impl Fn(f32) -> f32,
+        reference: impl Fn(f32) -> f32,
+        inputs: impl Iterator<Item = f32>,
+    ) -> f32 {
+        inputs
+            .map(|x| {
+                let expected = reference(x);
+                ((f(x) - expected) / expected).abs()
+            })
+            .fold(0.0, f32::max)
+    }
+
+    fn max_absolute_error(
+        f: impl Fn(f32) -> f32,
+        reference: impl Fn(f32) -> f32,
+        inputs: impl Iterator<Item = f32>,
+    ) -> f32 {
+        inputs
+            .map(|x| (f(x) - reference(x)).abs())
+            .fold(0.0, f32::max)
+    }
+
+    // geometric sweep from 1e-6 up to 1e6, covering every cone response the pipeline produces
+    fn positive_sweep() -> impl Iterator<Item = f32> {
+        (0..=120_000).map(|i| 10f32.powf(-6.0 + i as f32 / 10_000.0))
+    }
+
+    // linear sweep over [-range, range]
+    fn signed_sweep(range: f32) -> impl Iterator<Item = f32> {
+        (-100_000..=100_000).map(move |i| range * i as f32 / 100_000.0)
+    }
+
+    #[test]
+    fn pow_042_error() {
+        let err = max_relative_error(pow_042, |x| x.powf(0.42), positive_sweep());
+        assert!(err < 1e-6, "pow_042 relative error {}", err);
+
+        // every exponent, including the extremes of the normal range
+        let err = max_relative_error(
+            pow_042,
+            |x| (x as f64).powf(0.42) as f32,
+            (-126..=127).map(|e| 1.37 * 2f32.powi(e).min(f32::MAX / 1.37)),
+        );
+        assert!(
+            err < 1e-6,
+            "pow_042 relative error across exponents {}",
+            err
+        );
+    }
+
+    #[test]
+    fn pow_042_edge_cases() {
+        assert_eq!(pow_042(0.0), 0.0);
+        assert_eq!(pow_042(1.0), 1.0);
+        assert_eq!(pow_042(f32::INFINITY), f32::INFINITY);
+        assert!(pow_042(-1.0).is_nan());
+        assert!(pow_042(f32::NAN).is_nan());
+    }
+
+    #[test]
+    fn powf_error() {
+        for &exponent in &[
+            0.25f32,
+            0.42,
+            0.73,
+            0.9,
+            1.0 / 0.42,
+            1.0 / 0.9,
+            0.69 * 1.927,
+        ] {
+            let err = max_relative_error(
+                |x| powf(x, exponent),
+                |x| x.powf(exponent),
+                positive_sweep(),
+            );
+            assert!(err < 3e-6, "powf(x, {}) relative error {}", exponent, err);
+        }
+    }
+
+    #[test]
+    fn powf_edge_cases() {
+        assert_eq!(powf(0.0, 0.42), 0.0);
+        assert_eq!(powf(1.0, 0.42), 1.0);
+        assert!(powf(-1.0, 0.42).is_nan());
+        assert!(powf(f32::NAN, 0.42).is_nan());
+    }
+
+    #[test]
+    fn ln_error() {
+        let err = max_absolute_error(ln, f32::ln, positive_sweep());
+        assert!(err < 1e-6, "ln absolute error {}", err);
+
+        // the Jab transformation takes ln(1 + c2 * M), so the region around 1 matters most
+        let err = max_absolute_error(
+            ln,
+            f32::ln,
+            (0..=100_000).map(|i| 1.0 + i as f32 / 10_000.0),
+        );
+        assert!(err < 3e-7, "ln absolute error near 1 {}", err);
+    }
+
+    #[test]
+    fn atan2_error() {
+        let err = (0..3600)
+            .flat_map(|angle| {
+                let angle = (angle as f32 / 10.0).to_radians();
+                [0.001f32, 1.0, 100.0]
+                    .iter()
+                    .map(move |radius| (radius * angle.sin(), radius * angle.cos()))
+                    .collect::<Vec<_>>()
+            })
+            .map(|(y, x)| (atan2(y, x) - y.atan2(x)).abs())
+            .fold(0.0, f32::max);
+        assert!(err < 3e-7, "atan2 absolute error {}", err);
+
+        assert_eq!(atan2(0.0, 0.0), 0.0);
+        assert_eq!(atan2(0.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn cos_sin_error() {
+        // hue angles, plus the h + 2 offset used in the eccentricity factor
+        let err = max_absolute_error(cos, f32::cos, signed_sweep(2.0 * PI + 2.0));
+        assert!(err < 2e-7, "cos absolute error {}", err);
+
+        let err = max_absolute_error(sin, f32::sin, signed_sweep(2.0 * PI + 2.0));
+        assert!(err < 2e-7, "sin absolute error {}", err);
+    }
+
+    /// An `f32` whose math goes through `std`, to run the `f32` pipeline without the approximations
+    /// in the same build as with them.
+    #[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+    struct StdF32(f32);
+
+    macro_rules! std_f32_ops {
+        ($($op:ident $method:ident $assign:ident $assign_method:ident),*) => {$(
+            impl core::ops::$op for StdF32 {
+                type Output = StdF32;
+                fn $method(self, other: StdF32) -> StdF32 {
+                    StdF32(self.0.$method(other.0))
+                }
+            }
+            impl core::ops::$assign for StdF32 {
+                fn $assign_method(&mut self, other: StdF32) {
+                    self.0.$assign_method(other.0)
+                }
+            }
+        )*};
+    }
+
+    std_f32_ops!(
+        Add add AddAssign add_assign,
+        Sub sub SubAssign sub_assign,
+        Mul mul MulAssign mul_assign,
+        Div div DivAssign div_assign
+    );
+
+    impl core::ops::Neg for StdF32 {
+        type Output = StdF32;
+        fn neg(self) -> StdF32 {
+            StdF32(-self.0)
+        }
+    }
+
+    const fn std_f32<const N: usize>(values: [f32; N]) -> [StdF32; N] {
+        let mut out = [StdF32(0.0); N];
+        let mut i = 0;
+        while i < N {
+            out[i] = StdF32(values[i]);
+            i += 1;
+        }
+        out
+    }
+
+    impl crate::Float for StdF32 {
+        const PI: StdF32 = StdF32(PI);
+        const VIEWING_CONDITIONS: ViewingConditions<StdF32> = {
+            let vc = ViewingConditions::<f32>::DEFAULT;
+            let [wx, wy, wz, wl, wm, ws] = std_f32([
+                vc.white.x,
+                vc.white.y,
+                vc.white.z,
+                vc.white_lms.l,
+                vc.white_lms.m,
+                vc.white_lms.s,
+            ]);
+            let [la, yb, f, c, nc, n, k, z, fl, nbb, ncb, d, aw] = std_f32([
+                vc.la,
+                vc.yb,
+                vc.f,
+                vc.c,
+                vc.nc,
+                vc.n,
+                vc.k,
+                vc.z,
+                vc.fl,
+                vc.nbb,
+                vc.ncb,
+                vc.d,
+                vc.achromatic_response_to_white,
+            ]);
+            ViewingConditions {
+                white: XYZ {
+                    x: wx,
+                    y: wy,
+                    z: wz,
+                },
+                white_lms: LMS {
+                    l: wl,
+                    m: wm,
+                    s: ws,
+                },
+                la,
+                yb,
+                f,
+                c,
+                nc,
+                n,
+                k,
+                z,
+                fl,
+                nbb,
+                ncb,
+                d,
+                achromatic_response_to_white: aw,
+            }
+        };
+
+        fn from_f64(v: f64) -> StdF32 {
+            StdF32(v as f32)
+        }
+        fn to_f64(self) -> f64 {
+            self.0 as f64
+        }
+        fn abs(self) -> StdF32 {
+            StdF32(self.0.abs())
+        }
+        fn sqrt(self) -> StdF32 {
+            StdF32(self.0.sqrt())
+        }
+        fn powi(self, n: i32) -> StdF32 {
+            StdF32(self.0.powi(n))
+        }
+        fn powf(self, n: StdF32) -> StdF32 {
+            StdF32(self.0.powf(n.0))
+        }
+        fn ln(self) -> StdF32 {
+            StdF32(self.0.ln())
+        }
+        fn cos(self) -> StdF32 {
+            StdF32(self.0.cos())
+        }
+        fn sin(self) -> StdF32 {
+            StdF32(self.0.sin())
+        }
+        fn atan2(self, other: StdF32) -> StdF32 {
+            StdF32(self.0.atan2(other.0))
+        }
+        fn linearize(c: u8) -> StdF32 {
+            StdF32(crate::consts::sRGB_LOOKUP[c as usize])
+        }
+    }
+
+    // the numbers in the module docs; the whole cube takes too long without optimizations
+    #[test]
+    #[cfg_attr(debug_assertions, ignore = "run with --release")]
+    fn srgb_cube_delta_e() {
+        let (mut max, mut sum) = (0.0f64, 0.0f64);
+        for rgb in 0..=0xff_ff_ffu32 {
+            let [_, r, g, b] = rgb.to_be_bytes();
+            let approximate = Jab::<UCS>::from([r, g, b]);
+            let exact = Jab::<UCS, StdF32>::from_srgb([r, g, b]);
+            let delta_e = ((approximate.J - exact.J.0).powi(2)
+                + (approximate.a - exact.a.0).powi(2)
+                + (approximate.b - exact.b.0).powi(2))
+            .sqrt() as f64;
+            max = max.max(delta_e);
+            sum += delta_e;
+        }
+
+        let mean = sum / (1u32 << 24) as f64;
+        assert!(max <= 0.0004, "max ΔE {}", max);
+        let mean_bound = if cfg!(feature = "micromath") {
+            0.000023
+        } else {
+            0.00002
+        };
+        assert!(mean <= mean_bound, "mean ΔE {}", mean);
+    }
+}
@@ -0,0 +1,369 @@
+//! Floating point abstraction, letting every color type work in either `f32` or `f64`.
+use core::fmt::Debug;
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use crate::consts::ViewingConditions;
+use crate::{utils, LinearRGB, HPE, LMS, XYZ};
+
+/// A floating point type the CAM02 conversions can be computed in.
+///
+/// `f32` is the default everywhere and keeps the lookup table, SSE and `approximate_math` fast paths.
+/// `f64` computes every step exactly, for round-trips and scientific use.
+pub trait Float:
+    Copy
+    + Default
+    + Debug
+    + PartialEq
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+{
+    const PI: Self;
+
+    /// The CIECAM02 viewing conditions, precomputed in this precision.
+    const VIEWING_CONDITIONS: ViewingConditions<Self>;
+
+    fn from_f64(v: f64) -> Self;
+    fn to_f64(self) -> f64;
+
+    fn abs(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn powf(self, n: Self) -> Self;
+    fn ln(self) -> Self;
+    fn cos(self) -> Self;
+    fn sin(self) -> Self;
+    fn atan2(self, other: Self) -> Self;
+
+    /// e^x.
+    #[inline(always)]
+    fn exp(self) -> Self {
+        Self::from_f64(core::f64::consts::E).powf(self)
+    }
+
+    /// x^0.42, the exponent of the post-adaptation nonlinearity.
+    #[inline(always)]
+    fn pow_042(self) -> Self {
+        self.powf(Self::from_f64(0.42))
+    }
+
+    /// Linearizes an sRGB channel.
+    #[inline(always)]
+    fn linearize(c: u8) -> Self {
+        utils::linearize_channel_generic(c)
+    }
+
+    #[inline(always)]
+    fn linear_rgb_to_xyz(rgb: &LinearRGB<Self>) -> XYZ<Self> {
+        utils::linear_rgb_to_xyz_scalar(rgb)
+    }
+
+    #[inline(always)]
+    fn xyz_to_lms(xyz: &XYZ<Self>) -> LMS<Self> {
+        utils::xyz_to_lms_scalar(xyz)
+    }
+
+    #[inline(always)]
+    fn lms_to_hpe(lms: &LMS<Self>) -> HPE<Self> {
+        utils::lms_to_hpe_scalar(lms)
+    }
+}
+
+#[cfg(feature = "approximate_math")]
+use crate::fastmath;
+
+#[cfg(not(any(feature = "std", feature = "libm", feature = "micromath")))]
+compile_error!(
+    "kasi-kule needs one of the `std`, `libm` or `micromath` features for its float math"
+);
+
+// the float math backend: std if available, then libm, then micromath (f32 only).
+// the fast approximations replace most of these under approximate_math.
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "approximate_math", allow(dead_code, unused_imports))]
+mod math {
+    pub fn sqrtf(x: f32) -> f32 {
+        x.sqrt()
+    }
+    pub fn powf(x: f32, n: f32) -> f32 {
+        x.powf(n)
+    }
+    pub fn powif(x: f32, n: i32) -> f32 {
+        x.powi(n)
+    }
+    pub fn logf(x: f32) -> f32 {
+        x.ln()
+    }
+    pub fn cosf(x: f32) -> f32 {
+        x.cos()
+    }
+    pub fn sinf(x: f32) -> f32 {
+        x.sin()
+    }
+    pub fn atan2f(y: f32, x: f32) -> f32 {
+        y.atan2(x)
+    }
+
+    pub fn sqrt(x: f64) -> f64 {
+        x.sqrt()
+    }
+    pub fn pow(x: f64, n: f64) -> f64 {
+        x.powf(n)
+    }
+    pub fn powi(x: f64, n: i32) -> f64 {
+        x.powi(n)
+    }
+    pub fn log(x: f64) -> f64 {
+        x.ln()
+    }
+    pub fn cos(x: f64) -> f64 {
+        x.cos()
+    }
+    pub fn sin(x: f64) -> f64 {
+        x.sin()
+    }
+    pub fn atan2(y: f64, x: f64) -> f64 {
+        y.atan2(x)
+    }
+}
+
+#[cfg(all(not(feature = "std"), feature = "libm"))]
+#[cfg_attr(feature = "approximate_math", allow(dead_code, unused_imports))]
+mod math {
+    pub use libm::{atan2, atan2f, cos, cosf, log, logf, pow, powf, sin, sinf, sqrt, sqrtf};
+
+    pub fn powif(x: f32, n: i32) -> f32 {
+        powf(x, n as f32)
+    }
+    pub fn powi(x: f64, n: i32) -> f64 {
+        pow(x, n as f64)
+    }
+}
+
+#[cfg(all(not(feature = "std"), not(feature = "libm"), feature = "micromath"))]
+mod math {
+    // the micromath feature turns on approximate_math, so only sqrt and powi come from here.
+    use micromath::F32Ext;
+
+    pub fn sqrtf(x: f32) -> f32 {
+        // micromath's estimate is only good to a few percent, so refine it with two newton steps
+        let estimate = F32Ext::sqrt(x);
+        if estimate <= 0.0 || estimate.is_nan() {
+            return estimate;
+        }
+        let estimate = 0.5 * (estimate + x / estimate);
+        0.5 * (estimate + x / estimate)
+    }
+    pub fn powif(x: f32, n: i32) -> f32 {
+        let result = (0..n.unsigned_abs()).fold(1.0, |acc, _| acc * x);
+        if n < 0 {
+            1.0 / result
+        } else {
+            result
+        }
+    }
+}
+
+impl Float for f32 {
+    const PI: f32 = core::f32::consts::PI;
+    const VIEWING_CONDITIONS: ViewingConditions<f32> = ViewingConditions::<f32>::DEFAULT;
+
+    #[inline(always)]
+    fn from_f64(v: f64) -> f32 {
+        v as f32
+    }
+
+    #[inline(always)]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    #[inline(always)]
+    fn abs(self) -> f32 {
+        f32::abs(self)
+    }
+
+    #[inline(always)]
+    fn sqrt(self) -> f32 {
+        math::sqrtf(self)
+    }
+
+    #[inline(always)]
+    fn powi(self, n: i32) -> f32 {
+        math::powif(self, n)
+    }
+
+    #[inline(always)]
+    fn powf(self, n: f32) -> f32 {
+        #[cfg(feature = "approximate_math")]
+        return fastmath::powf(self, n);
+        #[cfg(not(feature = "approximate_math"))]
+        return math::powf(self, n);
+    }
+
+    #[inline(always)]
+    fn ln(self) -> f32 {
+        #[cfg(feature = "approximate_math")]
+        return fastmath::ln(self);
+        #[cfg(not(feature = "approximate_math"))]
+        return math::logf(self);
+    }
+
+    #[inline(always)]
+    fn cos(self) -> f32 {
+        #[cfg(feature = "approximate_math")]
+        return fastmath::cos(self);
+        #[cfg(not(feature = "approximate_math"))]
+        return math::cosf(self);
+    }
+
+    #[inline(always)]
+    fn sin(self) -> f32 {
+        #[cfg(feature = "approximate_math")]
+        return fastmath::sin(self);
+        #[cfg(not(feature = "approximate_math"))]
+        return math::sinf(self);
+    }
+
+    #[inline(always)]
+    fn atan2(self, other: f32) -> f32 {
+        #[cfg(feature = "approximate_math")]
+        return fastmath::atan2(self, other);
+        #[cfg(not(feature = "approximate_math"))]
+        return math::atan2f(self, other);
+    }
+
+    #[inline(always)]
+    fn pow_042(self) -> f32 {
+        #[cfg(feature = "approximate_math")]
+        return fastmath::pow_042(self);
+        #[cfg(not(feature = "approximate_math"))]
+        return math::powf(self, 0.42);
+    }
+
+    #[inline(always)]
+    fn linearize(c: u8) -> f32 {
+        // safety: bounds checked by type; array is u8::MAX-sized and indexes are u8s
+        unsafe { *crate::consts::sRGB_LOOKUP.get_unchecked(c as usize) }
+    }
+
+    #[inline(always)]
+    fn linear_rgb_to_xyz(rgb: &LinearRGB) -> XYZ {
+        #[cfg(all(feature = "sse", any(target_arch = "x86", target_arch = "x86_64")))]
+        {
+            unsafe {
+                if crate::sse::sse_available() {
+                    let res = crate::sse::sse_xyz(rgb);
+                    return XYZ {
+                        x: res[0],
+                        y: res[1],
+                        z: res[2],
+                    };
+                }
+            }
+        }
+
+        utils::linear_rgb_to_xyz_scalar(rgb)
+    }
+
+    #[inline(always)]
+    fn xyz_to_lms(xyz: &XYZ) -> LMS {
+        #[cfg(all(feature = "sse", any(target_arch = "x86", target_arch = "x86_64")))]
+        {
+            unsafe {
+                if crate::sse::sse_available() {
+                    let res = crate::sse::sse_lms(xyz);
+                    return LMS {
+                        l: res[0],
+                        m: res[1],
+                        s: res[2],
+                    };
+                }
+            }
+        }
+
+        utils::xyz_to_lms_scalar(xyz)
+    }
+
+    #[inline(always)]
+    fn lms_to_hpe(lms: &LMS) -> HPE {
+        #[cfg(all(feature = "sse", any(target_arch = "x86", target_arch = "x86_64")))]
+        {
+            unsafe {
+                if crate::sse::sse_available() {
+                    let res = crate::sse::sse_hpe(lms);
+                    return HPE {
+                        lh: res[0],
+                        mh: res[1],
+                        sh: res[2],
+                    };
+                }
+            }
+        }
+
+        utils::lms_to_hpe_scalar(lms)
+    }
+}
+
+#[cfg(any(feature = "std", feature = "libm"))]
+impl Float for f64 {
+    const PI: f64 = core::f64::consts::PI;
+    const VIEWING_CONDITIONS: ViewingConditions<f64> = ViewingConditions::<f64>::DEFAULT;
+
+    #[inline(always)]
+    fn from_f64(v: f64) -> f64 {
+        v
+    }
+
+    #[inline(always)]
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    #[inline(always)]
+    fn abs(self) -> f64 {
+        f64::abs(self)
+    }
+
+    #[inline(always)]
+    fn sqrt(self) -> f64 {
+        math::sqrt(self)
+    }
+
+    #[inline(always)]
+    fn powi(self, n: i32) -> f64 {
+        math::powi(self, n)
+    }
+
+    #[inline(always)]
+    fn powf(self, n: f64) -> f64 {
+        math::pow(self, n)
+    }
+
+    #[inline(always)]
+    fn ln(self) -> f64 {
+        math::log(self)
+    }
+
+    #[inline(always)]
+    fn cos(self) -> f64 {
+        math::cos(self)
+    }
+
+    #[inline(always)]
+    fn sin(self) -> f64 {
+        math::sin(self)
+    }
+
+    #[inline(always)]
+    fn atan2(self, other: f64) -> f64 {
+        math::atan2(self, other)
+    }
+}
@@ -0,0 +1,278 @@
+//! Gamut mapping from [`JCh`] back into sRGB.
+//!
+//! Adjusting the lightness or chroma of a color easily pushes it outside of sRGB.
+//! A [`GamutMapping`] brings it back inside while holding its hue, and reports how far the color had to move:
+//! ```
+//! use kasi_kule::gamut::GamutMapping;
+//! use kasi_kule::JCh;
+//!
+//! let vivid_blue = JCh::new(40.0, 120.0, 260.0);
+//! let mapped = GamutMapping::ChromaReduction.map(&vivid_blue);
+//! assert!(!mapped.in_gamut && mapped.delta_e > 1.0);
+//! ```
+//!
+//! Only J, C and h of the input are used, as in the inverse conversion into [`LMS`].
+use crate::{sRGB, Float, JCh, Jab, LinearRGB, LMS, UCS, XYZ};
+
+/// How to bring a color that's outside of sRGB back into it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GamutMapping {
+    /// Clamps each linear RGB channel into range.
+    /// Fast, but shifts hue and lightness.
+    Clip,
+    /// Holds J and h, and searches for the largest chroma inside the gamut.
+    /// J is clamped to 0-100 first.
+    ChromaReduction,
+    /// Holds h, and moves J and C together towards mid grey (J = 50).
+    /// This compresses lightness as well as chroma, which keeps more colorfulness for very light and very dark colors.
+    LightnessChromaReduction,
+    /// The CSS Color 4 algorithm: chroma reduction which stops as soon as clipping moves the color by less than a
+    /// just noticeable difference, taken as ΔE 2 in CAM02-UCS.
+    Css,
+}
+
+/// A gamut mapped color.
+#[derive(Debug, Copy, Clone)]
+pub struct GamutMapped<F: Float = f32> {
+    pub srgb: sRGB,
+    /// ΔE in CAM02-UCS between the requested color and `srgb`, including rounding to code values.
+    pub delta_e: F,
+    /// Whether the requested color was already inside the gamut.
+    pub in_gamut: bool,
+}
+
+// bisection steps for the chroma searches, which gets within 1e-6 of the boundary
+const BISECTION_STEPS: usize = 20;
+
+// the just noticeable difference and search tolerance of the CSS algorithm, scaled from OKLab into CAM02-UCS
+const CSS_JND: f64 = 2.0;
+const CSS_EPSILON: f64 = 0.01;
+
+/// Whether a color lies inside sRGB, to within half a code value.
+pub fn in_gamut<F: Float>(color: &JCh<F>) -> bool {
+    to_linear(color).to_srgb().is_some()
+}
+
+fn to_linear<F: Float>(color: &JCh<F>) -> LinearRGB<F> {
+    LinearRGB::from(&XYZ::from(&LMS::from(color)))
+}
+
+fn clip<F: Float>(rgb: &LinearRGB<F>) -> LinearRGB<F> {
+    let (zero, one) = (F::default(), F::from_f64(1.0));
+    // written so that NaN clips to 0
+    let clip = |c: F| {
+        if c > one {
+            one
+        } else if c > zero {
+            c
+        } else {
+            zero
+        }
+    };
+
+    LinearRGB {
+        r: clip(rgb.r),
+        g: clip(rgb.g),
+        b: clip(rgb.b),
+    }
+}
+
+fn delta_e<F: Float>(rgb: &LinearRGB<F>, color: &JCh<F>) -> F {
+    let jab = Jab::<UCS, F>::from(&JCh::from(&LMS::from(&XYZ::from(rgb))));
+    jab.squared_difference(&Jab::from(color)).sqrt()
+}
+
+/// Bisects along `path`, from `path(0)` (assumed inside the gamut) to `path(1)` (outside),
+/// returning the position of the last color found inside.
+pub(crate) fn bisect<F: Float>(path: impl Fn(F) -> JCh<F>) -> F {
+    let (mut inside, mut outside) = (F::default(), F::from_f64(1.0));
+    for _ in 0..BISECTION_STEPS {
+        let t = (inside + outside) / F::from_f64(2.0);
+        if in_gamut(&path(t)) {
+            inside = t;
+        } else {
+            outside = t;
+        }
+    }
+
+    inside
+}
+
+/// Bisects along `path`, and clips what was found,
+/// since the grey at the end of the path can itself be slightly outside.
+fn bisect_clipped<F: Float>(path: impl Fn(F) -> JCh<F>) -> LinearRGB<F> {
+    clip(&to_linear(&path(bisect(&path))))
+}
+
+fn css<F: Float>(color: &JCh<F>) -> LinearRGB<F> {
+    let c = F::from_f64;
+    let (jnd, epsilon) = (c(CSS_JND), c(CSS_EPSILON));
+
+    if color.J >= c(100.0) {
+        return LinearRGB {
+            r: c(1.0),
+            g: c(1.0),
+            b: c(1.0),
+        };
+    }
+    if color.J <= F::default() {
+        return LinearRGB::default();
+    }
+
+    let mut clipped = clip(&to_linear(color));
+    if delta_e(&clipped, color) < jnd {
+        return clipped;
+    }
+
+    let (mut min, mut max) = (F::default(), color.C);
+    let mut min_in_gamut = true;
+    while max - min > epsilon {
+        let chroma = (min + max) / c(2.0);
+        let current = JCh::new(color.J, chroma, color.h);
+
+        if min_in_gamut && in_gamut(&current) {
+            min = chroma;
+            continue;
+        }
+
+        clipped = clip(&to_linear(&current));
+        let error = delta_e(&clipped, &current);
+        if error < jnd {
+            if jnd - error < epsilon {
+                break;
+            }
+            min_in_gamut = false;
+            min = chroma;
+        } else {
+            max = chroma;
+        }
+    }
+
+    clipped
+}
+
+impl GamutMapping {
+    /// Maps a color into sRGB. Colors already inside are only rounded to the nearest code values.
+    pub fn map<F: Float>(self, color: &JCh<F>) -> GamutMapped<F> {
+        let c = F::from_f64;
+        let target = JCh::new(color.J, color.C, color.h);
+
+        let rgb = to_linear(&target);
+        let in_gamut = rgb.to_srgb().is_some();
+
+        let rgb = match self {
+            _ if in_gamut => rgb,
+            GamutMapping::Clip => clip(&rgb),
+            GamutMapping::ChromaReduction => {
+                let J = if target.J > c(100.0) {
+                    c(100.0)
+                } else if target.J > F::default() {
+                    target.J
+                } else {
+                    F::default()
+                };
+                bisect_clipped(|t| JCh::new(J, t * target.C, target.h))
+            }
+            GamutMapping::LightnessChromaReduction => bisect_clipped(|t| {
+                JCh::new(c(50.0) + t * (target.J - c(50.0)), t * target.C, target.h)
+            }),
+            GamutMapping::Css => css(&target),
+        };
+
+        let srgb = rgb.to_srgb().unwrap_or_default();
+        let delta_e = Jab::<UCS, F>::from_srgb(srgb)
+            .squared_difference(&Jab::from(&target))
+            .sqrt();
+
+        GamutMapped {
+            srgb,
+            delta_e,
+            in_gamut,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const METHODS: [GamutMapping; 4] = [
+        GamutMapping::Clip,
+        GamutMapping::ChromaReduction,
+        GamutMapping::LightnessChromaReduction,
+        GamutMapping::Css,
+    ];
+
+    fn hue_difference(a: f32, b: f32) -> f32 {
+        let difference = (a - b).abs() % 360.0;
+        difference.min(360.0 - difference)
+    }
+
+    #[test]
+    fn in_gamut_colors_are_kept() {
+        for rgb in [[0, 0, 0], [255, 255, 255], [255, 0, 0], [12, 200, 99]] {
+            let color = JCh::from(rgb);
+            assert!(in_gamut(&color));
+
+            for method in METHODS {
+                let mapped = method.map(&color);
+                assert!(mapped.in_gamut);
+                assert_eq!([mapped.srgb.r, mapped.srgb.g, mapped.srgb.b], rgb);
+                assert!(mapped.delta_e < 0.01, "{:?} {}", method, mapped.delta_e);
+            }
+        }
+    }
+
+    #[test]
+    fn chroma_reduction_holds_lightness_and_hue() {
+        for h in (0..360).step_by(30) {
+            for J in [20.0, 50.0, 80.0] {
+                let color = JCh::new(J, 150.0, h as f32);
+                assert!(!in_gamut(&color));
+
+                let mapped = GamutMapping::ChromaReduction.map(&color);
+                let result = JCh::from(mapped.srgb);
+                assert!(!mapped.in_gamut);
+                assert!((result.J - J).abs() < 1.0, "J {} -> {}", J, result.J);
+                assert!(hue_difference(result.h, h as f32) < 2.0);
+                assert!(result.C < 150.0);
+
+                // every method has to move the color, and the CSS method only trades up to a JND for keeping chroma
+                for method in METHODS {
+                    let other = method.map(&color);
+                    assert!(other.delta_e > 0.0);
+                    if method == GamutMapping::Css {
+                        assert!(other.delta_e < mapped.delta_e + 2.0);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn css_stays_close() {
+        let color = JCh::new(60.0, 90.0, 140.0);
+        let css = GamutMapping::Css.map(&color);
+        let result = JCh::from(css.srgb);
+        assert!((result.J - 60.0).abs() < 2.0);
+        assert!(hue_difference(result.h, 140.0) < 3.0);
+
+        let white = GamutMapping::Css.map(&JCh::new(110.0, 30.0, 90.0));
+        assert_eq!([white.srgb.r, white.srgb.g, white.srgb.b], [255, 255, 255]);
+        let black = GamutMapping::Css.map(&JCh::new(-5.0, 30.0, 90.0));
+        assert_eq!([black.srgb.r, black.srgb.g, black.srgb.b], [0, 0, 0]);
+    }
+
+    #[test]
+    fn lightness_compression() {
+        let color = JCh::new(98.0, 80.0, 250.0);
+        let mapped = GamutMapping::LightnessChromaReduction.map(&color);
+        let result = JCh::from(mapped.srgb);
+        assert!(result.J < 98.0 && result.J > 50.0);
+        assert!(hue_difference(result.h, 250.0) < 2.0);
+
+        // holding lightness near white leaves almost no chroma
+        let held = JCh::from(GamutMapping::ChromaReduction.map(&color).srgb);
+        assert!(result.C > held.C);
+    }
+}
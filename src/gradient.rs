@@ -0,0 +1,489 @@
+//! Perceptual interpolation between colors, and gradients through any number of stops.
+//!
+//! Interpolating in sRGB gives muddy, dark midpoints. A [`Gradient`] interpolates in [`Jab`] instead,
+//! either along straight lines or around the hue circle, and gamut maps the result back into sRGB:
+//! ```
+//! use kasi_kule::gradient::{Curve, Gradient, HueInterpolation, Interpolation};
+//! use kasi_kule::{Jab, UCS};
+//!
+//! let stops = [Jab::<UCS>::from([0, 0, 80]), Jab::<UCS>::from([255, 220, 0])];
+//! let gradient = Gradient::new(&stops, Interpolation::JCh(HueInterpolation::Shorter), Curve::Linear);
+//!
+//! // nine colors, evenly spaced by ΔE
+//! let ramp = gradient.steps(9);
+//! assert_eq!(ramp.len(), 9);
+//! ```
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::gamut::GamutMapping;
+use crate::utils::wrap_hue;
+use crate::{sRGB, Float, JCh, Jab, JabSpace};
+
+/// Which way around the hue circle polar interpolation goes, as in CSS Color 4.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HueInterpolation {
+    /// The shorter arc between two hues.
+    Shorter,
+    /// The longer arc between two hues.
+    Longer,
+    /// Always towards increasing hue angles.
+    Increasing,
+    /// Always towards decreasing hue angles.
+    Decreasing,
+}
+
+/// The coordinates colors are interpolated in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Straight lines through J, a and b of the Jab space.
+    Jab,
+    /// The polar form of the Jab space: J, colorfulness (the length of a, b) and hue.
+    JabPolar(HueInterpolation),
+    /// CIECAM02 lightness, chroma and hue.
+    JCh(HueInterpolation),
+}
+
+/// The curve through the stops.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Curve {
+    /// Straight segments between neighbouring stops.
+    Linear,
+    /// A uniform cubic B-spline, which smooths out the corners at the stops.
+    /// It passes through the first and last stops, but only near the ones in between.
+    BSpline,
+    /// A monotone cubic spline through every stop, which never overshoots:
+    /// each coordinate stays between the values of the stops on either side, so monotone lightness stays monotone.
+    MonotoneCubic,
+}
+
+// stops with less chroma than this have no meaningful hue, and take the hue of their neighbours
+const ACHROMATIC: f64 = 1e-3;
+
+// samples per segment when measuring the length of the gradient
+const LENGTH_SAMPLES: usize = 64;
+
+/// A gradient through evenly spaced stops, from t = 0 at the first stop to t = 1 at the last.
+#[derive(Debug, Clone)]
+pub struct Gradient<S: JabSpace, F: Float = f32> {
+    // the stops in the interpolation's coordinates, with hues unwrapped so they can be interpolated directly
+    points: Vec<[F; 3]>,
+    interpolation: Interpolation,
+    curve: Curve,
+    /// How colors outside of sRGB are brought back in by [`Gradient::srgb`] and [`Gradient::steps`].
+    pub gamut_mapping: GamutMapping,
+    space: PhantomData<S>,
+}
+
+fn hue_angle<F: Float>(a: F, b: F) -> F {
+    let mut h = (F::from_f64(180.0) / F::PI) * b.atan2(a);
+    if h < F::default() {
+        h += F::from_f64(360.0);
+    }
+    h
+}
+
+/// Reduces a hue difference into the range the hue interpolation allows.
+fn hue_difference<F: Float>(difference: F, hue: HueInterpolation) -> F {
+    let c = F::from_f64;
+    let mut d = c(difference.to_f64() % 360.0);
+    match hue {
+        HueInterpolation::Shorter | HueInterpolation::Longer => {
+            if d > c(180.0) {
+                d -= c(360.0);
+            } else if d <= c(-180.0) {
+                d += c(360.0);
+            }
+
+            if hue == HueInterpolation::Longer {
+                if d > F::default() {
+                    d -= c(360.0);
+                } else {
+                    d += c(360.0);
+                }
+            }
+        }
+        HueInterpolation::Increasing if d < F::default() => d += c(360.0),
+        HueInterpolation::Decreasing if d > F::default() => d -= c(360.0),
+        _ => {}
+    }
+    d
+}
+
+/// Unwraps the hues of polar points so that neighbours differ by the hue interpolation's arc.
+fn unwrap_hues<F: Float>(points: &mut [[F; 3]], hue: HueInterpolation) {
+    let achromatic = |point: &[F; 3]| point[1] < F::from_f64(ACHROMATIC);
+    let flags: Vec<bool> = points.iter().map(achromatic).collect();
+
+    let mut previous = match points.iter().find(|point| !achromatic(point)) {
+        Some(point) => point[2],
+        None => return,
+    };
+    for point in points.iter_mut() {
+        if achromatic(point) {
+            point[2] = previous;
+        } else {
+            previous = point[2];
+        }
+    }
+
+    for i in 1..points.len() {
+        let difference = if flags[i] || flags[i - 1] {
+            F::default()
+        } else {
+            hue_difference(points[i][2] - points[i - 1][2], hue)
+        };
+        points[i][2] = points[i - 1][2] + difference;
+    }
+}
+
+impl<S: JabSpace, F: Float> Gradient<S, F> {
+    /// A gradient through `stops`, mapping colors outside of sRGB with [`GamutMapping::Css`].
+    ///
+    /// # Panics
+    /// If `stops` is empty.
+    pub fn new(stops: &[Jab<S, F>], interpolation: Interpolation, curve: Curve) -> Gradient<S, F> {
+        assert!(!stops.is_empty(), "a gradient needs at least one stop");
+
+        let mut points: Vec<[F; 3]> = stops
+            .iter()
+            .map(|jab| match interpolation {
+                Interpolation::Jab => [jab.J, jab.a, jab.b],
+                Interpolation::JabPolar(_) => [
+                    jab.J,
+                    (jab.a * jab.a + jab.b * jab.b).sqrt(),
+                    hue_angle(jab.a, jab.b),
+                ],
+                Interpolation::JCh(_) => {
+                    let jch = JCh::from(jab);
+                    [jch.J, jch.C, jch.h]
+                }
+            })
+            .collect();
+
+        if let Interpolation::JabPolar(hue) | Interpolation::JCh(hue) = interpolation {
+            unwrap_hues(&mut points, hue);
+        }
+
+        Gradient {
+            points,
+            interpolation,
+            curve,
+            gamut_mapping: GamutMapping::Css,
+            space: PhantomData,
+        }
+    }
+
+    /// The color at `t`, clamped to [0, 1], before gamut mapping.
+    pub fn jab(&self, t: F) -> Jab<S, F> {
+        let [x, y, z] = self.point(t);
+        match self.interpolation {
+            Interpolation::Jab => Jab::new_const(x, y, z),
+            Interpolation::JabPolar(_) => {
+                let h = (F::PI / F::from_f64(180.0)) * z;
+                Jab::new_const(x, y * h.cos(), y * h.sin())
+            }
+            Interpolation::JCh(_) => Jab::from(&JCh::new(x, y, wrap_hue(z))),
+        }
+    }
+
+    /// The color at `t`, clamped to [0, 1], before gamut mapping.
+    pub fn jch(&self, t: F) -> JCh<F> {
+        match self.interpolation {
+            Interpolation::JCh(_) => {
+                let [J, C, h] = self.point(t);
+                JCh::new(J, C, wrap_hue(h))
+            }
+            _ => JCh::from(&self.jab(t)),
+        }
+    }
+
+    /// The color at `t`, clamped to [0, 1], mapped into sRGB.
+    pub fn srgb(&self, t: F) -> sRGB {
+        self.gamut_mapping.map(&self.jch(t)).srgb
+    }
+
+    /// `n` positions along the gradient, from 0 to 1, with equal ΔE (in the Jab space) between neighbours.
+    /// ΔE is measured along the gradient before gamut mapping.
+    pub fn uniform_positions(&self, n: usize) -> Vec<F> {
+        let c = F::from_f64;
+        if n < 2 {
+            return (0..n).map(|_| F::default()).collect();
+        }
+
+        let samples = LENGTH_SAMPLES * (self.points.len() - 1).max(1);
+        let mut lengths = Vec::with_capacity(samples + 1);
+        lengths.push(F::default());
+        let mut previous = self.jab(F::default());
+        for i in 1..=samples {
+            let jab = self.jab(c(i as f64 / samples as f64));
+            let length = lengths[i - 1] + jab.squared_difference(&previous).sqrt();
+            lengths.push(length);
+            previous = jab;
+        }
+
+        let total = lengths[samples];
+        (0..n)
+            .map(|k| {
+                let fraction = c(k as f64 / (n - 1) as f64);
+                if total <= F::default() {
+                    return fraction;
+                }
+
+                // find the sample interval containing the target length, and interpolate within it
+                let target = total * fraction;
+                let i = lengths
+                    .partition_point(|length| *length < target)
+                    .clamp(1, samples);
+                let (from, to) = (lengths[i - 1], lengths[i]);
+                let within = if to > from {
+                    (target - from) / (to - from)
+                } else {
+                    F::default()
+                };
+                (c((i - 1) as f64) + within) / c(samples as f64)
+            })
+            .collect()
+    }
+
+    /// `n` colors mapped into sRGB, evenly spaced by ΔE along the gradient.
+    pub fn steps(&self, n: usize) -> Vec<sRGB> {
+        self.uniform_positions(n)
+            .into_iter()
+            .map(|t| self.srgb(t))
+            .collect()
+    }
+
+    /// Evaluates the curve at `t`, in the interpolation's coordinates.
+    fn point(&self, t: F) -> [F; 3] {
+        let c = F::from_f64;
+        let n = self.points.len();
+        if n == 1 {
+            return self.points[0];
+        }
+
+        let t = if t > c(1.0) {
+            c(1.0)
+        } else if t > F::default() {
+            t
+        } else {
+            F::default()
+        };
+        let position = t * c((n - 1) as f64);
+        let i = (position.to_f64() as usize).min(n - 2);
+        let u = position - c(i as f64);
+
+        let mut point = [F::default(); 3];
+        for (axis, coordinate) in point.iter_mut().enumerate() {
+            let y = |k: usize| self.points[k][axis];
+            *coordinate = match self.curve {
+                Curve::Linear => y(i) + u * (y(i + 1) - y(i)),
+                Curve::BSpline => {
+                    // the missing neighbours at the ends are reflected, so the spline passes through the end stops
+                    let (v1, v2) = (y(i), y(i + 1));
+                    let v0 = if i > 0 { y(i - 1) } else { c(2.0) * v1 - v2 };
+                    let v3 = if i + 2 < n {
+                        y(i + 2)
+                    } else {
+                        c(2.0) * v2 - v1
+                    };
+                    b_spline(u, [v0, v1, v2, v3])
+                }
+                Curve::MonotoneCubic => {
+                    let tangent = |k: usize| {
+                        let slope = |k: usize| y(k + 1) - y(k);
+                        if k == 0 {
+                            slope(0)
+                        } else if k == n - 1 {
+                            slope(n - 2)
+                        } else {
+                            let (before, after) = (slope(k - 1), slope(k));
+                            // the harmonic mean keeps the spline monotone, and flattens it at extrema
+                            if before * after <= F::default() {
+                                F::default()
+                            } else {
+                                c(2.0) * before * after / (before + after)
+                            }
+                        }
+                    };
+                    hermite(u, y(i), y(i + 1), tangent(i), tangent(i + 1))
+                }
+            };
+        }
+        point
+    }
+}
+
+/// The uniform cubic B-spline segment between `v[1]` and `v[2]`.
+fn b_spline<F: Float>(u: F, v: [F; 4]) -> F {
+    let c = F::from_f64;
+    let (u2, u3) = (u * u, u * u * u);
+    ((c(1.0) - c(3.0) * u + c(3.0) * u2 - u3) * v[0]
+        + (c(4.0) - c(6.0) * u2 + c(3.0) * u3) * v[1]
+        + (c(1.0) + c(3.0) * u + c(3.0) * u2 - c(3.0) * u3) * v[2]
+        + u3 * v[3])
+        / c(6.0)
+}
+
+/// The cubic Hermite segment from `y0` to `y1`, with tangents `m0` and `m1`.
+fn hermite<F: Float>(u: F, y0: F, y1: F, m0: F, m1: F) -> F {
+    let c = F::from_f64;
+    let (u2, u3) = (u * u, u * u * u);
+    (c(2.0) * u3 - c(3.0) * u2 + c(1.0)) * y0
+        + (u3 - c(2.0) * u2 + u) * m0
+        + (c(3.0) * u2 - c(2.0) * u3) * y1
+        + (u3 - u2) * m1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::UCS;
+
+    fn jab(rgb: [u8; 3]) -> Jab<UCS> {
+        Jab::from(rgb)
+    }
+
+    fn delta_e(a: &Jab<UCS>, b: &Jab<UCS>) -> f32 {
+        a.squared_difference(b).sqrt()
+    }
+
+    #[test]
+    fn linear_jab() {
+        let (from, to) = (jab([255, 0, 0]), jab([0, 0, 255]));
+        let gradient = Gradient::new(&[from, to], Interpolation::Jab, Curve::Linear);
+
+        let middle = gradient.jab(0.5);
+        assert!((middle.J - (from.J + to.J) / 2.0).abs() < 1e-4);
+        assert!((middle.a - (from.a + to.a) / 2.0).abs() < 1e-4);
+        assert!((middle.b - (from.b + to.b) / 2.0).abs() < 1e-4);
+
+        assert!(delta_e(&gradient.jab(-1.0), &from) < 1e-4);
+        assert!(delta_e(&gradient.jab(2.0), &to) < 1e-4);
+    }
+
+    #[test]
+    fn hue_directions() {
+        // red is at h = 32 and blue at h = 257, so the shorter arc goes through magenta and the longer through green
+        let stops = [jab([255, 0, 0]), jab([0, 0, 255])];
+        let middle_hue = |hue| {
+            Gradient::new(&stops, Interpolation::JCh(hue), Curve::Linear)
+                .jch(0.5)
+                .h
+        };
+
+        let shorter = middle_hue(HueInterpolation::Shorter);
+        let longer = middle_hue(HueInterpolation::Longer);
+        assert!(!(10.0..=300.0).contains(&shorter), "{}", shorter);
+        assert!(longer > 100.0 && longer < 200.0, "{}", longer);
+        assert_eq!(middle_hue(HueInterpolation::Increasing), longer);
+        assert_eq!(middle_hue(HueInterpolation::Decreasing), shorter);
+
+        let polar = Gradient::new(
+            &stops,
+            Interpolation::JabPolar(HueInterpolation::Longer),
+            Curve::Linear,
+        );
+        let h = polar.jch(0.5).h;
+        assert!(h > 100.0 && h < 200.0, "{}", h);
+    }
+
+    #[test]
+    fn achromatic_stops_keep_hue() {
+        let red = JCh::from([255, 0, 0]);
+        let stops = [Jab::from(&JCh::new(100.0, 0.0, 0.0)), jab([255, 0, 0])];
+
+        for interpolation in [
+            Interpolation::JCh(HueInterpolation::Shorter),
+            Interpolation::JCh(HueInterpolation::Longer),
+            Interpolation::JabPolar(HueInterpolation::Longer),
+        ] {
+            let gradient = Gradient::new(&stops, interpolation, Curve::Linear);
+            for t in [0.25, 0.5, 0.75] {
+                let h = gradient.jch(t).h;
+                assert!((h - red.h).abs() < 0.1, "{:?} {} {}", interpolation, t, h);
+            }
+        }
+    }
+
+    #[test]
+    fn splines() {
+        let stops = [
+            jab([0, 0, 0]),
+            jab([200, 0, 0]),
+            jab([255, 200, 0]),
+            jab([255, 255, 255]),
+        ];
+
+        let cubic = Gradient::new(&stops, Interpolation::Jab, Curve::MonotoneCubic);
+        for (i, stop) in stops.iter().enumerate() {
+            assert!(delta_e(&cubic.jab(i as f32 / 3.0), stop) < 1e-3);
+        }
+        let mut previous = -1.0;
+        for i in 0..=100 {
+            let J = cubic.jab(i as f32 / 100.0).J;
+            assert!(J >= previous);
+            previous = J;
+        }
+
+        let spline = Gradient::new(&stops, Interpolation::Jab, Curve::BSpline);
+        assert!(delta_e(&spline.jab(0.0), &stops[0]) < 1e-3);
+        assert!(delta_e(&spline.jab(1.0), &stops[3]) < 1e-3);
+        assert!(delta_e(&spline.jab(1.0 / 3.0), &stops[1]) > 1.0);
+    }
+
+    #[test]
+    fn uniform_steps() {
+        let stops = [jab([0, 0, 80]), jab([0, 160, 120]), jab([255, 240, 180])];
+        let gradient = Gradient::new(&stops, Interpolation::Jab, Curve::Linear);
+
+        let positions = gradient.uniform_positions(11);
+        assert_eq!(positions.len(), 11);
+        assert_eq!((positions[0], positions[10]), (0.0, 1.0));
+
+        // each step is the same length along the gradient, so the straight ΔE between neighbours
+        // only comes up short for the step that cuts the corner at the middle stop
+        let colors: Vec<_> = positions.iter().map(|t| gradient.jab(*t)).collect();
+        let distances: Vec<_> = colors.windows(2).map(|w| delta_e(&w[0], &w[1])).collect();
+        let length = delta_e(&stops[0], &stops[1]) + delta_e(&stops[1], &stops[2]);
+        let step = length / 10.0;
+        for distance in &distances {
+            assert!(
+                *distance < step * 1.005 && *distance > step * 0.9,
+                "{} vs {}",
+                distance,
+                step
+            );
+        }
+        let exact = distances
+            .iter()
+            .filter(|d| (*d - step).abs() < step * 0.005);
+        assert_eq!(exact.count(), 9);
+
+        let steps = gradient.steps(11);
+        assert_eq!([steps[0].r, steps[0].g, steps[0].b], [0, 0, 80]);
+        assert_eq!([steps[10].r, steps[10].g, steps[10].b], [255, 240, 180]);
+
+        assert!(gradient.uniform_positions(0).is_empty());
+        assert_eq!(gradient.uniform_positions(1), [0.0]);
+    }
+
+    #[test]
+    fn gamut_mapped() {
+        // the middle of red to cyan through green is far outside sRGB in chroma
+        let stops = [jab([255, 0, 0]), jab([0, 255, 255])];
+        let mut gradient = Gradient::new(
+            &stops,
+            Interpolation::JCh(HueInterpolation::Longer),
+            Curve::Linear,
+        );
+
+        for method in [GamutMapping::Css, GamutMapping::ChromaReduction] {
+            gradient.gamut_mapping = method;
+            let middle = gradient.srgb(0.5);
+            let mapped = JCh::from(middle);
+            let requested = gradient.jch(0.5);
+            assert!((mapped.J - requested.J).abs() < 2.0, "{:?}", method);
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! Nearest color search over a set of [`Jab`] colors.
+//!
+//! [`JabTree`] is a k-d tree which answers nearest, k-nearest and radius queries by ΔE,
+//! without comparing the query against every color:
+//! ```
+//! use kasi_kule::index::JabTree;
+//! use kasi_kule::{Jab, UCS};
+//!
+//! let palette: Vec<Jab<UCS>> = [[0, 0, 0], [255, 0, 0], [0, 0, 255], [255, 255, 255]]
+//!     .iter()
+//!     .map(|rgb| Jab::from_srgb(*rgb))
+//!     .collect();
+//! let tree = JabTree::new(&palette);
+//!
+//! let nearest = tree.nearest(&Jab::from_srgb([200, 30, 20])).unwrap();
+//! assert_eq!(nearest.index, 1);
+//! ```
+//!
+//! The tree keeps the [`JabSpace`] of its colors, so it can only be queried with colors of the same space:
+//! ```compile_fail
+//! # use kasi_kule::index::JabTree;
+//! # use kasi_kule::{Jab, SCD, UCS};
+//! let tree = JabTree::new(&[Jab::<UCS>::from_srgb([255, 0, 0])]);
+//! tree.nearest(&Jab::<SCD>::from_srgb([200, 30, 20]));
+//! ```
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::marker::PhantomData;
+
+use crate::{Float, Jab, JabSpace};
+
+/// A color found by a query.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Neighbour<F: Float = f32> {
+    /// The position of the color in the slice the tree was built from.
+    pub index: usize,
+    /// ΔE between the color and the query, as [`Jab::squared_difference`] measures it.
+    pub delta_e: F,
+}
+
+/// A k-d tree over colors in the same [`JabSpace`].
+///
+/// The tree is stored implicitly: the colors are sorted so that the median of every range splits it,
+/// cycling through the J, a and b axes.
+#[derive(Debug, Clone)]
+pub struct JabTree<S: JabSpace, F: Float = f32> {
+    // coordinates with J divided by k_l, so that squared distances match `Jab::squared_difference`
+    points: Vec<[F; 3]>,
+    indices: Vec<usize>,
+    space: PhantomData<S>,
+}
+
+fn coordinates<S: JabSpace, F: Float>(color: &Jab<S, F>) -> [F; 3] {
+    [color.J / F::from_f64(S::k_l_f64), color.a, color.b]
+}
+
+fn squared_distance<F: Float>(a: &[F; 3], b: &[F; 3]) -> F {
+    let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+}
+
+fn compare<F: Float>(a: &F, b: &F) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+}
+
+impl<S: JabSpace, F: Float> JabTree<S, F> {
+    pub fn new(colors: &[Jab<S, F>]) -> JabTree<S, F> {
+        let mut entries: Vec<([F; 3], usize)> = colors
+            .iter()
+            .enumerate()
+            .map(|(i, color)| (coordinates(color), i))
+            .collect();
+        sort(&mut entries, 0);
+
+        JabTree {
+            points: entries.iter().map(|(point, _)| *point).collect(),
+            indices: entries.iter().map(|(_, index)| *index).collect(),
+            space: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The closest color to `query`, or None if the tree is empty.
+    pub fn nearest(&self, query: &Jab<S, F>) -> Option<Neighbour<F>> {
+        self.k_nearest(query, 1).pop()
+    }
+
+    /// The `k` closest colors to `query`, closest first.
+    pub fn k_nearest(&self, query: &Jab<S, F>, k: usize) -> Vec<Neighbour<F>> {
+        let mut found = Vec::with_capacity(k + 1);
+        if k > 0 {
+            self.search(
+                &coordinates(query),
+                0,
+                self.len(),
+                0,
+                &mut |index, distance| {
+                    let position = found.partition_point(|(_, d)| compare(d, &distance).is_le());
+                    if position < k {
+                        found.insert(position, (index, distance));
+                        found.truncate(k);
+                    }
+                    // only colors closer than the furthest one found can still make it in
+                    if found.len() < k {
+                        None
+                    } else {
+                        found.last().map(|(_, d)| *d)
+                    }
+                },
+            );
+        }
+
+        self.neighbours(found)
+    }
+
+    /// Every color within ΔE `radius` of `query`, closest first.
+    pub fn within(&self, query: &Jab<S, F>, radius: F) -> Vec<Neighbour<F>> {
+        let limit = radius * radius;
+        let mut found = Vec::new();
+        self.search(
+            &coordinates(query),
+            0,
+            self.len(),
+            0,
+            &mut |index, distance| {
+                if distance <= limit {
+                    found.push((index, distance));
+                }
+                Some(limit)
+            },
+        );
+
+        found.sort_by(|a, b| compare(&a.1, &b.1));
+        self.neighbours(found)
+    }
+
+    fn neighbours(&self, found: Vec<(usize, F)>) -> Vec<Neighbour<F>> {
+        found
+            .into_iter()
+            .map(|(i, distance)| Neighbour {
+                index: self.indices[i],
+                delta_e: distance.sqrt(),
+            })
+            .collect()
+    }
+
+    /// Visits the points of the subtree over `start..end` that could be within reach.
+    /// `visit` is given each point's position and squared distance,
+    /// and returns the squared distance beyond which nothing is wanted any more.
+    fn search(
+        &self,
+        query: &[F; 3],
+        start: usize,
+        end: usize,
+        axis: usize,
+        visit: &mut impl FnMut(usize, F) -> Option<F>,
+    ) -> Option<F> {
+        if start >= end {
+            return None;
+        }
+
+        let median = start + (end - start) / 2;
+        let mut reach = visit(median, squared_distance(query, &self.points[median]));
+
+        let offset = query[axis] - self.points[median][axis];
+        let next = (axis + 1) % 3;
+        let (near, far) = if offset < F::default() {
+            ((start, median), (median + 1, end))
+        } else {
+            ((median + 1, end), (start, median))
+        };
+
+        if let Some(limit) = self.search(query, near.0, near.1, next, visit) {
+            reach = Some(limit);
+        }
+        // the far side is only worth searching if the splitting plane is within reach
+        if reach.map_or(true, |limit| offset * offset <= limit) {
+            if let Some(limit) = self.search(query, far.0, far.1, next, visit) {
+                reach = Some(limit);
+            }
+        }
+
+        reach
+    }
+}
+
+/// Sorts `entries` into an implicit k-d tree, splitting on `axis` at the top.
+fn sort<F: Float>(entries: &mut [([F; 3], usize)], axis: usize) {
+    if entries.len() < 2 {
+        return;
+    }
+
+    let median = entries.len() / 2;
+    entries.select_nth_unstable_by(median, |a, b| compare(&a.0[axis], &b.0[axis]));
+    let (below, above) = entries.split_at_mut(median);
+    sort(below, (axis + 1) % 3);
+    sort(&mut above[1..], (axis + 1) % 3);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{LCD, UCS};
+
+    fn random_colors<S: JabSpace>(rng: &mut fastrand::Rng, n: usize) -> Vec<Jab<S>> {
+        (0..n)
+            .map(|_| Jab::from_srgb([rng.u8(..), rng.u8(..), rng.u8(..)]))
+            .collect()
+    }
+
+    fn brute_force<S: JabSpace>(colors: &[Jab<S>], query: &Jab<S>) -> Vec<(usize, f32)> {
+        let mut all: Vec<(usize, f32)> = colors
+            .iter()
+            .enumerate()
+            .map(|(i, color)| (i, color.squared_difference(query).sqrt()))
+            .collect();
+        all.sort_by(|a, b| a.1.total_cmp(&b.1));
+        all
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = fastrand::Rng::with_seed(11);
+        let colors = random_colors::<LCD>(&mut rng, 500);
+        let tree = JabTree::new(&colors);
+        assert_eq!(tree.len(), 500);
+
+        for query in random_colors::<LCD>(&mut rng, 50) {
+            let expected = brute_force(&colors, &query);
+
+            let nearest = tree.nearest(&query).unwrap();
+            assert!((nearest.delta_e - expected[0].1).abs() < 1e-4);
+
+            let k_nearest = tree.k_nearest(&query, 7);
+            assert_eq!(k_nearest.len(), 7);
+            for (found, (_, delta_e)) in k_nearest.iter().zip(&expected) {
+                assert!((found.delta_e - delta_e).abs() < 1e-4);
+            }
+
+            let within = tree.within(&query, 15.0);
+            let count = expected.iter().filter(|(_, d)| *d <= 15.0).count();
+            assert_eq!(within.len(), count);
+            assert!(within.windows(2).all(|w| w[0].delta_e <= w[1].delta_e));
+        }
+    }
+
+    #[test]
+    fn small_trees() {
+        let empty = JabTree::<UCS>::new(&[]);
+        let query = Jab::<UCS>::from_srgb([10, 20, 30]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.nearest(&query), None);
+        assert!(empty.within(&query, 100.0).is_empty());
+
+        let colors = [
+            Jab::<UCS>::from_srgb([10, 20, 30]),
+            Jab::from_srgb([200, 0, 0]),
+        ];
+        let tree = JabTree::new(&colors);
+        assert_eq!(tree.nearest(&query).unwrap().index, 0);
+        assert_eq!(tree.nearest(&query).unwrap().delta_e, 0.0);
+        assert_eq!(tree.k_nearest(&query, 5).len(), 2);
+        assert!(tree.k_nearest(&query, 0).is_empty());
+        assert_eq!(tree.within(&query, 1.0).len(), 1);
+    }
+}
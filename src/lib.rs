@@ -1,3 +1,4 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![allow(non_snake_case)]
 #![allow(non_camel_case_types)]
 #![allow(non_upper_case_globals)]
@@ -5,24 +6,71 @@
 //! kasi-kule is a small rust implementation of the [CIECAM02 color space](https://en.wikipedia.org/wiki/CIECAM02) and conversion to it from standard RGB.
 //! It is based on the [d3-cam02](https://github.com/connorgr/d3-cam02/) and [colorspacious](https://github.com/njsmith/colorspacious).
 //!
+//! Every color type is generic over its [`Float`] precision, defaulting to `f32`.
+//! The `From` conversions out of sRGB are the `f32` fast path; other precisions go through `from_srgb`:
+//! ```
+//! use kasi_kule::{Jab, UCS};
+//! # #[cfg(any(feature = "std", feature = "libm"))] {
+//!
+//! let fast = Jab::<UCS>::from([255, 0, 0]);
+//! let precise = Jab::<UCS, f64>::from_srgb([255, 0, 0]);
+//! assert!((fast.J as f64 - precise.J).abs() < 0.01);
+//! # }
+//! ```
+//!
+//! The crate is `no_std` when the default `std` feature is turned off.
+//! Float math then comes from either the `libm` feature, or from `micromath` (which only supports `f32`, and turns on the `approximate_math` approximations).
+//! The modules that build tables, like [`boundary`], also need the `alloc` feature.
+//!
 //! The name, kasi-kule, is a translation of 'flower' into toki pona - literally, 'colorful plant'.
 //! o sitelen pona!
-use std::f32::consts::PI;
-use std::marker::PhantomData;
+use appearance::CamAppearance;
+use consts::ViewingConditions;
+use core::marker::PhantomData;
+use error::{finite, within, Stage};
+#[cfg(feature = "alloc")]
+extern crate alloc;
+pub mod appearance;
+#[cfg(feature = "alloc")]
+pub mod boundary;
+#[cfg(feature = "alloc")]
+pub mod colormap;
 pub mod consts;
+pub mod contrast;
+pub mod css;
+pub mod cvd;
+#[cfg(feature = "alloc")]
+pub mod diff;
+pub mod error;
+pub mod float;
+pub mod gamut;
+#[cfg(feature = "alloc")]
+pub mod gradient;
+#[cfg(feature = "alloc")]
+pub mod index;
+#[cfg(feature = "alloc")]
+pub mod palette;
+#[cfg(feature = "image")]
+pub mod planar;
+#[cfg(feature = "alloc")]
+pub mod quantize;
+#[cfg(test)]
+mod reference;
 pub mod utils;
-use consts::VC;
-pub use consts::{LCD, SCD, UCS};
+pub use consts::{CartesianC, CartesianM, CartesianS, Chroma, Saturation, LCD, SCD, UCS};
+pub use error::Error;
+pub use float::Float;
 use utils::*;
+#[cfg(feature = "approximate_math")]
+pub mod fastmath;
+#[cfg(feature = "serde")]
+mod serialization;
 #[cfg(all(feature = "sse", any(target_arch = "x86", target_arch = "x86_64")))]
 pub mod sse;
 
-#[cfg(feature = "approximate_math")]
-#[allow(unused_imports)]
-use micromath::F32Ext;
-
 /// sRGB color, in the 0-255 range.
-#[derive(Default, Debug, Copy, Clone)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct sRGB {
     pub r: u8,
     pub g: u8,
@@ -51,22 +99,51 @@ impl From<(u8, u8, u8)> for sRGB {
 
 /// Linearized RGB, scaled from sRGB
 #[derive(Default, Debug, Copy, Clone)]
-pub struct LinearRGB {
-    pub r: f32,
-    pub g: f32,
-    pub b: f32,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinearRGB<F: Float = f32> {
+    pub r: F,
+    pub g: F,
+    pub b: F,
+}
+
+impl<F: Float> LinearRGB<F> {
+    pub fn from_srgb(rgb: impl Into<sRGB>) -> LinearRGB<F> {
+        let srgb = rgb.into();
+        LinearRGB {
+            r: F::linearize(srgb.r),
+            g: F::linearize(srgb.g),
+            b: F::linearize(srgb.b),
+        }
+    }
+
+    /// Encodes into sRGB, rounding to the nearest code value.
+    /// Returns `None` if any channel falls outside the sRGB gamut by more than half a code value.
+    pub fn to_srgb(&self) -> Option<sRGB> {
+        let encode = |c: F| {
+            let code = delinearize(c) * F::from_f64(255.0);
+            if !(F::from_f64(-0.5)..=F::from_f64(255.5)).contains(&code) {
+                return None;
+            }
+            Some((code.to_f64() + 0.5).clamp(0.0, 255.0) as u8)
+        };
+
+        Some(sRGB {
+            r: encode(self.r)?,
+            g: encode(self.g)?,
+            b: encode(self.b)?,
+        })
+    }
+}
+
+impl<F: Float> From<&XYZ<F>> for LinearRGB<F> {
+    fn from(xyz: &XYZ<F>) -> LinearRGB<F> {
+        xyz_to_linear_rgb_scalar(xyz)
+    }
 }
 
 impl From<&sRGB> for LinearRGB {
     fn from(srgb: &sRGB) -> LinearRGB {
-        // safety: bounds checked by type; array is u8::MAX-sized and indexes are u8s
-        unsafe {
-            LinearRGB {
-                r: *consts::sRGB_LOOKUP.get_unchecked(srgb.r as usize),
-                g: *consts::sRGB_LOOKUP.get_unchecked(srgb.g as usize),
-                b: *consts::sRGB_LOOKUP.get_unchecked(srgb.b as usize),
-            }
-        }
+        LinearRGB::from_srgb(*srgb)
     }
 }
 
@@ -78,265 +155,514 @@ impl<T: Into<sRGB>> From<T> for LinearRGB {
 
 /// CIEXYZ 1931 Color space, in the 0-100 range.
 #[derive(Debug, Copy, Clone)]
-pub struct XYZ {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct XYZ<F: Float = f32> {
+    pub x: F,
+    pub y: F,
+    pub z: F,
 }
 
-impl From<&LinearRGB> for XYZ {
-    fn from(rgb: &LinearRGB) -> XYZ {
-        #[cfg(all(feature = "sse",any(target_arch = "x86", target_arch = "x86_64")))]
-        {
-            unsafe {
-                if is_x86_feature_detected!("sse") {
-                    let res = sse::sse_xyz(rgb);
-                    return XYZ {
-                        x: res[0],
-                        y: res[1],
-                        z: res[2],
-                    };
-                }
-            }
-        }
+impl<F: Float> XYZ<F> {
+    pub fn from_srgb(rgb: impl Into<sRGB>) -> XYZ<F> {
+        XYZ::from(&LinearRGB::from_srgb(rgb))
+    }
+}
 
-        XYZ {
-            x: ((rgb.r * 0.4124) + (rgb.g * 0.3576) + (rgb.b * 0.1805)) * 100.0,
-            y: ((rgb.r * 0.2126) + (rgb.g * 0.7152) + (rgb.b * 0.0722)) * 100.0,
-            z: ((rgb.r * 0.0193) + (rgb.g * 0.1192) + (rgb.b * 0.9505)) * 100.0,
-        }
+impl<F: Float> From<&LinearRGB<F>> for XYZ<F> {
+    fn from(rgb: &LinearRGB<F>) -> XYZ<F> {
+        F::linear_rgb_to_xyz(rgb)
+    }
+}
+
+impl<F: Float> From<&LMS<F>> for XYZ<F> {
+    fn from(lms: &LMS<F>) -> XYZ<F> {
+        lms_to_xyz_scalar(lms)
     }
 }
 
 impl<T: Into<sRGB>> From<T> for XYZ {
     fn from(rgb: T) -> XYZ {
-        XYZ::from(&LinearRGB::from(&rgb.into()))
+        XYZ::from_srgb(rgb)
     }
 }
 
 /// Long-Medium-Short color space, derived from XYZ using the Mcat02 matrix.
 #[derive(Debug, Copy, Clone)]
-pub struct LMS {
-    pub l: f32,
-    pub m: f32,
-    pub s: f32,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LMS<F: Float = f32> {
+    pub l: F,
+    pub m: F,
+    pub s: F,
 }
 
-impl From<&XYZ> for LMS {
-    fn from(xyz: &XYZ) -> LMS {
-        #[cfg(all(feature = "sse",any(target_arch = "x86", target_arch = "x86_64")))]
-        {
-            unsafe {
-                if is_x86_feature_detected!("sse") {
-                    let res = sse::sse_lms(xyz);
-                    return LMS {
-                        l: res[0],
-                        m: res[1],
-                        s: res[2],
-                    };
-                }
+impl<F: Float> LMS<F> {
+    pub fn from_srgb(rgb: impl Into<sRGB>) -> LMS<F> {
+        LMS::from(&XYZ::from_srgb(rgb))
+    }
+}
+
+impl<F: Float> From<&XYZ<F>> for LMS<F> {
+    fn from(xyz: &XYZ<F>) -> LMS<F> {
+        F::xyz_to_lms(xyz)
+    }
+}
+
+impl<F: Float> From<&HPE<F>> for LMS<F> {
+    fn from(hpe: &HPE<F>) -> LMS<F> {
+        hpe_to_lms_scalar(hpe)
+    }
+}
+
+/// Inverts the CIECAM02 model, using only J, C and h.
+impl<F: Float> From<&JCh<F>> for LMS<F> {
+    fn from(jch: &JCh<F>) -> LMS<F> {
+        let vc = F::VIEWING_CONDITIONS;
+        let c = F::from_f64;
+        let zero = F::default();
+
+        let t = if jch.J > zero {
+            (jch.C / ((jch.J / c(100.0)).sqrt() * (c(1.64) - c(0.29).powf(vc.n)).powf(c(0.73))))
+                .powf(c(1.0 / 0.9))
+        } else {
+            zero
+        };
+
+        let h = (F::PI / c(180.0)) * jch.h;
+        let (sin, cos) = (h.sin(), h.cos());
+        let et = c(0.25) * ((h + c(2.0)).cos() + c(3.8));
+        let a = vc.achromatic_response_to_white * (jch.J / c(100.0)).powf(c(1.0) / (vc.c * vc.z));
+
+        let p2 = a / vc.nbb + c(0.305);
+        let p3 = c(21.0 / 20.0);
+
+        let (ca, cb) = if t == zero {
+            (zero, zero)
+        } else {
+            let p1 = (c(50000.0 / 13.0) * vc.nc * vc.ncb * et) / t;
+            let numerator = p2 * (c(2.0) + p3) * c(460.0 / 1403.0);
+
+            if sin.abs() >= cos.abs() {
+                let p4 = p1 / sin;
+                let cb = numerator
+                    / (p4 + (c(2.0) + p3) * c(220.0 / 1403.0) * (cos / sin) - c(27.0 / 1403.0)
+                        + p3 * c(6300.0 / 1403.0));
+                (cb * (cos / sin), cb)
+            } else {
+                let p5 = p1 / cos;
+                let ca = numerator
+                    / (p5 + (c(2.0) + p3) * c(220.0 / 1403.0)
+                        - (c(27.0 / 1403.0) - p3 * c(6300.0 / 1403.0)) * (sin / cos));
+                (ca, ca * (sin / cos))
             }
-        }
+        };
 
-        LMS {
-            l: (0.7328 * xyz.x) + (0.4296 * xyz.y) - (0.1624 * xyz.z),
-            m: (-0.7036 * xyz.x) + (1.6975 * xyz.y) + (0.0061 * xyz.z),
-            s: (0.0030 * xyz.x) + (0.0136 * xyz.y) + (0.9834 * xyz.z),
-        }
+        let lpa = (c(460.0) * p2 + c(451.0) * ca + c(288.0) * cb) / c(1403.0);
+        let mpa = (c(460.0) * p2 - c(891.0) * ca - c(261.0) * cb) / c(1403.0);
+        let spa = (c(460.0) * p2 - c(220.0) * ca - c(6300.0) * cb) / c(1403.0);
+
+        let adapted = LMS::from(&HPE {
+            lh: inverse_nonlinear_adaptation_scalar(lpa, vc.fl),
+            mh: inverse_nonlinear_adaptation_scalar(mpa, vc.fl),
+            sh: inverse_nonlinear_adaptation_scalar(spa, vc.fl),
+        });
+
+        let [l, m, s, _] = inverse_transform_cones([adapted.l, adapted.m, adapted.s, zero]);
+
+        LMS { l, m, s }
     }
 }
 
 impl<T: Into<sRGB>> From<T> for LMS {
     fn from(rgb: T) -> LMS {
-        LMS::from(&XYZ::from(&LinearRGB::from(&rgb.into())))
+        LMS::from_srgb(rgb)
     }
 }
 
 /// Hunt-Pointer-Estevez space, derived from CAM02 LMS.
 #[derive(Debug, Copy, Clone)]
-pub struct HPE {
-    pub lh: f32,
-    pub mh: f32,
-    pub sh: f32,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HPE<F: Float = f32> {
+    pub lh: F,
+    pub mh: F,
+    pub sh: F,
 }
 
-impl From<&LMS> for HPE {
-    fn from(lms: &LMS) -> HPE {
-        #[cfg(all(feature = "sse",any(target_arch = "x86", target_arch = "x86_64")))]
-        {
-            unsafe {
-                if is_x86_feature_detected!("sse") {
-                    let res = sse::sse_hpe(lms);
-                    return HPE {
-                        lh: res[0],
-                        mh: res[1],
-                        sh: res[2],
-                    };
-                }
-            }
-        }
+impl<F: Float> HPE<F> {
+    pub fn from_srgb(rgb: impl Into<sRGB>) -> HPE<F> {
+        HPE::from(&LMS::from_srgb(rgb))
+    }
+}
 
-        HPE {
-            lh: (0.7409792 * lms.l) + (0.2180250 * lms.m) + (0.0410058 * lms.s),
-            mh: (0.2853532 * lms.l) + (0.6242014 * lms.m) + (0.0904454 * lms.s),
-            sh: (-0.0096280 * lms.l) - (0.0056980 * lms.m) + (1.0153260 * lms.s),
-        }
+impl<F: Float> From<&LMS<F>> for HPE<F> {
+    fn from(lms: &LMS<F>) -> HPE<F> {
+        F::lms_to_hpe(lms)
     }
 }
 
 impl<T: Into<sRGB>> From<T> for HPE {
     fn from(rgb: T) -> HPE {
-        HPE::from(&LMS::from(&XYZ::from(&LinearRGB::from(&rgb.into()))))
+        HPE::from_srgb(rgb)
     }
 }
 
 /// The CIECAM02 JCh (Lightness, Chroma, Hue) color space, derived from LMS.
 #[derive(Default, Debug, Copy, Clone)]
-pub struct JCh {
-    pub J: f32,
-    pub C: f32,
-    pub H: f32,
-    pub h: f32,
-    pub Q: f32,
-    pub M: f32,
-    pub s: f32,
-}
-
-impl From<&LMS> for JCh {
-    fn from(lms: &LMS) -> JCh {
-        let [lc, mc, sc, _] = transform_cones([lms.l, lms.m, lms.s, 0.0]);
-
-        let hpe_transforms = HPE::from(&LMS {
-            l: lc,
-            m: mc,
-            s: sc,
-        });
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JCh<F: Float = f32> {
+    pub J: F,
+    pub C: F,
+    pub H: F,
+    pub h: F,
+    pub Q: F,
+    pub M: F,
+    pub s: F,
+}
 
-        let [lpa, mpa, spa, _] = nonlinear_adaptation(
-            [hpe_transforms.lh, hpe_transforms.mh, hpe_transforms.sh, 0.0],
-            VC::fl,
-        );
+impl<F: Float> JCh<F> {
+    /// Builds a color from its lightness, chroma and hue angle (in degrees), deriving the other correlates.
+    pub fn new(J: F, C: F, h: F) -> JCh<F> {
+        JCh::with_conditions(J, C, h, &F::VIEWING_CONDITIONS)
+    }
 
-        let ca = lpa - ((12.0 * mpa) / 11.0) + (spa / 11.0);
-        let cb = (1.0 / 9.0) * (lpa + mpa - 2.0 * spa);
+    /// As [`JCh::new`], in other viewing conditions than the crate's.
+    pub(crate) fn with_conditions(J: F, C: F, h: F, vc: &ViewingConditions<F>) -> JCh<F> {
+        let c = F::from_f64;
 
-        let mut result_color = JCh::default();
+        // just under 20.14°, H rounds up to 400 in f32, which is the same as 0
+        let H = c(hue_quadrature(h.to_f64()));
+        let H = if H >= c(400.0) { F::default() } else { H };
 
-        result_color.h = (180.0 / PI) * cb.atan2(ca);
-        if result_color.h < 0.0 {
-            result_color.h += 360.0;
-        }
+        let Q = (c(4.0) / vc.c)
+            * (J / c(100.0)).sqrt()
+            * (vc.achromatic_response_to_white + c(4.0))
+            * vc.fl.powf(c(0.25));
 
-        let H = match result_color.h {
-            h if h < 20.14 => {
-                let temp = ((h + 122.47) / 1.2) + ((20.14 - h) / 0.8);
-                300.0 + (100.0 * ((h + 122.47) / 1.2)) / temp
-            }
-            h if h < 90.0 => {
-                let temp = ((h - 20.14) / 0.8) + ((90.0 - h) / 0.7);
-                (100.0 * ((h - 20.14) / 0.8)) / temp
-            }
+        let M = C * vc.fl.powf(c(0.25));
 
-            h if h < 164.25 => {
-                let temp = ((h - 90.0) / 0.7) + ((164.25 - h) / 1.0);
-                100.0 + ((100.0 * ((h - 90.0) / 0.7)) / temp)
-            }
-            h if h < 237.53 => {
-                let temp = ((h - 164.25) / 1.0) + ((237.53 - h) / 1.2);
-                200.0 + ((100.0 * ((h - 164.25) / 1.0)) / temp)
-            }
-            h => {
-                let temp = ((h - 237.53) / 1.2) + ((360.0 - h + 20.14) / 0.8);
-                300.0 + ((100.0 * ((h - 237.53) / 1.2)) / temp)
-            }
+        // black has no brightness to have saturation relative to
+        let s = if Q > F::default() {
+            c(100.0) * (M / Q).sqrt()
+        } else {
+            F::default()
         };
 
-        result_color.H = H;
+        JCh {
+            J,
+            C,
+            H,
+            h,
+            Q,
+            M,
+            s,
+        }
+    }
+
+    pub fn from_srgb(rgb: impl Into<sRGB>) -> JCh<F> {
+        JCh::from(&LMS::from_srgb(rgb))
+    }
+}
+
+/// Hue quadrature, from the hue angle in degrees.
+fn hue_quadrature(h: f64) -> f64 {
+    match h {
+        h if h < 20.14 => {
+            let temp = ((h + 122.47) / 1.2) + ((20.14 - h) / 0.8);
+            300.0 + (100.0 * ((h + 122.47) / 1.2)) / temp
+        }
+        h if h < 90.0 => {
+            let temp = ((h - 20.14) / 0.8) + ((90.0 - h) / 0.7);
+            (100.0 * ((h - 20.14) / 0.8)) / temp
+        }
+
+        h if h < 164.25 => {
+            let temp = ((h - 90.0) / 0.7) + ((164.25 - h) / 1.0);
+            100.0 + ((100.0 * ((h - 90.0) / 0.7)) / temp)
+        }
+        h if h < 237.53 => {
+            let temp = ((h - 164.25) / 1.0) + ((237.53 - h) / 1.2);
+            200.0 + ((100.0 * ((h - 164.25) / 1.0)) / temp)
+        }
+        h => {
+            let temp = ((h - 237.53) / 1.2) + ((360.0 - h + 20.14) / 0.8);
+            300.0 + ((100.0 * ((h - 237.53) / 1.2)) / temp)
+        }
+    }
+}
+
+/// Adapts the cone responses to the white point, and passes them through the nonlinearity in HPE space.
+pub(crate) fn adapted_responses<F: Float>(lms: &LMS<F>, vc: &ViewingConditions<F>) -> [F; 3] {
+    let adapt = |cone: F, white_cone: F| {
+        cone * (((vc.white.y * vc.d) / white_cone) + (F::from_f64(1.0) - vc.d))
+    };
+    let white = vc.white_lms;
+
+    let hpe_transforms = HPE::from(&LMS {
+        l: adapt(lms.l, white.l),
+        m: adapt(lms.m, white.m),
+        s: adapt(lms.s, white.s),
+    });
+
+    let [lpa, mpa, spa, _] = nonlinear_adaptation(
+        [
+            hpe_transforms.lh,
+            hpe_transforms.mh,
+            hpe_transforms.sh,
+            F::default(),
+        ],
+        vc.fl,
+    );
+    [lpa, mpa, spa]
+}
 
-        let a = (2.0 * lpa + mpa + 0.05 * spa - 0.305) * VC::nbb;
-        result_color.J = 100.0 * (a / VC::achromatic_response_to_white).powf(VC::c * VC::z);
+/// Colors whose achromatic response A is negative, which only imaginary colors like pure Z have,
+/// get a lightness J of NaN, and so do the correlates derived from it: CIECAM02 raises A to a
+/// fractional power, and defines no lightness below black. [`JCh::try_from_lms`] returns an error instead.
+impl<F: Float> From<&LMS<F>> for JCh<F> {
+    fn from(lms: &LMS<F>) -> JCh<F> {
+        JCh::from(&CamAppearance::from(lms))
+    }
+}
 
-        let et = 0.25 * (((result_color.h * PI) / 180.0 + 2.0).cos() + 3.8);
-        let t = (50000.0 / 13.0) * VC::nc * VC::ncb * et * (ca.powi(2) + cb.powi(2)).sqrt()
-            / (lpa + mpa + (21.0 / 20.0) * spa);
+impl<F: Float> JCh<F> {
+    /// The same conversion as `JCh::from(&LMS)`, but checking the cone responses, the adapted responses
+    /// and the correlates on the way, and returning an error for the first value that isn't finite
+    /// instead of a color of NaNs.
+    ///
+    /// Any finite cone responses adapt to finite responses, but the darkest imaginary colors,
+    /// whose achromatic response is negative, have no lightness.
+    pub fn try_from_lms(lms: &LMS<F>) -> Result<JCh<F>, Error> {
+        finite(Stage::LMS, &[("l", lms.l), ("m", lms.m), ("s", lms.s)])?;
 
-        result_color.C = t.powf(0.9f32)
-            * (result_color.J / 100.0).sqrt()
-            * (1.64 - 0.29f32.powf(VC::n)).powf(0.73f32);
+        let vc = F::VIEWING_CONDITIONS;
+        let [lpa, mpa, spa] = adapted_responses(lms, &vc);
+        finite(Stage::HPE, &[("lh", lpa), ("mh", mpa), ("sh", spa)])?;
 
-        result_color.Q = (4.0 / VC::c)
-            * (result_color.J / 100.0).sqrt()
-            * (VC::achromatic_response_to_white + 4.0f32)
-            * VC::fl.powf(0.25f32);
+        let jch = JCh::from(&CamAppearance::from_adapted_responses([lpa, mpa, spa], &vc));
+        finite(
+            Stage::JCh,
+            &[
+                ("J", jch.J),
+                ("C", jch.C),
+                ("h", jch.h),
+                ("H", jch.H),
+                ("Q", jch.Q),
+                ("M", jch.M),
+                ("s", jch.s),
+            ],
+        )?;
+        Ok(jch)
+    }
 
-        result_color.M = result_color.C * VC::fl.powf(0.25f32);
+    /// The same conversion as `JCh::from(&LMS::from(xyz))`, checked as in [`JCh::try_from_lms`].
+    pub fn try_from_xyz(xyz: &XYZ<F>) -> Result<JCh<F>, Error> {
+        finite(Stage::XYZ, &[("x", xyz.x), ("y", xyz.y), ("z", xyz.z)])?;
+        JCh::try_from_lms(&LMS::from(xyz))
+    }
+}
+
+impl<F: Float> XYZ<F> {
+    /// Checks that the color is finite, and has no negative components, as no physical color does.
+    pub fn validate(&self) -> Result<(), Error> {
+        let range = (0.0, f64::MAX);
+        within(
+            Stage::XYZ,
+            &[
+                ("x", self.x, range),
+                ("y", self.y, range),
+                ("z", self.z, range),
+            ],
+        )
+    }
+}
+
+impl<F: Float> JCh<F> {
+    /// Checks the correlates the inverse model reads: a lightness J within 0-100, a non-negative chroma C,
+    /// and a finite hue angle h. White itself comes out a hair above 100, so J is allowed that much more.
+    pub fn validate(&self) -> Result<(), Error> {
+        within(
+            Stage::JCh,
+            &[
+                ("J", self.J, (0.0, 100.001)),
+                ("C", self.C, (0.0, f64::MAX)),
+                ("h", self.h, (f64::MIN, f64::MAX)),
+            ],
+        )
+    }
+}
 
-        result_color.s = 100.0 * (result_color.M / result_color.Q).sqrt();
+/// The checked forward model: the color is validated, and so is every stage.
+/// Unlike [`JCh::try_from_xyz`], this rejects negative XYZ components.
+///
+/// The viewing conditions are the crate's fixed ones, [`Float::VIEWING_CONDITIONS`], so there's nothing
+/// about them that could fail.
+impl<F: Float> TryFrom<&XYZ<F>> for JCh<F> {
+    type Error = Error;
 
-        result_color
+    fn try_from(xyz: &XYZ<F>) -> Result<JCh<F>, Error> {
+        xyz.validate()?;
+        JCh::try_from_xyz(xyz)
+    }
+}
+
+impl<S: JabSpace, F: Float> TryFrom<&XYZ<F>> for Jab<S, F> {
+    type Error = Error;
+
+    fn try_from(xyz: &XYZ<F>) -> Result<Jab<S, F>, Error> {
+        JCh::try_from(xyz).map(|jch| Jab::from(&jch))
+    }
+}
+
+/// The checked inverse model, for correlates that pass [`JCh::validate`].
+impl<F: Float> TryFrom<&JCh<F>> for XYZ<F> {
+    type Error = Error;
+
+    fn try_from(jch: &JCh<F>) -> Result<XYZ<F>, Error> {
+        jch.validate()?;
+
+        let lms = LMS::from(jch);
+        finite(Stage::LMS, &[("l", lms.l), ("m", lms.m), ("s", lms.s)])?;
+        let xyz = XYZ::from(&lms);
+        finite(Stage::XYZ, &[("x", xyz.x), ("y", xyz.y), ("z", xyz.z)])?;
+        Ok(xyz)
+    }
+}
+
+impl<S: JabSpace, F: Float> TryFrom<&Jab<S, F>> for XYZ<F> {
+    type Error = Error;
+
+    fn try_from(jab: &Jab<S, F>) -> Result<XYZ<F>, Error> {
+        finite(Stage::Jab, &[("J", jab.J), ("a", jab.a), ("b", jab.b)])?;
+        XYZ::try_from(&JCh::from(jab))
     }
 }
 
 impl<T: Into<sRGB>> From<T> for JCh {
     fn from(rgb: T) -> JCh {
-        JCh::from(&LMS::from(&XYZ::from(&LinearRGB::from(&rgb.into()))))
+        JCh::from_srgb(rgb)
     }
 }
 
 /// the JabSpace defines constants for transformation from JCh space into JabSpace. Used for type-checking comparisons between Jab colors.
 pub trait JabSpace {
+    /// Name of the space, used to tag serialized colors. Spaces that don't name themselves are all "Jab",
+    /// so their serialized colors can be read back into each other.
+    const NAME: &'static str = "Jab";
     const k_l: f32;
     const c1: f32;
+    /// The compression of the correlate; a space with a c2 of 0 leaves it as it is.
     const c2: f32;
+    /// `k_l`, `c1` and `c2` as the `f64` conversions use them. They default to the `f32` values,
+    /// so a space only needs them if its coefficients aren't exact in `f32`.
+    const k_l_f64: f64 = Self::k_l as f64;
+    const c1_f64: f64 = Self::c1 as f64;
+    const c2_f64: f64 = Self::c2 as f64;
+    /// The correlate a' and b' are built from, colorfulness for the CAM02 spaces.
+    const CORRELATE: Correlate = Correlate::Colorfulness;
+}
+
+/// A CIECAM02 correlate of colorfulness, which a [`JabSpace`] turns into the radius of a' and b'.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Correlate {
+    /// Chroma, C.
+    Chroma,
+    /// Colorfulness, M.
+    Colorfulness,
+    /// Saturation, s.
+    Saturation,
 }
 
 /// The CAM02 Jab color appearance model.
 /// It can be transformed from JCh space into an approximately perceptually uniform space (UCS), or into a space optimized for either LCD (Large Color Differences) or SCD (Small Color Differences).
 /// Subsequent calculations of color difference must be between colors within the same space (UCS/LCD/SCD).
 #[derive(Default, Debug, Copy, Clone)]
-pub struct Jab<S: JabSpace> {
-    pub J: f32,
-    pub a: f32,
-    pub b: f32,
+pub struct Jab<S: JabSpace, F: Float = f32> {
+    pub J: F,
+    pub a: F,
+    pub b: F,
     space: PhantomData<S>,
 }
 
-impl<S: JabSpace> Jab<S> {
-    pub const fn new_const(J: f32, a: f32, b: f32) -> Jab<S> {
+impl<S: JabSpace, F: Float> Jab<S, F> {
+    pub const fn new_const(J: F, a: F, b: F) -> Jab<S, F> {
         Jab {
             J,
             a,
             b,
-            space: PhantomData
+            space: PhantomData,
         }
     }
+
+    pub fn from_srgb(rgb: impl Into<sRGB>) -> Jab<S, F> {
+        Jab::from(&JCh::from_srgb(rgb))
+    }
 }
 
-impl<S: JabSpace> From<&JCh> for Jab<S> {
-    fn from(cam02: &JCh) -> Jab<S> {
-        let j_prime = ((1.0 + 100.0 * S::c1) * cam02.J) / (1.0 + S::c1 * cam02.J) / S::k_l;
+impl<S: JabSpace, F: Float> From<&JCh<F>> for Jab<S, F> {
+    fn from(cam02: &JCh<F>) -> Jab<S, F> {
+        let c = F::from_f64;
+        let (k_l, c1, c2) = (c(S::k_l_f64), c(S::c1_f64), c(S::c2_f64));
 
-        let m_prime = (1.0 / S::c2) * (1.0 + S::c2 * cam02.M).ln();
+        let j_prime = ((c(1.0) + c(100.0) * c1) * cam02.J) / (c(1.0) + c1 * cam02.J) / k_l;
+
+        let radius = match S::CORRELATE {
+            Correlate::Chroma => cam02.C,
+            Correlate::Colorfulness => cam02.M,
+            Correlate::Saturation => cam02.s,
+        };
+        let m_prime = if S::c2 == 0.0 {
+            radius
+        } else {
+            (c(1.0) / c2) * (c(1.0) + c2 * radius).ln()
+        };
 
         Jab {
             J: j_prime,
-            a: m_prime * ((PI / 180.0) * cam02.h).cos(),
-            b: m_prime * ((PI / 180.0) * cam02.h).sin(),
+            a: m_prime * ((F::PI / c(180.0)) * cam02.h).cos(),
+            b: m_prime * ((F::PI / c(180.0)) * cam02.h).sin(),
             space: PhantomData,
         }
     }
 }
 
+/// Inverts the Jab transformation, going back through lightness, the space's correlate, and hue.
+impl<S: JabSpace, F: Float> From<&Jab<S, F>> for JCh<F> {
+    fn from(jab: &Jab<S, F>) -> JCh<F> {
+        let vc = F::VIEWING_CONDITIONS;
+        let c = F::from_f64;
+        let (k_l, c1, c2) = (c(S::k_l_f64), c(S::c1_f64), c(S::c2_f64));
+
+        let J = (jab.J * k_l) / (c(1.0) + c(100.0) * c1 - c1 * jab.J * k_l);
+
+        let m_prime = (jab.a * jab.a + jab.b * jab.b).sqrt();
+        let radius = if S::c2 == 0.0 {
+            m_prime
+        } else {
+            ((c2 * m_prime).exp() - c(1.0)) / c2
+        };
+        let C = match S::CORRELATE {
+            Correlate::Chroma => radius,
+            Correlate::Colorfulness => radius / vc.fl.powf(c(0.25)),
+            Correlate::Saturation => {
+                // s = 100 sqrt(M / Q), and Q only depends on J
+                let Q = JCh::new(J, F::default(), F::default()).Q;
+                (radius / c(100.0)).powi(2) * Q / vc.fl.powf(c(0.25))
+            }
+        };
+
+        let h = wrap_hue((c(180.0) / F::PI) * jab.b.atan2(jab.a));
+
+        JCh::new(J, C, h)
+    }
+}
+
 impl<T: Into<sRGB>, S: JabSpace> From<T> for Jab<S> {
     fn from(rgb: T) -> Jab<S> {
-        Jab::<S>::from(&JCh::from(&LMS::from(&XYZ::from(&LinearRGB::from(
-            &rgb.into(),
-        )))))
+        Jab::from_srgb(rgb)
     }
 }
 
-impl<S: JabSpace> From<[f32; 3]> for Jab<S> {
-    fn from(jab: [f32; 3]) -> Jab<S> {
+impl<S: JabSpace, F: Float> From<[F; 3]> for Jab<S, F> {
+    fn from(jab: [F; 3]) -> Jab<S, F> {
         Jab {
             J: jab[0],
             a: jab[1],
@@ -346,8 +672,8 @@ impl<S: JabSpace> From<[f32; 3]> for Jab<S> {
     }
 }
 
-impl<S: JabSpace> From<(f32, f32, f32)> for Jab<S> {
-    fn from(jab: (f32, f32, f32)) -> Jab<S> {
+impl<S: JabSpace, F: Float> From<(F, F, F)> for Jab<S, F> {
+    fn from(jab: (F, F, F)) -> Jab<S, F> {
         Jab {
             J: jab.0,
             a: jab.1,
@@ -357,19 +683,21 @@ impl<S: JabSpace> From<(f32, f32, f32)> for Jab<S> {
     }
 }
 
-impl<S: JabSpace> Jab<S> {
-    pub fn squared_difference(&self, other: &Jab<S>) -> f32 {
+impl<S: JabSpace, F: Float> Jab<S, F> {
+    pub fn squared_difference(&self, other: &Jab<S, F>) -> F {
         let diff_j = (self.J - other.J).abs();
         let diff_a = (self.a - other.a).abs();
         let diff_b = (self.b - other.b).abs();
 
-        (diff_j / S::k_l).powi(2) + diff_a.powi(2) + diff_b.powi(2)
+        (diff_j / F::from_f64(S::k_l_f64)).powi(2) + diff_a.powi(2) + diff_b.powi(2)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{consts::UCS, JCh, Jab};
+    use crate::error::{Error, Stage};
+    use crate::{consts::UCS, JCh, Jab, LinearRGB, LMS, XYZ};
+    use proptest::prelude::*;
 
     macro_rules! float_eq {
         ($lhs:expr, $rhs:expr) => {
@@ -412,4 +740,359 @@ mod tests {
         float_eq!(blue.a, "-8.38");
         float_eq!(blue.b, "-39.16");
     }
+
+    #[test]
+    #[cfg(any(feature = "std", feature = "libm"))]
+    fn f64_channels() {
+        float_eq!(JCh::<f64>::from_srgb([100, 100, 100]).J, "32.16");
+        float_eq!(JCh::<f64>::from_srgb([255, 255, 255]).J, "100.00");
+
+        let red = JCh::<f64>::from_srgb([255, 0, 0]);
+        float_eq!(red.J, "46.93");
+        float_eq!(red.C, "111.30");
+        float_eq!(red.h, "32.15");
+
+        let blue = Jab::<UCS, f64>::from_srgb([0, 0, 255]);
+        float_eq!(blue.J, "31.22");
+        float_eq!(blue.a, "-8.38");
+        float_eq!(blue.b, "-39.16");
+    }
+
+    #[test]
+    fn inverse_round_trip() {
+        for r in (0..=255u8).step_by(15) {
+            for g in (0..=255u8).step_by(15) {
+                for b in (0..=255u8).step_by(15) {
+                    let jch = JCh::from([r, g, b]);
+                    let rgb = LinearRGB::from(&XYZ::from(&LMS::from(&jch)))
+                        .to_srgb()
+                        .unwrap();
+                    for (back, original) in [(rgb.r, r), (rgb.g, g), (rgb.b, b)] {
+                        assert!(back.abs_diff(original) <= 1, "{:?}", (r, g, b));
+                    }
+
+                    let jab = Jab::<UCS>::from([r, g, b]);
+                    let back = JCh::from(&jab);
+                    assert!((back.J - jch.J).abs() < 0.01);
+                    assert!((back.C - jch.C).abs() < 0.01);
+                    if jch.C > 1.0 {
+                        assert!((back.h - jch.h).abs() < 0.01);
+                    }
+
+                    #[cfg(any(feature = "std", feature = "libm"))]
+                    {
+                        let jch = JCh::<f64>::from_srgb([r, g, b]);
+                        let rgb = LinearRGB::from(&XYZ::from(&LMS::from(&jch)))
+                            .to_srgb()
+                            .unwrap();
+                        assert_eq!((rgb.r, rgb.g, rgb.b), (r, g, b));
+                    }
+                }
+            }
+        }
+    }
+
+    // the approximate_math feature adds at most ΔE 0.0004 on top of the error of the f32 pipeline,
+    // which the fastmath module checks over the whole cube.
+    #[test]
+    #[cfg(any(feature = "std", feature = "libm"))]
+    fn srgb_cube_delta_e() {
+        // every third code value along each axis, which includes both 0 and 255
+        let mut max_delta_e = 0.0f64;
+        for r in (0..=255u8).step_by(3) {
+            for g in (0..=255u8).step_by(3) {
+                for b in (0..=255u8).step_by(3) {
+                    let jab = Jab::<UCS>::from([r, g, b]);
+                    let reference = Jab::<UCS, f64>::from_srgb([r, g, b]);
+                    let delta_e = ((jab.J as f64 - reference.J).powi(2)
+                        + (jab.a as f64 - reference.a).powi(2)
+                        + (jab.b as f64 - reference.b).powi(2))
+                    .sqrt();
+                    max_delta_e = max_delta_e.max(delta_e);
+                }
+            }
+        }
+
+        assert!(
+            max_delta_e < 0.01,
+            "max ΔE against the f64 pipeline is {}",
+            max_delta_e
+        );
+    }
+
+    #[test]
+    fn imaginary_colors() {
+        // a negative HPE response, from an imaginary color, used to give NaN
+        for xyz in [
+            XYZ::<f32> {
+                x: 0.0,
+                y: 50.0,
+                z: 0.0,
+            },
+            XYZ {
+                x: 60.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            XYZ {
+                x: 1e6,
+                y: 1e6,
+                z: 1e6,
+            },
+        ] {
+            let jch = JCh::try_from_xyz(&xyz).unwrap();
+            let back = XYZ::from(&LMS::from(&jch));
+            for (back, original) in [(back.x, xyz.x), (back.y, xyz.y), (back.z, xyz.z)] {
+                assert!(
+                    (back - original).abs() <= 1e-3 * xyz.y.max(1.0),
+                    "{:?} came back as {:?}",
+                    xyz,
+                    back
+                );
+            }
+        }
+
+        #[cfg(any(feature = "std", feature = "libm"))]
+        {
+            let primary = XYZ::<f64> {
+                x: 0.0,
+                y: 50.0,
+                z: 0.0,
+            };
+            let jch = JCh::try_from_xyz(&primary).unwrap();
+            let back = XYZ::from(&LMS::from(&jch));
+            assert!((back.y - 50.0).abs() < 1e-9 && back.x.abs() < 1e-9 && back.z.abs() < 1e-9);
+        }
+
+        // the achromatic response of pure Z is negative, so it has no lightness
+        let blue = XYZ::<f32> {
+            x: 0.0,
+            y: 0.0,
+            z: 80.0,
+        };
+        assert_eq!(
+            JCh::try_from_xyz(&blue).unwrap_err(),
+            Error::NonFinite {
+                stage: Stage::JCh,
+                parameter: "J"
+            }
+        );
+        // and the infallible conversion gives it a NaN lightness, as documented on it
+        assert!(JCh::from(&LMS::from(&blue)).J.is_nan());
+    }
+
+    #[test]
+    fn non_finite_input() {
+        let nan = XYZ {
+            x: 20.0,
+            y: f32::NAN,
+            z: 20.0,
+        };
+        let error = JCh::try_from_xyz(&nan).unwrap_err();
+        assert_eq!(
+            error,
+            Error::NonFinite {
+                stage: Stage::XYZ,
+                parameter: "y"
+            }
+        );
+        assert_eq!(error.to_string(), "XYZ y isn't finite");
+
+        let infinite = LMS {
+            l: 20.0,
+            m: 20.0,
+            s: f32::INFINITY,
+        };
+        assert_eq!(
+            JCh::try_from_lms(&infinite).unwrap_err(),
+            Error::NonFinite {
+                stage: Stage::LMS,
+                parameter: "s"
+            }
+        );
+
+        let black = JCh::try_from_xyz(&XYZ::from([0, 0, 0])).unwrap();
+        assert!(
+            black.J < 1e-3 && black.C < 1e-3 && black.s < 1.0,
+            "{:?}",
+            black
+        );
+    }
+
+    #[test]
+    fn validated_conversions() {
+        use crate::consts::ViewingConditions;
+
+        for rgb in [
+            [0, 0, 0],
+            [255, 255, 255],
+            [255, 0, 0],
+            [0, 0, 255],
+            [12, 200, 90],
+        ] {
+            let xyz = XYZ::from(rgb);
+            let jch = JCh::try_from(&xyz).unwrap();
+            let back = XYZ::try_from(&jch).unwrap();
+            assert!((back.y - xyz.y).abs() < 0.01, "{:?}", rgb);
+
+            let jab = Jab::<UCS>::try_from(&xyz).unwrap();
+            let back = XYZ::try_from(&jab).unwrap();
+            assert!((back.y - xyz.y).abs() < 0.01, "{:?}", rgb);
+        }
+
+        let negative = XYZ::<f32> {
+            x: -1.0,
+            y: 20.0,
+            z: 20.0,
+        };
+        let error = JCh::try_from(&negative).unwrap_err();
+        assert_eq!(
+            error,
+            Error::OutOfRange {
+                stage: Stage::XYZ,
+                parameter: "x"
+            }
+        );
+        assert_eq!(error.to_string(), "XYZ x is out of range");
+        // the unvalidated constructor takes imaginary colors
+        assert!(JCh::try_from_xyz(&negative).is_ok());
+
+        assert_eq!(
+            XYZ::try_from(&JCh::<f32>::new(120.0, 20.0, 40.0)).unwrap_err(),
+            Error::OutOfRange {
+                stage: Stage::JCh,
+                parameter: "J"
+            }
+        );
+        assert_eq!(
+            XYZ::try_from(&JCh::<f32>::new(50.0, -1.0, 40.0)).unwrap_err(),
+            Error::OutOfRange {
+                stage: Stage::JCh,
+                parameter: "C"
+            }
+        );
+        assert_eq!(
+            XYZ::try_from(&Jab::<UCS>::new_const(50.0, f32::NAN, 0.0)).unwrap_err(),
+            Error::NonFinite {
+                stage: Stage::Jab,
+                parameter: "a"
+            }
+        );
+
+        assert_eq!(ViewingConditions::<f32>::DEFAULT.validate(), Ok(()));
+        let dark = ViewingConditions {
+            c: 0.4,
+            ..ViewingConditions::<f32>::DEFAULT
+        };
+        assert_eq!(
+            dark.validate(),
+            Err(Error::OutOfRange {
+                stage: Stage::ViewingConditions,
+                parameter: "c"
+            })
+        );
+        let unlit = ViewingConditions {
+            la: 0.0,
+            ..ViewingConditions::<f32>::DEFAULT
+        };
+        assert_eq!(
+            unlit.validate().unwrap_err().to_string(),
+            "viewing conditions la is out of range"
+        );
+    }
+
+    #[test]
+    fn correlate_variants() {
+        use crate::appearance::CamAppearance;
+        use crate::{CartesianC, CartesianM, CartesianS, Chroma, JabSpace, Saturation, LCD};
+
+        fn round_trip<S: JabSpace>(jch: &JCh) {
+            let back = JCh::from(&Jab::<S>::from(jch));
+            assert!((back.J - jch.J).abs() < 1e-3, "{}", S::NAME);
+            assert!((back.C - jch.C).abs() < 1e-3, "{}", S::NAME);
+            assert!((back.h - jch.h).abs() < 1e-3, "{}", S::NAME);
+        }
+
+        let orange = CamAppearance::from([255, 128, 0]);
+        let jch = JCh::from(&orange);
+
+        let plain = Jab::<CartesianC>::from(&jch);
+        assert_eq!([plain.J, plain.a, plain.b], orange.jab());
+        let plain = Jab::<CartesianS>::from(&jch);
+        assert_eq!([plain.J, plain.a, plain.b], orange.jsh());
+        let plain = Jab::<CartesianM>::from(&jch);
+        assert!(((plain.a.powi(2) + plain.b.powi(2)).sqrt() - jch.M).abs() < 1e-3);
+
+        // the compressed variants keep the space's lightness, and compress their own correlate
+        let (ucs, chroma) = (Jab::<UCS>::from(&jch), Jab::<Chroma<UCS>>::from(&jch));
+        assert_eq!(ucs.J, chroma.J);
+        let radius = (chroma.a.powi(2) + chroma.b.powi(2)).sqrt();
+        assert!((radius - (1.0 + UCS::c2 * jch.C).ln() / UCS::c2).abs() < 1e-3);
+        assert!(radius > (ucs.a.powi(2) + ucs.b.powi(2)).sqrt());
+
+        for rgb in [[255, 128, 0], [12, 200, 90], [30, 30, 200]] {
+            let jch = JCh::from(rgb);
+            round_trip::<CartesianC>(&jch);
+            round_trip::<CartesianM>(&jch);
+            round_trip::<CartesianS>(&jch);
+            round_trip::<Chroma<UCS>>(&jch);
+            round_trip::<Saturation<UCS>>(&jch);
+            round_trip::<Chroma<LCD>>(&jch);
+            round_trip::<Saturation<LCD>>(&jch);
+        }
+    }
+
+    fn grey(level: u8) -> JCh {
+        JCh::from([level, level, level])
+    }
+
+    proptest! {
+        #[test]
+        fn hue_ranges(rgb: [u8; 3]) {
+            let jch = JCh::from(rgb);
+            prop_assert!((0.0..360.0).contains(&jch.h), "h is {}", jch.h);
+            prop_assert!((0.0..400.0).contains(&jch.H), "H is {}", jch.H);
+        }
+
+        #[test]
+        fn hue_ranges_from_jab(a in -100.0f32..100.0, b in prop_oneof![-100.0f32..100.0, -1e-4f32..0.0]) {
+            let jch = JCh::from(&Jab::<UCS>::new_const(50.0, a, b));
+            prop_assert!((0.0..360.0).contains(&jch.h), "h is {}", jch.h);
+            prop_assert!((0.0..400.0).contains(&jch.H), "H is {}", jch.H);
+        }
+
+        #[test]
+        fn hue_quadrature_range(h in prop_oneof![0.0f32..360.0, 20.1399f32..20.14]) {
+            let H = JCh::new(50.0, 20.0, h).H;
+            prop_assert!((0.0..400.0).contains(&H), "H of {} is {}", h, H);
+        }
+
+        #[test]
+        fn lightness_increases_with_grey_level(a: u8, b: u8) {
+            prop_assume!(a < b);
+            prop_assert!(grey(a).J < grey(b).J);
+        }
+
+        // the crate's degree of adaptation D is 0.83, not 1, so greys keep a little of the chroma of the white
+        // point: C 3.168 for white itself, and less for every darker grey. They all share its hue.
+        #[test]
+        fn greys_are_nearly_achromatic(level: u8) {
+            let (jch, white) = (grey(level), grey(255));
+            prop_assert!(jch.C < 3.17, "C is {}", jch.C);
+            prop_assert!(jch.C <= white.C + 1e-4, "C is {}", jch.C);
+            if level > 0 {
+                prop_assert!((jch.h - white.h).abs() < 0.01, "h is {}", jch.h);
+            }
+        }
+
+        #[test]
+        fn squared_difference_is_symmetric(a: [u8; 3], b: [u8; 3]) {
+            let (a, b) = (Jab::<UCS>::from(a), Jab::<UCS>::from(b));
+            prop_assert_eq!(a.squared_difference(&b), b.squared_difference(&a));
+            prop_assert_eq!(a.squared_difference(&a), 0.0);
+            if a.J != b.J || a.a != b.a || a.b != b.b {
+                prop_assert!(a.squared_difference(&b) > 0.0);
+            }
+        }
+    }
 }
@@ -0,0 +1,291 @@
+//! Categorical palettes whose colors are as far apart as possible.
+//!
+//! [`distinct`] picks sRGB colors maximising the smallest ΔE in CAM02-UCS between any two of them,
+//! within the [`Constraints`] given:
+//! ```
+//! use kasi_kule::palette::{distinct, min_distance, Constraints};
+//!
+//! let constraints = Constraints {
+//!     lightness: (40.0, 80.0),
+//!     min_chroma: 20.0,
+//!     ..Constraints::default()
+//! };
+//! let palette = distinct(8, &constraints);
+//! assert_eq!(palette.len(), 8);
+//! assert!(min_distance(&palette) > 15.0);
+//!
+//! // the same seed always gives the same palette
+//! assert_eq!(palette, distinct(8, &constraints));
+//! ```
+//!
+//! Colors are first picked greedily, each as far as possible from the ones before it,
+//! then moved around one at a time for as long as that pushes the closest pair apart.
+use alloc::vec::Vec;
+
+use crate::utils::wrap_hue;
+use crate::{sRGB, Float, JCh, Jab, UCS};
+
+/// What the colors of a palette have to satisfy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraints {
+    /// The range of lightness J the colors fall in.
+    pub lightness: (f32, f32),
+    /// The least chroma C of any color, to keep them away from grey.
+    pub min_chroma: f32,
+    /// Colors that have to be in the palette, like brand colors. They come first in the palette, and aren't checked
+    /// against the other constraints.
+    pub fixed: Vec<sRGB>,
+    /// Ranges of hue angle h to stay out of, in degrees, going counterclockwise from the first angle to the second.
+    pub excluded_hues: Vec<(f32, f32)>,
+    /// The seed of the random search. Palettes are deterministic for a given seed.
+    pub seed: u64,
+}
+
+impl Default for Constraints {
+    /// Any sRGB color, with seed 0.
+    fn default() -> Constraints {
+        Constraints {
+            lightness: (0.0, 100.0),
+            min_chroma: 0.0,
+            fixed: Vec::new(),
+            excluded_hues: Vec::new(),
+            seed: 0,
+        }
+    }
+}
+
+impl Constraints {
+    /// Whether a color satisfies the lightness, chroma and hue constraints.
+    pub fn allows(&self, color: &JCh) -> bool {
+        let (low, high) = self.lightness;
+        let excluded = self.excluded_hues.iter().any(|&(from, to)| {
+            let (from, to, h) = (wrap_hue(from), wrap_hue(to), wrap_hue(color.h));
+            if from <= to {
+                h >= from && h <= to
+            } else {
+                h >= from || h <= to
+            }
+        });
+
+        color.J >= low && color.J <= high && color.C >= self.min_chroma && !excluded
+    }
+}
+
+// code values between the colors of the initial candidate grid, which has 18 levels per channel
+const GRID_STEP: usize = 15;
+
+// rounds of moving colors around, and how far a color moves (in code values) when it's nudged
+const ROUNDS: usize = 400;
+const NUDGE: u32 = 24;
+
+/// SplitMix64, which is all the randomness the search needs.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Candidate {
+    rgb: sRGB,
+    jab: Jab<UCS>,
+}
+
+impl Candidate {
+    fn new(rgb: sRGB) -> Candidate {
+        Candidate {
+            rgb,
+            jab: Jab::from_srgb(rgb),
+        }
+    }
+
+    fn distance(&self, other: &Candidate) -> f32 {
+        Float::sqrt(self.jab.squared_difference(&other.jab))
+    }
+}
+
+/// The ΔE in CAM02-UCS from `palette[i]` to the closest other color.
+fn nearest(palette: &[Candidate], i: usize, color: &Candidate) -> f32 {
+    palette
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| *j != i)
+        .map(|(_, other)| color.distance(other))
+        .fold(f32::INFINITY, f32::min)
+}
+
+/// The smallest ΔE in CAM02-UCS between any two colors, or infinity for fewer than two colors.
+pub fn min_distance(colors: &[sRGB]) -> f32 {
+    let colors: Vec<Candidate> = colors.iter().map(|rgb| Candidate::new(*rgb)).collect();
+    (0..colors.len())
+        .map(|i| nearest(&colors, i, &colors[i]))
+        .fold(f32::INFINITY, f32::min)
+}
+
+/// Picks `n` colors that are as distinct as possible, starting with the fixed colors of the constraints.
+///
+/// Returns fewer colors if the constraints rule out all but a few sRGB colors,
+/// and only the first `n` fixed colors if there are more than that.
+pub fn distinct(n: usize, constraints: &Constraints) -> Vec<sRGB> {
+    let mut random = Random(constraints.seed);
+    let allowed = |rgb: sRGB| constraints.allows(&JCh::from_srgb(rgb));
+
+    let levels = || (0..=255).step_by(GRID_STEP).map(|level| level as u8);
+    let candidates: Vec<Candidate> = levels()
+        .flat_map(|r| levels().flat_map(move |g| levels().map(move |b| sRGB { r, g, b })))
+        .filter(|rgb| allowed(*rgb))
+        .map(Candidate::new)
+        .collect();
+
+    let fixed = constraints.fixed.len().min(n);
+    let mut palette: Vec<Candidate> = constraints.fixed[..fixed]
+        .iter()
+        .map(|rgb| Candidate::new(*rgb))
+        .collect();
+    if candidates.is_empty() {
+        return palette.iter().map(|color| color.rgb).collect();
+    }
+
+    // greedy farthest point picking, from a random first color when nothing is fixed
+    if palette.is_empty() && n > 0 {
+        palette.push(candidates[random.below(candidates.len())]);
+    }
+    while palette.len() < n {
+        let mut best = (0.0, None);
+        for candidate in &candidates {
+            let distance = nearest(&palette, usize::MAX, candidate);
+            if distance > best.0 {
+                best = (distance, Some(*candidate));
+            }
+        }
+        match best.1 {
+            Some(candidate) => palette.push(candidate),
+            // every candidate is already in the palette
+            None => break,
+        }
+    }
+
+    // move a color of the closest pair at a time, either to a random candidate or a nudged copy of itself,
+    // whenever that takes it further from its nearest neighbour
+    let nudge = |random: &mut Random, channel: u8| {
+        let offset = random.below(2 * NUDGE as usize + 1) as i32 - NUDGE as i32;
+        (channel as i32 + offset).clamp(0, 255) as u8
+    };
+    for _ in 0..ROUNDS {
+        let distances: Vec<f32> = (0..palette.len())
+            .map(|i| nearest(&palette, i, &palette[i]))
+            .collect();
+        let Some(closest) =
+            (fixed..palette.len()).min_by(|&a, &b| distances[a].total_cmp(&distances[b]))
+        else {
+            break;
+        };
+
+        let current = palette[closest];
+        let moved = if random.below(2) == 0 {
+            candidates[random.below(candidates.len())]
+        } else {
+            let rgb = sRGB {
+                r: nudge(&mut random, current.rgb.r),
+                g: nudge(&mut random, current.rgb.g),
+                b: nudge(&mut random, current.rgb.b),
+            };
+            if !allowed(rgb) {
+                continue;
+            }
+            Candidate::new(rgb)
+        };
+
+        if nearest(&palette, closest, &moved) > distances[closest] {
+            palette[closest] = moved;
+        }
+    }
+
+    palette.iter().map(|color| color.rgb).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn far_apart() {
+        let palette = distinct(6, &Constraints::default());
+        assert_eq!(palette.len(), 6);
+        // six colors spread over the whole gamut sit far apart
+        assert!(min_distance(&palette) > 40.0, "{}", min_distance(&palette));
+
+        let more = distinct(12, &Constraints::default());
+        assert!(min_distance(&more) < min_distance(&palette));
+        assert!(min_distance(&more) > 25.0);
+    }
+
+    #[test]
+    fn deterministic_seeding() {
+        let constraints = Constraints {
+            seed: 7,
+            ..Constraints::default()
+        };
+        let palette = distinct(5, &constraints);
+        assert_eq!(palette, distinct(5, &constraints));
+
+        let other = distinct(
+            5,
+            &Constraints {
+                seed: 8,
+                ..Constraints::default()
+            },
+        );
+        assert_ne!(palette, other);
+    }
+
+    #[test]
+    fn constraints_hold() {
+        let constraints = Constraints {
+            lightness: (50.0, 75.0),
+            min_chroma: 30.0,
+            fixed: alloc::vec![sRGB::from([0, 0, 0])],
+            // no greens
+            excluded_hues: alloc::vec![(100.0, 180.0)],
+            seed: 3,
+        };
+        let palette = distinct(7, &constraints);
+        assert_eq!(palette.len(), 7);
+        assert_eq!(palette[0], sRGB::from([0, 0, 0]));
+
+        for rgb in &palette[1..] {
+            let color = JCh::from(*rgb);
+            assert!(constraints.allows(&color), "{:?}", color);
+            assert!(!(100.0..=180.0).contains(&color.h));
+        }
+
+        // excluded hues can wrap around 0
+        let reds = Constraints {
+            excluded_hues: alloc::vec![(330.0, 40.0)],
+            ..Constraints::default()
+        };
+        assert!(!reds.allows(&JCh::from([255, 0, 0])));
+        assert!(reds.allows(&JCh::from([0, 0, 255])));
+    }
+
+    #[test]
+    fn impossible_constraints() {
+        let constraints = Constraints {
+            min_chroma: 200.0,
+            fixed: alloc::vec![sRGB::from([255, 0, 0])],
+            ..Constraints::default()
+        };
+        assert_eq!(distinct(4, &constraints), [sRGB::from([255, 0, 0])]);
+        assert!(distinct(0, &Constraints::default()).is_empty());
+    }
+}
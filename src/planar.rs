@@ -0,0 +1,297 @@
+//! Whole-image conversion between [`image`] buffers and planar [`JCh`] or [`Jab`] buffers.
+//!
+//! Each correlate gets a plane of its own, so image processing can work on one channel at a time:
+//! ```
+//! use image::RgbImage;
+//! use kasi_kule::gamut::GamutMapping;
+//! use kasi_kule::planar::JabPlanes;
+//! use kasi_kule::UCS;
+//!
+//! let image = RgbImage::from_fn(4, 4, |x, y| image::Rgb([x as u8 * 60, y as u8 * 60, 128]));
+//! let mut planes = JabPlanes::<UCS>::from_rgb(&image);
+//!
+//! // darken the whole image by 10 units of J'
+//! for J in planes.J.iter_mut() {
+//!     *J -= 10.0;
+//! }
+//! let darker = planes.to_rgb(GamutMapping::Clip);
+//! assert!(darker.get_pixel(3, 3)[2] < image.get_pixel(3, 3)[2]);
+//! ```
+//!
+//! `u8` images are sRGB encoded. `f32` images hold the same encoding scaled to 0-1, and can go outside that range:
+//! those channels are linearized with the sRGB curve mirrored around 0, and aren't clipped on the way back.
+//! Alpha, where there is any, is carried through untouched from 0 to 1.
+use image::{ImageBuffer, Pixel, Rgb, Rgb32FImage, RgbImage, Rgba, RgbaImage};
+
+use crate::gamut::GamutMapping;
+use crate::utils::{delinearize, linearize};
+use crate::{sRGB, Float, JCh, Jab, JabSpace, LinearRGB, LMS, XYZ};
+
+/// An image as planes of lightness J, chroma C and hue angle h, in row-major order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JChPlanes<F: Float = f32> {
+    pub width: u32,
+    pub height: u32,
+    pub J: Vec<F>,
+    pub C: Vec<F>,
+    pub h: Vec<F>,
+    pub alpha: Option<Vec<F>>,
+}
+
+/// An image as planes of J', a' and b' in a [`JabSpace`], in row-major order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JabPlanes<S: JabSpace, F: Float = f32> {
+    pub width: u32,
+    pub height: u32,
+    pub J: Vec<F>,
+    pub a: Vec<F>,
+    pub b: Vec<F>,
+    pub alpha: Option<Vec<F>>,
+    space: core::marker::PhantomData<S>,
+}
+
+fn float_to_jch<F: Float>(rgb: [f32; 3]) -> JCh<F> {
+    let c = |channel: f32| linearize(F::from_f64(channel as f64));
+    let linear = LinearRGB {
+        r: c(rgb[0]),
+        g: c(rgb[1]),
+        b: c(rgb[2]),
+    };
+    JCh::from(&LMS::from(&XYZ::from(&linear)))
+}
+
+fn jch_to_float<F: Float>(color: &JCh<F>) -> [f32; 3] {
+    let linear = LinearRGB::from(&XYZ::from(&LMS::from(color)));
+    [linear.r, linear.g, linear.b].map(|channel| delinearize(channel).to_f64() as f32)
+}
+
+fn alpha_to_u8<F: Float>(alpha: F) -> u8 {
+    (alpha.to_f64() * 255.0 + 0.5).clamp(0.0, 255.0) as u8
+}
+
+impl<F: Float> JChPlanes<F> {
+    fn from_pixels<P: Pixel>(
+        image: &ImageBuffer<P, Vec<P::Subpixel>>,
+        color: impl Fn(&P) -> JCh<F>,
+        alpha: Option<fn(&P) -> F>,
+    ) -> JChPlanes<F> {
+        let count = image.pixels().len();
+        let mut planes = JChPlanes {
+            width: image.width(),
+            height: image.height(),
+            J: Vec::with_capacity(count),
+            C: Vec::with_capacity(count),
+            h: Vec::with_capacity(count),
+            alpha: alpha.map(|_| Vec::with_capacity(count)),
+        };
+
+        for pixel in image.pixels() {
+            let jch = color(pixel);
+            planes.J.push(jch.J);
+            planes.C.push(jch.C);
+            planes.h.push(jch.h);
+            if let (Some(plane), Some(alpha)) = (planes.alpha.as_mut(), alpha) {
+                plane.push(alpha(pixel));
+            }
+        }
+
+        planes
+    }
+
+    pub fn from_rgb(image: &RgbImage) -> JChPlanes<F> {
+        JChPlanes::from_pixels(image, |pixel| JCh::from_srgb(pixel.0), None)
+    }
+
+    pub fn from_rgba(image: &RgbaImage) -> JChPlanes<F> {
+        JChPlanes::from_pixels(
+            image,
+            |pixel| JCh::from_srgb([pixel[0], pixel[1], pixel[2]]),
+            Some(|pixel| F::from_f64(pixel[3] as f64 / 255.0)),
+        )
+    }
+
+    pub fn from_rgb32f(image: &Rgb32FImage) -> JChPlanes<F> {
+        JChPlanes::from_pixels(image, |pixel| float_to_jch(pixel.0), None)
+    }
+
+    /// The color of the pixel at position `i`.
+    pub fn get(&self, i: usize) -> JCh<F> {
+        JCh::new(self.J[i], self.C[i], self.h[i])
+    }
+
+    /// Converts back into sRGB, bringing colors outside of it back in with `mapping`.
+    pub fn to_rgb(&self, mapping: GamutMapping) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            let srgb = mapping.map(&self.get(self.position(x, y))).srgb;
+            Rgb([srgb.r, srgb.g, srgb.b])
+        })
+    }
+
+    /// Like [`JChPlanes::to_rgb`], with the alpha plane or full opacity.
+    pub fn to_rgba(&self, mapping: GamutMapping) -> RgbaImage {
+        RgbaImage::from_fn(self.width, self.height, |x, y| {
+            let i = self.position(x, y);
+            let sRGB { r, g, b } = mapping.map(&self.get(i)).srgb;
+            let alpha = self
+                .alpha
+                .as_ref()
+                .map_or(255, |alpha| alpha_to_u8(alpha[i]));
+            Rgba([r, g, b, alpha])
+        })
+    }
+
+    /// Converts back into sRGB encoded floats, without clipping.
+    pub fn to_rgb32f(&self) -> Rgb32FImage {
+        Rgb32FImage::from_fn(self.width, self.height, |x, y| {
+            Rgb(jch_to_float(&self.get(self.position(x, y))))
+        })
+    }
+
+    fn position(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+}
+
+impl<S: JabSpace, F: Float> JabPlanes<S, F> {
+    fn from_jch(planes: JChPlanes<F>) -> JabPlanes<S, F> {
+        let count = planes.J.len();
+        let (mut J, mut a, mut b) = (
+            Vec::with_capacity(count),
+            Vec::with_capacity(count),
+            Vec::with_capacity(count),
+        );
+        for i in 0..count {
+            let color = Jab::<S, F>::from(&planes.get(i));
+            J.push(color.J);
+            a.push(color.a);
+            b.push(color.b);
+        }
+
+        JabPlanes {
+            width: planes.width,
+            height: planes.height,
+            J,
+            a,
+            b,
+            alpha: planes.alpha,
+            space: core::marker::PhantomData,
+        }
+    }
+
+    fn to_jch(&self) -> JChPlanes<F> {
+        let count = self.J.len();
+        let mut planes = JChPlanes {
+            width: self.width,
+            height: self.height,
+            J: Vec::with_capacity(count),
+            C: Vec::with_capacity(count),
+            h: Vec::with_capacity(count),
+            alpha: self.alpha.clone(),
+        };
+
+        for i in 0..count {
+            let color = JCh::from(&self.get(i));
+            planes.J.push(color.J);
+            planes.C.push(color.C);
+            planes.h.push(color.h);
+        }
+
+        planes
+    }
+
+    pub fn from_rgb(image: &RgbImage) -> JabPlanes<S, F> {
+        JabPlanes::from_jch(JChPlanes::from_rgb(image))
+    }
+
+    pub fn from_rgba(image: &RgbaImage) -> JabPlanes<S, F> {
+        JabPlanes::from_jch(JChPlanes::from_rgba(image))
+    }
+
+    pub fn from_rgb32f(image: &Rgb32FImage) -> JabPlanes<S, F> {
+        JabPlanes::from_jch(JChPlanes::from_rgb32f(image))
+    }
+
+    /// The color of the pixel at position `i`.
+    pub fn get(&self, i: usize) -> Jab<S, F> {
+        Jab::new_const(self.J[i], self.a[i], self.b[i])
+    }
+
+    /// Converts back into sRGB, bringing colors outside of it back in with `mapping`.
+    pub fn to_rgb(&self, mapping: GamutMapping) -> RgbImage {
+        self.to_jch().to_rgb(mapping)
+    }
+
+    /// Like [`JabPlanes::to_rgb`], with the alpha plane or full opacity.
+    pub fn to_rgba(&self, mapping: GamutMapping) -> RgbaImage {
+        self.to_jch().to_rgba(mapping)
+    }
+
+    /// Converts back into sRGB encoded floats, without clipping.
+    pub fn to_rgb32f(&self) -> Rgb32FImage {
+        self.to_jch().to_rgb32f()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SCD, UCS};
+
+    fn gradient() -> RgbaImage {
+        RgbaImage::from_fn(16, 8, |x, y| {
+            Rgba([x as u8 * 16, y as u8 * 32, 255 - x as u8 * 8, y as u8 * 30])
+        })
+    }
+
+    #[test]
+    fn round_trips() {
+        let rgba = gradient();
+        let planes = JChPlanes::<f32>::from_rgba(&rgba);
+        assert_eq!((planes.width, planes.height, planes.J.len()), (16, 8, 128));
+        assert_eq!(planes.to_rgba(GamutMapping::Clip), rgba);
+
+        let jch = JCh::from([
+            rgba.get_pixel(5, 3)[0],
+            rgba.get_pixel(5, 3)[1],
+            rgba.get_pixel(5, 3)[2],
+        ]);
+        assert_eq!(planes.J[3 * 16 + 5], jch.J);
+
+        let rgb = image::DynamicImage::ImageRgba8(rgba).to_rgb8();
+        let jab = JabPlanes::<SCD>::from_rgb(&rgb);
+        assert!(jab.alpha.is_none());
+        assert_eq!(jab.to_rgb(GamutMapping::Css), rgb);
+        assert_eq!(jab.to_rgba(GamutMapping::Css).get_pixel(0, 0)[3], 255);
+    }
+
+    #[test]
+    fn float_images() {
+        // including channels outside of 0-1
+        let image = Rgb32FImage::from_fn(4, 4, |x, y| {
+            Rgb([x as f32 / 3.0, y as f32 / 3.0 - 0.1, 1.2])
+        });
+        let planes = JabPlanes::<UCS>::from_rgb32f(&image);
+        let back = planes.to_rgb32f();
+        for (original, result) in image.pixels().zip(back.pixels()) {
+            for channel in 0..3 {
+                assert!(
+                    (original[channel] - result[channel]).abs() < 1e-3,
+                    "{:?} {:?}",
+                    original,
+                    result
+                );
+            }
+        }
+
+        // a float image of u8 values converts like the u8 image
+        let rgb = RgbImage::from_fn(4, 4, |x, y| Rgb([x as u8 * 80, y as u8 * 80, 40]));
+        let float = image::DynamicImage::ImageRgb8(rgb.clone()).to_rgb32f();
+        let (from_u8, from_f32) = (
+            JChPlanes::<f32>::from_rgb(&rgb),
+            JChPlanes::<f32>::from_rgb32f(&float),
+        );
+        for (a, b) in from_u8.J.iter().zip(&from_f32.J) {
+            assert!((a - b).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,519 @@
+//! Color quantization: extracting a palette from pixels, and remapping pixels onto a palette.
+//!
+//! Colors are clustered in a [`Jab`] space, so the palette follows perceived differences rather than RGB distances:
+//! ```
+//! use kasi_kule::quantize::{quantize, remap, Method};
+//! use kasi_kule::{sRGB, Jab, UCS};
+//!
+//! // a 4x2 image of two reds and two blues
+//! let pixels: Vec<sRGB> = [[250, 0, 0], [240, 10, 0], [0, 0, 250], [0, 10, 240]]
+//!     .iter()
+//!     .cycle()
+//!     .take(8)
+//!     .map(|rgb| sRGB::from(*rgb))
+//!     .collect();
+//!
+//! let palette = quantize::<UCS>(&pixels, 2, Method::MedianCut);
+//! assert_eq!(palette.len(), 2);
+//! assert_eq!(palette[0].population, 4);
+//!
+//! let colors: Vec<Jab<UCS>> = palette.iter().map(|swatch| swatch.color).collect();
+//! let indices = remap(&pixels, 4, &colors, false);
+//! assert_eq!(indices[0], indices[1]);
+//! assert_ne!(indices[0], indices[2]);
+//! ```
+use alloc::vec::Vec;
+
+use crate::gamut::GamutMapping;
+use crate::index::JabTree;
+use crate::{sRGB, Float, JCh, Jab, JabSpace};
+
+/// How to cluster the colors of an image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Method {
+    /// Recursively splits the box of colors with the largest variance at its weighted median.
+    MedianCut,
+    /// Lloyd's algorithm, starting from the median cut palette, for at most this many iterations.
+    KMeans { iterations: usize },
+    /// Buckets colors into an octree over the bounding box of the image's colors,
+    /// then merges the least populated branches. Fast, but can return fewer colors than asked for.
+    Octree,
+}
+
+/// A palette color, with how many pixels it stands for.
+#[derive(Debug, Copy, Clone)]
+pub struct Swatch<S: JabSpace> {
+    /// The mean of the pixels in the cluster.
+    pub color: Jab<S>,
+    /// The closest sRGB color to the mean.
+    pub srgb: sRGB,
+    /// The number of pixels in the cluster.
+    pub population: usize,
+    /// The share of all pixels in the cluster, from 0 to 1.
+    pub weight: f32,
+}
+
+// levels of the octree, which splits the bounding box of the colors into 64 steps along each axis
+const OCTREE_DEPTH: u32 = 6;
+
+/// A distinct color of the image, with J divided by k_l so that distances match `Jab::squared_difference`.
+#[derive(Debug, Copy, Clone)]
+struct Entry {
+    point: [f32; 3],
+    count: usize,
+}
+
+fn coordinates<S: JabSpace>(color: &Jab<S>) -> [f32; 3] {
+    [color.J / S::k_l, color.a, color.b]
+}
+
+fn to_jab<S: JabSpace>(point: &[f32; 3]) -> Jab<S> {
+    Jab::new_const(point[0] * S::k_l, point[1], point[2])
+}
+
+fn squared_distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    (0..3)
+        .map(|axis| (a[axis] - b[axis]) * (a[axis] - b[axis]))
+        .sum()
+}
+
+/// Counts the distinct colors of an image, which saves converting every pixel.
+fn histogram<S: JabSpace>(pixels: &[sRGB]) -> Vec<Entry> {
+    let mut keys: Vec<u32> = pixels
+        .iter()
+        .map(|rgb| (rgb.r as u32) << 16 | (rgb.g as u32) << 8 | rgb.b as u32)
+        .collect();
+    keys.sort_unstable();
+
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut previous = None;
+    for key in keys {
+        if previous == Some(key) {
+            if let Some(entry) = entries.last_mut() {
+                entry.count += 1;
+            }
+            continue;
+        }
+        previous = Some(key);
+        let rgb = [(key >> 16) as u8, (key >> 8) as u8, key as u8];
+        entries.push(Entry {
+            point: coordinates(&Jab::<S>::from_srgb(rgb)),
+            count: 1,
+        });
+    }
+
+    entries
+}
+
+/// The population and weighted mean of a cluster of entries.
+fn mean<'a>(entries: impl IntoIterator<Item = &'a Entry>) -> ([f32; 3], usize) {
+    let (mut sum, mut count) = ([0.0f32; 3], 0);
+    for entry in entries {
+        for (total, coordinate) in sum.iter_mut().zip(entry.point) {
+            *total += coordinate * entry.count as f32;
+        }
+        count += entry.count;
+    }
+
+    (sum.map(|total| total / count.max(1) as f32), count)
+}
+
+/// The axis with the largest weighted variance, and the total weighted variance, of a cluster.
+fn variance(entries: &[Entry]) -> (usize, f32) {
+    let (center, _) = mean(entries);
+    let mut spread = [0.0f32; 3];
+    for entry in entries {
+        for axis in 0..3 {
+            let offset = entry.point[axis] - center[axis];
+            spread[axis] += offset * offset * entry.count as f32;
+        }
+    }
+
+    let axis = (0..3)
+        .max_by(|&a, &b| spread[a].total_cmp(&spread[b]))
+        .unwrap_or(0);
+    (axis, spread.iter().sum())
+}
+
+/// Splits the entries into at most `colors` boxes, as ranges of the reordered entries.
+fn median_cut(entries: &mut [Entry], colors: usize) -> Vec<(usize, usize)> {
+    let mut boxes = alloc::vec![(0, entries.len())];
+    while boxes.len() < colors {
+        let Some((i, axis)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, (start, end))| end - start > 1)
+            .map(|(i, &(start, end))| (i, variance(&entries[start..end])))
+            .max_by(|a, b| a.1 .1.total_cmp(&b.1 .1))
+            .map(|(i, (axis, _))| (i, axis))
+        else {
+            break;
+        };
+
+        let (start, end) = boxes[i];
+        let cluster = &mut entries[start..end];
+        cluster.sort_unstable_by(|a, b| a.point[axis].total_cmp(&b.point[axis]));
+
+        // the weighted median, keeping at least one entry on each side
+        let half = cluster.iter().map(|entry| entry.count).sum::<usize>() / 2;
+        let mut seen = 0;
+        let split = cluster
+            .iter()
+            .position(|entry| {
+                seen += entry.count;
+                seen > half
+            })
+            .unwrap_or(0)
+            .clamp(1, cluster.len() - 1);
+
+        boxes[i] = (start, start + split);
+        boxes.push((start + split, end));
+    }
+
+    boxes
+}
+
+fn k_means(entries: &[Entry], mut centers: Vec<[f32; 3]>, iterations: usize) -> Vec<[f32; 3]> {
+    let mut assignments = alloc::vec![usize::MAX; entries.len()];
+    for _ in 0..iterations {
+        let mut changed = false;
+        for (entry, assignment) in entries.iter().zip(&mut assignments) {
+            let nearest = (0..centers.len())
+                .min_by(|&a, &b| {
+                    squared_distance(&entry.point, &centers[a])
+                        .total_cmp(&squared_distance(&entry.point, &centers[b]))
+                })
+                .unwrap_or(0);
+            changed |= nearest != *assignment;
+            *assignment = nearest;
+        }
+        if !changed {
+            break;
+        }
+
+        for (i, center) in centers.iter_mut().enumerate() {
+            let members = entries
+                .iter()
+                .zip(&assignments)
+                .filter(|(_, assignment)| **assignment == i)
+                .map(|(entry, _)| entry);
+            let (mean, count) = mean(members);
+            // an emptied cluster keeps its center
+            if count > 0 {
+                *center = mean;
+            }
+        }
+    }
+
+    centers
+}
+
+/// Buckets the entries into an octree and merges its leaves until there are at most `colors`.
+/// Returns the entries grouped by leaf, as ranges of the reordered entries.
+fn octree(entries: &mut [Entry], colors: usize) -> Vec<(usize, usize)> {
+    let (mut low, mut high) = ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]);
+    for entry in entries.iter() {
+        for axis in 0..3 {
+            low[axis] = low[axis].min(entry.point[axis]);
+            high[axis] = high[axis].max(entry.point[axis]);
+        }
+    }
+
+    // the path down to each entry's cell at full depth, three bits (one per axis) per level
+    let cells = 1u32 << OCTREE_DEPTH;
+    let code = |point: &[f32; 3]| {
+        let steps = [0, 1, 2].map(|axis| {
+            let range = high[axis] - low[axis];
+            let t = if range > 0.0 {
+                (point[axis] - low[axis]) / range
+            } else {
+                0.0
+            };
+            ((t * cells as f32) as u32).min(cells - 1)
+        });
+
+        let mut code = 0u64;
+        for level in (0..OCTREE_DEPTH).rev() {
+            for step in steps {
+                code = code << 1 | ((step >> level) & 1) as u64;
+            }
+        }
+        code
+    };
+    entries.sort_unstable_by_key(|entry| code(&entry.point));
+    let codes: Vec<u64> = entries.iter().map(|entry| code(&entry.point)).collect();
+
+    // leaves as (code, level, start, end), going up a level at a time from full depth
+    let mut leaves: Vec<(u64, u32, usize, usize)> = Vec::new();
+    for (i, &code) in codes.iter().enumerate() {
+        match leaves.last_mut() {
+            Some(leaf) if leaf.0 == code => leaf.3 = i + 1,
+            _ => leaves.push((code, OCTREE_DEPTH, i, i + 1)),
+        }
+    }
+
+    let population = |start: usize, end: usize| -> usize {
+        entries[start..end].iter().map(|entry| entry.count).sum()
+    };
+    let mut level = OCTREE_DEPTH;
+    while leaves.len() > colors.max(1) && level > 0 {
+        // the parents of the leaves at this level, with their populations, least populated first
+        let mut parents: Vec<(u64, usize)> = Vec::new();
+        for leaf in leaves.iter().filter(|leaf| leaf.1 == level) {
+            let (parent, count) = (leaf.0 >> 3, population(leaf.2, leaf.3));
+            match parents.last_mut() {
+                Some(last) if last.0 == parent => last.1 += count,
+                _ => parents.push((parent, count)),
+            }
+        }
+        parents.sort_by_key(|parent| parent.1);
+
+        for (parent, _) in parents {
+            if leaves.len() <= colors.max(1) {
+                break;
+            }
+            // siblings are next to each other, since the leaves are sorted by code
+            let children: Vec<usize> = (0..leaves.len())
+                .filter(|&i| leaves[i].1 == level && leaves[i].0 >> 3 == parent)
+                .collect();
+            let (first, last) = (children[0], children[children.len() - 1]);
+            let merged = (parent, level - 1, leaves[first].2, leaves[last].3);
+            leaves.splice(first..=last, [merged]);
+        }
+        level -= 1;
+    }
+
+    leaves
+        .iter()
+        .map(|&(_, _, start, end)| (start, end))
+        .collect()
+}
+
+/// Reduces the pixels to a palette of at most `colors` colors, most populated first.
+pub fn quantize<S: JabSpace>(pixels: &[sRGB], colors: usize, method: Method) -> Vec<Swatch<S>> {
+    let mut entries = histogram::<S>(pixels);
+    if entries.is_empty() || colors == 0 {
+        return Vec::new();
+    }
+
+    let clusters: Vec<([f32; 3], usize)> = match method {
+        Method::MedianCut => median_cut(&mut entries, colors)
+            .into_iter()
+            .map(|(start, end)| mean(&entries[start..end]))
+            .collect(),
+        Method::Octree => octree(&mut entries, colors)
+            .into_iter()
+            .map(|(start, end)| mean(&entries[start..end]))
+            .collect(),
+        Method::KMeans { iterations } => {
+            let centers = median_cut(&mut entries, colors)
+                .into_iter()
+                .map(|(start, end)| mean(&entries[start..end]).0)
+                .collect();
+            let centers = k_means(&entries, centers, iterations);
+
+            let mut populations = alloc::vec![0; centers.len()];
+            for entry in &entries {
+                let nearest = (0..centers.len())
+                    .min_by(|&a, &b| {
+                        squared_distance(&entry.point, &centers[a])
+                            .total_cmp(&squared_distance(&entry.point, &centers[b]))
+                    })
+                    .unwrap_or(0);
+                populations[nearest] += entry.count;
+            }
+            centers
+                .into_iter()
+                .zip(populations)
+                .filter(|(_, population)| *population > 0)
+                .collect()
+        }
+    };
+
+    let mut swatches: Vec<Swatch<S>> = clusters
+        .into_iter()
+        .map(|(point, population)| {
+            let color = to_jab::<S>(&point);
+            let srgb = GamutMapping::Clip.map(&JCh::from(&color)).srgb;
+            Swatch {
+                color,
+                srgb,
+                population,
+                weight: population as f32 / pixels.len() as f32,
+            }
+        })
+        .collect();
+    swatches.sort_by_key(|swatch| core::cmp::Reverse(swatch.population));
+    swatches
+}
+
+/// Maps every pixel to the index of its closest palette color.
+///
+/// With `dither`, the difference between each pixel and its palette color is spread over the pixels after it
+/// with Floyd–Steinberg error diffusion, in the Jab space of the palette.
+///
+/// # Panics
+///
+/// Panics if the palette is empty while there are pixels, or if the pixels don't fill whole rows of `width`.
+pub fn remap<S: JabSpace>(
+    pixels: &[sRGB],
+    width: usize,
+    palette: &[Jab<S>],
+    dither: bool,
+) -> Vec<usize> {
+    if pixels.is_empty() {
+        return Vec::new();
+    }
+    assert!(!palette.is_empty(), "remapping onto an empty palette");
+    assert!(
+        width > 0 && pixels.len() % width == 0,
+        "pixels don't fill whole rows"
+    );
+
+    let tree = JabTree::new(palette);
+    let nearest = |point: &[f32; 3]| {
+        tree.nearest(&to_jab(point))
+            .map(|neighbour| neighbour.index)
+            .unwrap_or(0)
+    };
+    let points: Vec<[f32; 3]> = palette.iter().map(coordinates).collect();
+
+    let mut buffer: Vec<[f32; 3]> = pixels
+        .iter()
+        .map(|rgb| coordinates(&Jab::<S>::from_srgb(*rgb)))
+        .collect();
+    let height = pixels.len() / width;
+    let mut indices = Vec::with_capacity(pixels.len());
+    for y in 0..height {
+        for x in 0..width {
+            let current = buffer[y * width + x];
+            let index = nearest(&current);
+            indices.push(index);
+            if !dither {
+                continue;
+            }
+
+            let error = [0, 1, 2].map(|axis| current[axis] - points[index][axis]);
+            let mut spread = |dx: isize, dy: usize, share: f32| {
+                let (x, y) = (x as isize + dx, y + dy);
+                if x >= 0 && (x as usize) < width && y < height {
+                    let pixel = &mut buffer[y * width + x as usize];
+                    for axis in 0..3 {
+                        pixel[axis] += error[axis] * share;
+                    }
+                }
+            };
+            spread(1, 0, 7.0 / 16.0);
+            spread(-1, 1, 3.0 / 16.0);
+            spread(0, 1, 5.0 / 16.0);
+            spread(1, 1, 1.0 / 16.0);
+        }
+    }
+
+    indices
+}
+
+/// The mean ΔE between the pixels and the palette colors they're mapped to.
+pub fn mean_error<S: JabSpace>(pixels: &[sRGB], palette: &[Jab<S>], indices: &[usize]) -> f32 {
+    let total: f32 = pixels
+        .iter()
+        .zip(indices)
+        .map(|(rgb, &index)| {
+            Float::sqrt(Jab::<S>::from_srgb(*rgb).squared_difference(&palette[index]))
+        })
+        .sum();
+    total / pixels.len().max(1) as f32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SCD, UCS};
+
+    const METHODS: [Method; 3] = [
+        Method::MedianCut,
+        Method::KMeans { iterations: 20 },
+        Method::Octree,
+    ];
+
+    /// A 64x64 image with a horizontal hue gradient and a vertical lightness gradient.
+    fn image() -> Vec<sRGB> {
+        (0..64)
+            .flat_map(|y| {
+                (0..64).map(move |x| {
+                    let level = |offset: i32| ((x * 4 + offset) % 256) as u8 / 2 + y as u8 * 2;
+                    sRGB::from([level(0), level(85), level(170)])
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn populations() {
+        let pixels = image();
+        for method in METHODS {
+            let palette = quantize::<UCS>(&pixels, 16, method);
+            assert!(palette.len() <= 16 && palette.len() >= 8, "{:?}", method);
+
+            let total: usize = palette.iter().map(|swatch| swatch.population).sum();
+            assert_eq!(total, pixels.len());
+            let weights: f32 = palette.iter().map(|swatch| swatch.weight).sum();
+            assert!((weights - 1.0).abs() < 1e-4);
+            assert!(palette
+                .windows(2)
+                .all(|pair| pair[0].population >= pair[1].population));
+
+            let colors: Vec<Jab<UCS>> = palette.iter().map(|swatch| swatch.color).collect();
+            let indices = remap(&pixels, 64, &colors, false);
+            assert!(
+                mean_error(&pixels, &colors, &indices) < 12.0,
+                "{:?}",
+                method
+            );
+        }
+    }
+
+    #[test]
+    fn k_means_refines_median_cut() {
+        let pixels = image();
+        let error = |method| {
+            let colors: Vec<Jab<SCD>> = quantize::<SCD>(&pixels, 8, method)
+                .iter()
+                .map(|swatch| swatch.color)
+                .collect();
+            mean_error(&pixels, &colors, &remap(&pixels, 64, &colors, false))
+        };
+        assert!(error(Method::KMeans { iterations: 20 }) <= error(Method::MedianCut) + 1e-3);
+    }
+
+    #[test]
+    fn few_colors() {
+        let pixels = [sRGB::from([10, 200, 30]); 5];
+        for method in METHODS {
+            let palette = quantize::<UCS>(&pixels, 4, method);
+            assert_eq!(palette.len(), 1);
+            assert_eq!(palette[0].srgb, sRGB::from([10, 200, 30]));
+            assert_eq!(palette[0].population, 5);
+        }
+        assert!(quantize::<UCS>(&[], 4, Method::Octree).is_empty());
+        assert!(quantize::<UCS>(&pixels, 0, Method::MedianCut).is_empty());
+    }
+
+    #[test]
+    fn dithering() {
+        // mid grey, remapped onto black and white
+        let pixels = [sRGB::from([119, 119, 119]); 64];
+        let palette = [
+            Jab::<UCS>::from_srgb([0, 0, 0]),
+            Jab::<UCS>::from_srgb([255, 255, 255]),
+        ];
+
+        let flat = remap(&pixels, 8, &palette, false);
+        assert!(flat.windows(2).all(|pair| pair[0] == pair[1]));
+
+        // dithering mixes both, about evenly in perceptual terms, since mid grey sits halfway in J
+        let dithered = remap(&pixels, 8, &palette, true);
+        let white = dithered.iter().filter(|&&index| index == 1).count();
+        assert!(white > 16 && white < 48, "{}", white);
+    }
+}
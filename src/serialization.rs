@@ -0,0 +1,232 @@
+//! serde support for [`Jab`], which carries its space as a tag so colors from different spaces can't be mixed up.
+//! Every other color type derives its implementations.
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::{Float, Jab, JabSpace};
+
+const FIELDS: &[&str] = &["J", "a", "b", "space"];
+
+impl<S: JabSpace, F: Float + Serialize> Serialize for Jab<S, F> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut state = serializer.serialize_struct("Jab", 4)?;
+        state.serialize_field("J", &self.J)?;
+        state.serialize_field("a", &self.a)?;
+        state.serialize_field("b", &self.b)?;
+        state.serialize_field("space", S::NAME)?;
+        state.end()
+    }
+}
+
+/// The space tag of a serialized Jab, which only deserializes if it names `S`.
+struct SpaceTag<S: JabSpace>(PhantomData<S>);
+
+impl<'de, S: JabSpace> Deserialize<'de> for SpaceTag<S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TagVisitor<S>(PhantomData<S>);
+
+        impl<'de, S: JabSpace> Visitor<'de> for TagVisitor<S> {
+            type Value = SpaceTag<S>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "the Jab space name \"{}\"", S::NAME)
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                if v == S::NAME {
+                    Ok(SpaceTag(PhantomData))
+                } else {
+                    Err(E::invalid_value(de::Unexpected::Str(v), &self))
+                }
+            }
+        }
+
+        deserializer.deserialize_str(TagVisitor(PhantomData))
+    }
+}
+
+enum Field {
+    J,
+    A,
+    B,
+    Space,
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldVisitor;
+
+        impl<'de> Visitor<'de> for FieldVisitor {
+            type Value = Field;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("`J`, `a`, `b` or `space`")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Field, E> {
+                match v {
+                    "J" => Ok(Field::J),
+                    "a" => Ok(Field::A),
+                    "b" => Ok(Field::B),
+                    "space" => Ok(Field::Space),
+                    _ => Err(E::unknown_field(v, FIELDS)),
+                }
+            }
+        }
+
+        deserializer.deserialize_identifier(FieldVisitor)
+    }
+}
+
+struct JabVisitor<S, F>(PhantomData<(S, F)>);
+
+impl<'de, S: JabSpace, F: Float + Deserialize<'de>> Visitor<'de> for JabVisitor<S, F> {
+    type Value = Jab<S, F>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a Jab color in the {} space", S::NAME)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let J = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let a = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let b = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        seq.next_element::<SpaceTag<S>>()?
+            .ok_or_else(|| de::Error::invalid_length(3, &self))?;
+
+        Ok(Jab::new_const(J, a, b))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let (mut J, mut a, mut b, mut space) = (None, None, None, None);
+
+        while let Some(key) = map.next_key()? {
+            match key {
+                Field::J if J.is_some() => return Err(de::Error::duplicate_field("J")),
+                Field::A if a.is_some() => return Err(de::Error::duplicate_field("a")),
+                Field::B if b.is_some() => return Err(de::Error::duplicate_field("b")),
+                Field::Space if space.is_some() => return Err(de::Error::duplicate_field("space")),
+                Field::J => J = Some(map.next_value()?),
+                Field::A => a = Some(map.next_value()?),
+                Field::B => b = Some(map.next_value()?),
+                Field::Space => space = Some(map.next_value::<SpaceTag<S>>()?),
+            }
+        }
+
+        let J = J.ok_or_else(|| de::Error::missing_field("J"))?;
+        let a = a.ok_or_else(|| de::Error::missing_field("a"))?;
+        let b = b.ok_or_else(|| de::Error::missing_field("b"))?;
+        space.ok_or_else(|| de::Error::missing_field("space"))?;
+
+        Ok(Jab::new_const(J, a, b))
+    }
+}
+
+impl<'de, S: JabSpace, F: Float + Deserialize<'de>> Deserialize<'de> for Jab<S, F> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct("Jab", FIELDS, JabVisitor(PhantomData))
+    }
+}
+
+// the tests use f64, which needs std or libm
+#[cfg(all(test, any(feature = "std", feature = "libm")))]
+mod test {
+    use crate::{sRGB, JCh, Jab, LinearRGB, HPE, LCD, LMS, SCD, UCS, XYZ};
+
+    #[test]
+    fn jab_json_round_trip() {
+        let red = Jab::<UCS>::from([255, 0, 0]);
+        let json = serde_json::to_string(&red).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"J":{},"a":{},"b":{},"space":"UCS"}}"#,
+                red.J, red.a, red.b
+            )
+        );
+
+        let back: Jab<UCS> = serde_json::from_str(&json).unwrap();
+        assert_eq!((back.J, back.a, back.b), (red.J, red.a, red.b));
+    }
+
+    #[test]
+    fn jab_msgpack_round_trip() {
+        let blue = Jab::<SCD, f64>::from_srgb([0, 0, 255]);
+
+        // rmp-serde writes structs as arrays by default, and maps with `with_struct_map`
+        let compact = rmp_serde::to_vec(&blue).unwrap();
+        let named = rmp_serde::to_vec_named(&blue).unwrap();
+
+        for bytes in [compact, named] {
+            let back: Jab<SCD, f64> = rmp_serde::from_slice(&bytes).unwrap();
+            assert_eq!((back.J, back.a, back.b), (blue.J, blue.a, blue.b));
+        }
+    }
+
+    #[test]
+    fn jab_space_is_checked() {
+        let json = serde_json::to_string(&Jab::<UCS>::from([255, 0, 0])).unwrap();
+        let err = serde_json::from_str::<Jab<LCD>>(&json).unwrap_err();
+        assert!(err.to_string().contains("\"UCS\""), "{}", err);
+
+        let bytes = rmp_serde::to_vec(&Jab::<UCS>::from([255, 0, 0])).unwrap();
+        assert!(rmp_serde::from_slice::<Jab<SCD>>(&bytes).is_err());
+
+        assert!(serde_json::from_str::<Jab<UCS>>(r#"{"J":1.0,"a":2.0,"b":3.0}"#).is_err());
+    }
+
+    #[test]
+    fn unnamed_space() {
+        #[derive(Debug, Default, Copy, Clone)]
+        struct Custom;
+        impl crate::JabSpace for Custom {
+            const k_l: f32 = 1.0;
+            const c1: f32 = 0.007;
+            const c2: f32 = 0.02;
+        }
+
+        let json = serde_json::to_string(&Jab::<Custom>::new_const(50.0, 1.0, 2.0)).unwrap();
+        assert_eq!(json, r#"{"J":50.0,"a":1.0,"b":2.0,"space":"Jab"}"#);
+        let back: Jab<Custom> = serde_json::from_str(&json).unwrap();
+        assert_eq!((back.J, back.a, back.b), (50.0, 1.0, 2.0));
+    }
+
+    #[test]
+    fn derived_round_trips() {
+        let srgb = sRGB::from([12, 34, 56]);
+        let back: sRGB = serde_json::from_str(&serde_json::to_string(&srgb).unwrap()).unwrap();
+        assert_eq!((back.r, back.g, back.b), (12, 34, 56));
+
+        let lrgb = LinearRGB::from(srgb);
+        let back: LinearRGB = serde_json::from_str(&serde_json::to_string(&lrgb).unwrap()).unwrap();
+        assert_eq!((back.r, back.g, back.b), (lrgb.r, lrgb.g, lrgb.b));
+
+        let xyz = XYZ::<f64>::from_srgb(srgb);
+        let back: XYZ<f64> = rmp_serde::from_slice(&rmp_serde::to_vec(&xyz).unwrap()).unwrap();
+        assert_eq!((back.x, back.y, back.z), (xyz.x, xyz.y, xyz.z));
+
+        let lms = LMS::from(srgb);
+        let back: LMS = serde_json::from_str(&serde_json::to_string(&lms).unwrap()).unwrap();
+        assert_eq!((back.l, back.m, back.s), (lms.l, lms.m, lms.s));
+
+        let hpe = HPE::from(srgb);
+        let back: HPE = serde_json::from_str(&serde_json::to_string(&hpe).unwrap()).unwrap();
+        assert_eq!((back.lh, back.mh, back.sh), (hpe.lh, hpe.mh, hpe.sh));
+
+        let jch = JCh::from(srgb);
+        let back: JCh = rmp_serde::from_slice(&rmp_serde::to_vec_named(&jch).unwrap()).unwrap();
+        assert_eq!(
+            (back.J, back.C, back.H, back.h, back.Q, back.M, back.s),
+            (jch.J, jch.C, jch.H, jch.h, jch.Q, jch.M, jch.s)
+        );
+    }
+}
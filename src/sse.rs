@@ -3,29 +3,42 @@ use crate::{
     LinearRGB, LMS, XYZ,
 };
 
+/// Whether the SSE paths can be used: detected at runtime with `std`, and from the target features without it.
+#[inline(always)]
+pub fn sse_available() -> bool {
+    #[cfg(feature = "std")]
+    return is_x86_feature_detected!("sse");
+    #[cfg(not(feature = "std"))]
+    return cfg!(target_feature = "sse");
+}
+
+/// # Safety
+/// The CPU must support SSE.
 #[target_feature(enable = "sse")]
 pub unsafe fn sse_xyz(rgb: &LinearRGB) -> [f32; 4] {
     #[cfg(target_arch = "x86")]
-    use std::arch::x86::*;
+    use core::arch::x86::*;
     #[cfg(target_arch = "x86_64")]
-    use std::arch::x86_64::*;
+    use core::arch::x86_64::*;
 
     let r = _mm_mul_ps(_mm_setr_ps(0.4124, 0.2126, 0.0193, 0.0), _mm_set1_ps(rgb.r));
     let g = _mm_mul_ps(_mm_setr_ps(0.3576, 0.7152, 0.1192, 0.0), _mm_set1_ps(rgb.g));
     let b = _mm_mul_ps(_mm_setr_ps(0.1805, 0.0722, 0.9505, 0.0), _mm_set1_ps(rgb.b));
 
-    std::mem::transmute::<__m128, [f32; 4]>(_mm_mul_ps(
+    core::mem::transmute::<__m128, [f32; 4]>(_mm_mul_ps(
         _mm_add_ps(_mm_add_ps(r, g), b),
         _mm_set1_ps(100.0),
     ))
 }
 
+/// # Safety
+/// The CPU must support SSE.
 #[target_feature(enable = "sse")]
 pub unsafe fn sse_lms(xyz: &XYZ) -> [f32; 4] {
     #[cfg(target_arch = "x86")]
-    use std::arch::x86::*;
+    use core::arch::x86::*;
     #[cfg(target_arch = "x86_64")]
-    use std::arch::x86_64::*;
+    use core::arch::x86_64::*;
 
     let x = _mm_mul_ps(
         _mm_setr_ps(0.7328, -0.7036, 0.0030, 0.0),
@@ -37,40 +50,44 @@ pub unsafe fn sse_lms(xyz: &XYZ) -> [f32; 4] {
         _mm_set1_ps(xyz.z),
     );
 
-    std::mem::transmute::<__m128, [f32; 4]>(_mm_add_ps(_mm_add_ps(x, y), z))
+    core::mem::transmute::<__m128, [f32; 4]>(_mm_add_ps(_mm_add_ps(x, y), z))
 }
 
+/// # Safety
+/// The CPU must support SSE.
 #[target_feature(enable = "sse")]
 pub unsafe fn sse_hpe(lms: &LMS) -> [f32; 4] {
     #[cfg(target_arch = "x86")]
-    use std::arch::x86::*;
+    use core::arch::x86::*;
     #[cfg(target_arch = "x86_64")]
-    use std::arch::x86_64::*;
+    use core::arch::x86_64::*;
 
     let l = _mm_mul_ps(
         _mm_setr_ps(0.7409792, 0.2853532, -0.0096280, 0.0),
         _mm_set1_ps(lms.l),
     );
     let m = _mm_mul_ps(
-        _mm_setr_ps(0.2180250, 0.6242014, -0.0056980, 0.0),
+        _mm_setr_ps(0.218025, 0.6242014, -0.0056980, 0.0),
         _mm_set1_ps(lms.m),
     );
     let s = _mm_mul_ps(
-        _mm_setr_ps(0.0410058, 0.0904454, 1.0153260, 0.0),
+        _mm_setr_ps(0.0410058, 0.0904454, 1.015326, 0.0),
         _mm_set1_ps(lms.s),
     );
 
-    std::mem::transmute::<__m128, [f32; 4]>(_mm_add_ps(_mm_add_ps(l, m), s))
+    core::mem::transmute::<__m128, [f32; 4]>(_mm_add_ps(_mm_add_ps(l, m), s))
 }
 
+/// # Safety
+/// The CPU must support SSE.
 #[target_feature(enable = "sse")]
 pub unsafe fn sse_transform_cones(cones: [f32; 4]) -> [f32; 4] {
     #[cfg(target_arch = "x86")]
-    use std::arch::x86::*;
+    use core::arch::x86::*;
     #[cfg(target_arch = "x86_64")]
-    use std::arch::x86_64::*;
+    use core::arch::x86_64::*;
 
-    std::mem::transmute::<__m128, [f32; 4]>(_mm_mul_ps(
+    core::mem::transmute::<__m128, [f32; 4]>(_mm_mul_ps(
         _mm_loadu_ps(cones[..].as_ptr()),
         _mm_add_ps(
             _mm_div_ps(
@@ -86,9 +103,9 @@ pub unsafe fn sse_transform_cones(cones: [f32; 4]) -> [f32; 4] {
 // #[target_feature(enable = "sse,sse2")]
 // pub unsafe fn sse2_nonlinear_adaptation(cones: [f32; 4], fl: f32) -> [f32; 4] {
 //     #[cfg(target_arch = "x86")]
-//     use std::arch::x86::*;
+//     use core::arch::x86::*;
 //     #[cfg(target_arch = "x86_64")]
-//     use std::arch::x86_64::*;
+//     use core::arch::x86_64::*;
 
 //     use crate::simd_math::*;
 
@@ -112,62 +129,100 @@ pub unsafe fn sse_transform_cones(cones: [f32; 4]) -> [f32; 4] {
 //         _mm_set1_ps(0.1),
 //     );
 
-//     std::mem::transmute::<__m128, [f32; 4]>(res)
+//     core::mem::transmute::<__m128, [f32; 4]>(res)
 // }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{LinearRGB, HPE, LMS, XYZ};
-
-    #[test]
-    fn test_sse_xyz() {
-        let color = [fastrand::u8(..), fastrand::u8(..), fastrand::u8(..)];
-        let lrgb = LinearRGB::from(color);
-        let scalar = XYZ::from(color);
-        let sse_result = unsafe { sse_xyz(&lrgb) };
-
-        assert_eq!(scalar.x, sse_result[0], "X channel does not match");
-        assert_eq!(scalar.y, sse_result[1], "Y channel does not match");
-        assert_eq!(scalar.z, sse_result[2], "Z channel does not match");
-    }
+    use crate::utils::{linear_rgb_to_xyz_scalar, lms_to_hpe_scalar, xyz_to_lms_scalar};
+    use crate::{LinearRGB, LMS, XYZ};
+    use proptest::prelude::*;
 
-    #[test]
-    fn test_sse_lms() {
-        let color = [fastrand::u8(..), fastrand::u8(..), fastrand::u8(..)];
-        let xyz = XYZ::from(color);
-        let scalar = LMS::from(&xyz);
-
-        let sse_result = unsafe { sse_lms(&xyz) };
+    // the conversions themselves go through SSE whenever it's available, so the scalar side
+    // calls the scalar functions directly
 
-        assert_eq!(scalar.l, sse_result[0], "L channel does not match");
-        assert_eq!(scalar.m, sse_result[1], "M channel does not match");
-        assert_eq!(scalar.s, sse_result[2], "S channel does not match");
+    // the SSE paths multiply and add in the same order as the scalar ones, so they agree to the bit,
+    // for any color and not only those from sRGB
+    fn component() -> impl Strategy<Value = f32> {
+        -200.0f32..200.0
     }
 
-    #[test]
-    fn test_sse_hpe() {
-        let color = [fastrand::u8(..), fastrand::u8(..), fastrand::u8(..)];
-        let lms = LMS::from(color);
-        let scalar = HPE::from(&lms);
-
-        let sse_result = unsafe { sse_hpe(&lms) };
-
-        assert_eq!(scalar.lh, sse_result[0], "Lh channel does not match");
-        assert_eq!(scalar.mh, sse_result[1], "Mh channel does not match");
-        assert_eq!(scalar.sh, sse_result[2], "Sh channel does not match");
+    proptest! {
+        #[test]
+        fn test_sse_xyz(color: [u8; 3]) {
+            let lrgb = LinearRGB::from(color);
+            let scalar = linear_rgb_to_xyz_scalar(&lrgb);
+            let sse_result = unsafe { sse_xyz(&lrgb) };
+
+            prop_assert_eq!(scalar.x, sse_result[0], "X channel does not match");
+            prop_assert_eq!(scalar.y, sse_result[1], "Y channel does not match");
+            prop_assert_eq!(scalar.z, sse_result[2], "Z channel does not match");
+        }
+
+        #[test]
+        fn test_sse_lms(x in component(), y in component(), z in component()) {
+            let xyz = XYZ { x, y, z };
+            let scalar = xyz_to_lms_scalar(&xyz);
+            let sse_result = unsafe { sse_lms(&xyz) };
+
+            prop_assert_eq!(scalar.l, sse_result[0], "L channel does not match");
+            prop_assert_eq!(scalar.m, sse_result[1], "M channel does not match");
+            prop_assert_eq!(scalar.s, sse_result[2], "S channel does not match");
+        }
+
+        #[test]
+        fn test_sse_hpe(l in component(), m in component(), s in component()) {
+            let lms = LMS { l, m, s };
+            let scalar = lms_to_hpe_scalar(&lms);
+            let sse_result = unsafe { sse_hpe(&lms) };
+
+            prop_assert_eq!(scalar.lh, sse_result[0], "Lh channel does not match");
+            prop_assert_eq!(scalar.mh, sse_result[1], "Mh channel does not match");
+            prop_assert_eq!(scalar.sh, sse_result[2], "Sh channel does not match");
+        }
+
+        #[test]
+        fn test_sse_transform_cones(l in component(), m in component(), s in component()) {
+            let scalar = crate::utils::transform_cones([l, m, s, 0.0]);
+            let sse_result = unsafe { sse_transform_cones([l, m, s, 0.0]) };
+
+            prop_assert_eq!(scalar[0], sse_result[0], "L channel does not match");
+            prop_assert_eq!(scalar[1], sse_result[1], "M channel does not match");
+            prop_assert_eq!(scalar[2], sse_result[2], "S channel does not match");
+        }
     }
 
+    // every sRGB color, through every stage; the proptests above cover regular debug runs
     #[test]
-    fn test_sse_transform_cones() {
-        let color = [fastrand::u8(..), fastrand::u8(..), fastrand::u8(..)];
-        let lms = LMS::from(color);
-        let scalar = crate::utils::transform_cones([lms.l, lms.m, lms.s, 0.0]);
-        let sse_result = unsafe { sse_transform_cones([lms.l, lms.m, lms.s, 0.0]) };
-
-        assert_eq!(scalar[0], sse_result[0], "L channel does not match");
-        assert_eq!(scalar[1], sse_result[1], "M channel does not match");
-        assert_eq!(scalar[2], sse_result[2], "S channel does not match");
+    #[cfg_attr(debug_assertions, ignore = "run with --release")]
+    fn sse_matches_scalar_for_all_srgb() {
+        for code in 0..=0xffffffu32 {
+            let color = [(code >> 16) as u8, (code >> 8) as u8, code as u8];
+            let lrgb = LinearRGB::from(color);
+            let xyz = linear_rgb_to_xyz_scalar(&lrgb);
+            let lms = xyz_to_lms_scalar(&xyz);
+            let cones = [lms.l, lms.m, lms.s, 0.0];
+            let hpe = lms_to_hpe_scalar(&lms);
+            let (sse_xyz, sse_lms, sse_hpe, sse_cones) = unsafe {
+                (
+                    sse_xyz(&lrgb),
+                    sse_lms(&xyz),
+                    sse_hpe(&lms),
+                    sse_transform_cones(cones),
+                )
+            };
+
+            assert_eq!([xyz.x, xyz.y, xyz.z], sse_xyz[..3], "{:?}", color);
+            assert_eq!([lms.l, lms.m, lms.s], sse_lms[..3], "{:?}", color);
+            assert_eq!([hpe.lh, hpe.mh, hpe.sh], sse_hpe[..3], "{:?}", color);
+            assert_eq!(
+                crate::utils::transform_cones(cones)[..3],
+                sse_cones[..3],
+                "{:?}",
+                color
+            );
+        }
     }
 
     // #[test]
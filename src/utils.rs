@@ -1,22 +1,124 @@
 #![allow(dead_code)]
 
-use crate::consts::{self, VC};
-
-#[cfg(feature = "approximate_math")]
-#[allow(unused_imports)]
-use micromath::F32Ext;
+use crate::{Float, LinearRGB, HPE, LMS, XYZ};
 
 pub fn linearize_channel(c: u8) -> f32 {
-    let c = c as f32 / 255.0;
-    if c > 0.04045 {
-        ((c + 0.055) / 1.055).powf(2.4)
+    linearize_channel_generic(c)
+}
+
+/// [`linearize_channel`], in either precision.
+pub fn linearize_channel_generic<F: Float>(c: u8) -> F {
+    linearize(F::from_f64(c as f64) / F::from_f64(255.0))
+}
+
+/// Removes the sRGB transfer function from an encoded channel in the 0-1 range.
+/// Negative values are mirrored, as in CSS Color 4, so out-of-gamut colors survive a round trip.
+pub fn linearize<F: Float>(c: F) -> F {
+    let abs = c.abs();
+    let linear = if abs > F::from_f64(0.04045) {
+        ((abs + F::from_f64(0.055)) / F::from_f64(1.055)).powf(F::from_f64(2.4))
+    } else {
+        abs / F::from_f64(12.92)
+    };
+
+    if c < F::default() {
+        -linear
     } else {
-        c / 12.92
+        linear
+    }
+}
+
+/// Applies the sRGB transfer function to a linear channel, the inverse of [`linearize`].
+pub fn delinearize<F: Float>(c: F) -> F {
+    let abs = c.abs();
+    let encoded = if abs > F::from_f64(0.0031308) {
+        F::from_f64(1.055) * abs.powf(F::from_f64(1.0 / 2.4)) - F::from_f64(0.055)
+    } else {
+        abs * F::from_f64(12.92)
+    };
+
+    if c < F::default() {
+        -encoded
+    } else {
+        encoded
+    }
+}
+
+#[inline(always)]
+pub(crate) fn linear_rgb_to_xyz_scalar<F: Float>(rgb: &LinearRGB<F>) -> XYZ<F> {
+    let c = F::from_f64;
+    XYZ {
+        x: ((rgb.r * c(0.4124)) + (rgb.g * c(0.3576)) + (rgb.b * c(0.1805))) * c(100.0),
+        y: ((rgb.r * c(0.2126)) + (rgb.g * c(0.7152)) + (rgb.b * c(0.0722))) * c(100.0),
+        z: ((rgb.r * c(0.0193)) + (rgb.g * c(0.1192)) + (rgb.b * c(0.9505))) * c(100.0),
+    }
+}
+
+/// Inverse of [`linear_rgb_to_xyz_scalar`].
+#[inline(always)]
+pub(crate) fn xyz_to_linear_rgb_scalar<F: Float>(xyz: &XYZ<F>) -> LinearRGB<F> {
+    let c = F::from_f64;
+    let (x, y, z) = (xyz.x / c(100.0), xyz.y / c(100.0), xyz.z / c(100.0));
+    LinearRGB {
+        r: (c(3.2406254773200533) * x) - (c(1.5372079722103187) * y) - (c(0.4986285986982479) * z),
+        g: (c(-0.9689307147293194) * x) + (c(1.875756060885241) * y) + (c(0.04151752384295394) * z),
+        b: (c(0.05571012044551061) * x) - (c(0.2040210505984867) * y) + (c(1.0569959422543882) * z),
+    }
+}
+
+#[inline(always)]
+pub(crate) fn xyz_to_lms_scalar<F: Float>(xyz: &XYZ<F>) -> LMS<F> {
+    let c = F::from_f64;
+    LMS {
+        l: (c(0.7328) * xyz.x) + (c(0.4296) * xyz.y) - (c(0.1624) * xyz.z),
+        m: (c(-0.7036) * xyz.x) + (c(1.6975) * xyz.y) + (c(0.0061) * xyz.z),
+        s: (c(0.0030) * xyz.x) + (c(0.0136) * xyz.y) + (c(0.9834) * xyz.z),
+    }
+}
+
+/// Inverse of [`xyz_to_lms_scalar`].
+#[inline(always)]
+pub(crate) fn lms_to_xyz_scalar<F: Float>(lms: &LMS<F>) -> XYZ<F> {
+    let c = F::from_f64;
+    XYZ {
+        x: (c(1.0961238208355142) * lms.l) - (c(0.27886900021828726) * lms.m)
+            + (c(0.1827451793827731) * lms.s),
+        y: (c(0.45436904197535916) * lms.l)
+            + (c(0.4735331543074117) * lms.m)
+            + (c(0.07209780371722913) * lms.s),
+        z: (c(-0.009627608738429355) * lms.l) - (c(0.005698031216113421) * lms.m)
+            + (c(1.0153256399545427) * lms.s),
     }
 }
 
 #[inline(always)]
-pub(crate) fn nonlinear_adaptation(cones: [f32; 4], fl: f32) -> [f32; 4] {
+pub(crate) fn lms_to_hpe_scalar<F: Float>(lms: &LMS<F>) -> HPE<F> {
+    let c = F::from_f64;
+    HPE {
+        lh: (c(0.7409792) * lms.l) + (c(0.218025) * lms.m) + (c(0.0410058) * lms.s),
+        mh: (c(0.2853532) * lms.l) + (c(0.6242014) * lms.m) + (c(0.0904454) * lms.s),
+        sh: (c(-0.0096280) * lms.l) - (c(0.0056980) * lms.m) + (c(1.015326) * lms.s),
+    }
+}
+
+/// Inverse of [`lms_to_hpe_scalar`].
+#[inline(always)]
+pub(crate) fn hpe_to_lms_scalar<F: Float>(hpe: &HPE<F>) -> LMS<F> {
+    let c = F::from_f64;
+    LMS {
+        l: (c(1.5591519556453473) * hpe.lh)
+            - (c(0.5447222871038176) * hpe.mh)
+            - (c(0.014445260061086148) * hpe.sh),
+        m: (c(-0.7143265794813783) * hpe.lh) + (c(1.850310265486268) * hpe.mh)
+            - (c(0.13597654273909482) * hpe.sh),
+        s: (c(0.010776127252792218) * hpe.lh)
+            + (c(0.005218502936500394) * hpe.mh)
+            + (c(0.9840052620494348) * hpe.sh),
+    }
+}
+
+#[inline(always)]
+pub(crate) fn nonlinear_adaptation<F: Float>(cones: [F; 4], fl: F) -> [F; 4] {
     // #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     // {
     //     unsafe {
@@ -30,28 +132,84 @@ pub(crate) fn nonlinear_adaptation(cones: [f32; 4], fl: f32) -> [f32; 4] {
         nonlinear_adaptation_scalar(cones[0], fl),
         nonlinear_adaptation_scalar(cones[1], fl),
         nonlinear_adaptation_scalar(cones[2], fl),
-        0.0,
+        F::default(),
     ]
 }
 
+/// The post-adaptation nonlinearity, compressing an HPE cone response.
+/// It's signed, as in CIE 159:2004: negative responses, from saturated or imaginary colors,
+/// are compressed like positive ones of the same size instead of becoming NaN.
+#[inline(always)]
+pub fn nonlinear_adaptation_scalar<F: Float>(cone_response: F, fl: F) -> F {
+    let p = ((fl * cone_response.abs()) / F::from_f64(100.0)).pow_042();
+    let response = (F::from_f64(400.0) * p) / (F::from_f64(27.13) + p);
+
+    if cone_response < F::default() {
+        F::from_f64(0.1) - response
+    } else {
+        response + F::from_f64(0.1)
+    }
+}
+
+/// Inverse of [`nonlinear_adaptation_scalar`], recovering the cone response from the adapted response.
 #[inline(always)]
-pub fn nonlinear_adaptation_scalar(cone_response: f32, fl: f32) -> f32 {
-    let p = ((fl * cone_response) / 100.0).powf(0.42);
-    ((400.0 * p) / (27.13 + p)) + 0.1
+pub fn inverse_nonlinear_adaptation_scalar<F: Float>(adapted: F, fl: F) -> F {
+    let c = F::from_f64;
+    let x = adapted - c(0.1);
+    let response =
+        (c(100.0) / fl) * ((c(27.13) * x.abs()) / (c(400.0) - x.abs())).powf(c(1.0 / 0.42));
+
+    if x < F::default() {
+        -response
+    } else {
+        response
+    }
+}
+
+/// Wraps a hue angle, in degrees, into [0, 360).
+pub fn wrap_hue<F: Float>(h: F) -> F {
+    let mut h = h.to_f64() % 360.0;
+    if h < 0.0 {
+        h += 360.0;
+    }
+
+    // a tiny negative angle wraps to just under 360, which can round up to 360 itself in f32
+    let h = F::from_f64(h);
+    if h >= F::from_f64(360.0) {
+        F::default()
+    } else {
+        h
+    }
 }
 
 #[inline(always)]
-pub fn c_transform(cone: f32, d65_cone: f32) -> f32 {
-    cone * (((consts::D65_XYZ.y * VC::d) / d65_cone) + (1.0f32 - VC::d))
+pub fn c_transform<F: Float>(cone: F, d65_cone: F) -> F {
+    let vc = F::VIEWING_CONDITIONS;
+    cone * (((vc.white.y * vc.d) / d65_cone) + (F::from_f64(1.0) - vc.d))
+}
+
+#[inline(always)]
+pub fn transform_cones<F: Float>(cones: [F; 4]) -> [F; 4] {
+    let white = F::VIEWING_CONDITIONS.white_lms;
+    [
+        c_transform(cones[0], white.l),
+        c_transform(cones[1], white.m),
+        c_transform(cones[2], white.s),
+        F::default(),
+    ]
 }
 
+/// Inverse of [`transform_cones`], undoing the chromatic adaptation.
 #[inline(always)]
-pub fn transform_cones(cones: [f32; 4]) -> [f32; 4] {
+pub fn inverse_transform_cones<F: Float>(cones: [F; 4]) -> [F; 4] {
+    let vc = F::VIEWING_CONDITIONS;
+    let white = vc.white_lms;
+    let factor = |d65_cone: F| ((vc.white.y * vc.d) / d65_cone) + (F::from_f64(1.0) - vc.d);
     [
-        c_transform(cones[0], consts::D65_LMS.l),
-        c_transform(cones[1], consts::D65_LMS.m),
-        c_transform(cones[2], consts::D65_LMS.s),
-        0.0,
+        cones[0] / factor(white.l),
+        cones[1] / factor(white.m),
+        cones[2] / factor(white.s),
+        F::default(),
     ]
 }
 